@@ -0,0 +1,57 @@
+//! Registers constructors and destructors in every section `runtime.rs` runs, for checking
+//! their order in `emu`
+
+#![no_std]
+#![no_main]
+// `printf` takes `*const u8`, so the strings stay byte strings
+#![allow(clippy::manual_c_str_literals)]
+
+use sdk::printf;
+
+extern "C" fn ctor() {
+    unsafe{printf(b"ctors\n\0".as_ptr())};
+}
+
+extern "C" fn init_array_1() {
+    unsafe{printf(b"init_array 1\n\0".as_ptr())};
+}
+
+extern "C" fn init_array_2() {
+    unsafe{printf(b"init_array 2\n\0".as_ptr())};
+}
+
+extern "C" fn fini_array_1() {
+    unsafe{printf(b"fini_array 1\n\0".as_ptr())};
+}
+
+extern "C" fn fini_array_2() {
+    unsafe{printf(b"fini_array 2\n\0".as_ptr())};
+}
+
+extern "C" fn dtor_1() {
+    unsafe{printf(b"dtors 1\n\0".as_ptr())};
+}
+
+extern "C" fn dtor_2() {
+    unsafe{printf(b"dtors 2\n\0".as_ptr())};
+}
+
+// The functions of a section sit in one array each, so they are linked in the order written
+#[used]
+#[unsafe(link_section = ".ctors")]
+static CTORS: [extern "C" fn(); 1] = [ctor];
+#[used]
+#[unsafe(link_section = ".init_array")]
+static INIT_ARRAY: [extern "C" fn(); 2] = [init_array_1, init_array_2];
+#[used]
+#[unsafe(link_section = ".fini_array")]
+static FINI_ARRAY: [extern "C" fn(); 2] = [fini_array_1, fini_array_2];
+#[used]
+#[unsafe(link_section = ".dtors")]
+static DTORS: [extern "C" fn(); 2] = [dtor_1, dtor_2];
+
+/// The main routine
+#[unsafe(no_mangle)]
+pub fn main() {
+    unsafe{printf(b"main\n\0".as_ptr())};
+}
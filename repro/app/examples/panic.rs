@@ -0,0 +1,25 @@
+//! Panics two calls below `main` with an index out of bounds, for checking the report of the
+//! panic handler in `emu`
+
+#![no_std]
+#![no_main]
+
+use core::hint::black_box;
+
+use sdk as _;
+
+#[inline(never)]
+fn element(values: &[u32], idx: usize) -> u32 {
+    values[idx]
+}
+
+#[inline(never)]
+fn sum(values: &[u32], count: usize) -> u32 {
+    (0..count).map(|idx| element(values, idx)).sum()
+}
+
+/// The main routine
+#[unsafe(no_mangle)]
+pub fn main() {
+    black_box(sum(black_box(&[1, 2, 3]), black_box(4)));
+}
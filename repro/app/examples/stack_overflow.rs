@@ -0,0 +1,38 @@
+//! Recurses until its frames reach into the canary at the bottom of the stack and checks it
+//! with `sdk::stack::check`, for checking the overflow report of the `stackcheck` feature in
+//! `emu`, which has no VBlank to run the check on its own
+
+#![no_std]
+#![no_main]
+
+use core::hint::black_box;
+
+use sdk::stack::{self, CANARY_SIZE};
+
+unsafe extern "C" {
+    static __stack_guard: u32;
+}
+
+/// Returns: If `frame` starts in the canary
+fn in_canary(frame: &[u32]) -> bool {
+    (frame.as_ptr() as usize) < (&raw const __stack_guard) as usize + CANARY_SIZE
+}
+
+#[inline(never)]
+fn descend() {
+    let frame = black_box([0u32; 64]);
+    match in_canary(&frame) {
+        true  => stack::check(),
+        false => descend(),
+    }
+    // Keeps the frame alive past the call, so the recursion can't become a loop
+    black_box(&frame);
+}
+
+/// The main routine
+#[unsafe(no_mangle)]
+pub fn main() {
+    // Nothing reached the canary yet, this must not report anything
+    stack::check();
+    descend();
+}
@@ -1,5 +1,7 @@
 #![no_std]
 #![no_main]
+// The empty branches only 'use' the controller so it does not get optimized away
+#![allow(clippy::needless_ifs, clippy::extra_unused_lifetimes)]
 
 use sdk::{busy_wait, peripheral::{controller::digital_controller::{DigitalButton, DigitalController}, update_controller}};
 
@@ -28,9 +30,23 @@ fn update<'a>() {
 /// The main routine
 #[unsafe(no_mangle)]
 pub fn main() {
+    #[cfg(feature="alloc")]
+    check_heap();
+
     loop {
         update();
         update_controller();
         busy_wait(500*1000);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature="alloc")]
+extern crate alloc;
+
+/// Allocates and frees a few blocks, so a broken heap panics before the first frame
+#[cfg(feature="alloc")]
+fn check_heap() {
+    let buttons = alloc::vec![DigitalButton::Left, DigitalButton::Right, DigitalButton::Circle, DigitalButton::Start];
+    let boxed   = alloc::boxed::Box::new(core::hint::black_box(buttons.len()));
+    core::hint::black_box((buttons, boxed));
+}
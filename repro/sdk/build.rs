@@ -0,0 +1,11 @@
+use std::env;
+
+/// Sets the `host` cfg when the `host` feature is enabled or the target is not a console
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(host)");
+
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    if env::var_os("CARGO_FEATURE_HOST").is_some() || !matches!(target_arch.as_str(), "mips" | "riscv64") {
+        println!("cargo::rustc-cfg=host");
+    }
+}
@@ -0,0 +1,20 @@
+    .set push
+    .set noreorder
+    .section .text, "ax", @progbits
+    .align 2
+    .global bios_open_event
+    .type bios_open_event, @function
+
+bios_open_event:
+    li    $t2, 0xb0
+    jr    $t2
+    li    $t1, 0x08
+
+    .global bios_enable_event
+    .type bios_enable_event, @function
+
+bios_enable_event:
+    li    $t2, 0xb0
+    jr    $t2
+    li    $t1, 0x0c
+    .set pop
@@ -0,0 +1,315 @@
+//! A first-fit heap for `alloc` over the RAM between `.bss` and the stack
+//!
+//! The free blocks form a list sorted by address, each one starts with its size and the next
+//! free block. Allocated blocks carry no header, `dealloc` gets the size back from the
+//! `Layout`, and freed blocks are merged with their neighbours. With the `alloc` feature the
+//! console builds use it as `#[global_allocator]` from `__heap_start` up to `__stack_guard`
+//! of `ps-exe.ld`. Allocating from an interrupt handler is not supported.
+
+use core::{alloc::{GlobalAlloc, Layout}, cell::{Cell, UnsafeCell}, mem::{align_of, size_of}, ptr};
+
+/// A free block, the smallest unit the heap hands out
+#[repr(C)]
+struct Free {
+    size: usize,
+    next: *mut Free,
+}
+
+const UNIT: usize = size_of::<Free>();
+
+/// What the heap handed out so far
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    /// The size of the heap
+    pub size:        usize,
+    /// The bytes allocated, rounded up to whole units
+    pub used:        usize,
+    /// The most bytes allocated at once
+    pub peak:        usize,
+    /// The blocks allocated and not freed yet
+    pub allocations: usize,
+    /// The allocations that did not fit
+    pub failures:    usize,
+}
+
+/// A heap over a single range of memory
+pub struct Heap {
+    free:  *mut Free,
+    stats: Stats,
+}
+
+impl Heap {
+    /// Returns: A heap without memory, every allocation fails until `init`
+    pub const fn empty() -> Heap {
+        Heap{free: ptr::null_mut(), stats: Stats{size: 0, used: 0, peak: 0, allocations: 0, failures: 0}}
+    }
+
+    /// Hands `size` bytes from `start` on to the heap
+    ///
+    /// # Safety
+    ///
+    /// The memory has to be unused for as long as the heap lives
+    pub unsafe fn init(&mut self, start: *mut u8, size: usize) {
+        let offset = start.align_offset(UNIT);
+        let size   = size.saturating_sub(offset) & !(UNIT - 1);
+
+        *self = Heap::empty();
+        self.stats.size = size;
+        if size > 0 {
+            let block = unsafe{start.add(offset)}.cast::<Free>();
+            unsafe{block.write(Free{size, next: ptr::null_mut()})};
+            self.free = block;
+        }
+    }
+
+    /// Returns: The statistics
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Returns: The first free block fitting `layout` or null if none does
+    pub fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = Heap::unit(layout);
+
+        let Some((prev, block, start)) = self.find(size, align) else {
+            self.stats.failures += 1;
+            return ptr::null_mut();
+        };
+        let Free{size: free, next} = unsafe{block.read()};
+        let address = block as usize;
+        let front   = start - address;
+        let back    = address + free - (start + size);
+
+        let mut next = next;
+        if back > 0 {
+            let rest = block.with_addr(start + size);
+            unsafe{rest.write(Free{size: back, next})};
+            next = rest;
+        }
+        match (front > 0, prev.is_null()) {
+            (true, _)      => unsafe{block.write(Free{size: front, next})},
+            (false, true)  => self.free = next,
+            (false, false) => unsafe{(*prev).next = next},
+        }
+
+        self.stats.used        += size;
+        self.stats.peak         = self.stats.peak.max(self.stats.used);
+        self.stats.allocations += 1;
+        block.with_addr(start).cast()
+    }
+
+    /// Walks the free list for the first block `size` bytes aligned to `align` fit into
+    ///
+    /// The release builds miscompiled the walk inlined into its callers the same way as
+    /// `update_controller`, the loaded `size` was compared before the load delay was over. Out of
+    /// line and with volatile reads every field is loaded right where it is used.
+    ///
+    /// Returns: The block in front of the one found or null, the block and where the allocation
+    /// starts in it
+    #[inline(never)]
+    fn find(&self, size: usize, align: usize) -> Option<(*mut Free, *mut Free, usize)> {
+        let mut prev  = ptr::null_mut::<Free>();
+        let mut block = self.free;
+        while !block.is_null() {
+            let free    = unsafe{ptr::read_volatile(&raw const (*block).size)};
+            let address = block as usize;
+
+            // The space in front of the allocation has to hold a free block of its own
+            let mut start = address.next_multiple_of(align);
+            if start != address && start - address < UNIT {
+                start = (address + UNIT).next_multiple_of(align);
+            }
+
+            if start + size <= address + free {
+                return Some((prev, block, start));
+            }
+            prev  = block;
+            block = unsafe{ptr::read_volatile(&raw const (*block).next)};
+        }
+        None
+    }
+
+    /// Returns the block at `ptr` to the heap, merged with the free blocks around it
+    ///
+    /// # Safety
+    ///
+    /// `ptr` has to come from `allocate` of this heap with the same `layout`
+    pub unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = Heap::unit(layout);
+        let block     = ptr.cast::<Free>();
+
+        let mut prev = ptr::null_mut::<Free>();
+        let mut next = self.free;
+        while !next.is_null() && next < block {
+            prev = next;
+            next = unsafe{ptr::read_volatile(&raw const (*next).next)};
+        }
+
+        unsafe {
+            block.write(Free{size, next});
+            if !next.is_null() && block as usize + size == next as usize {
+                (*block).size += (*next).size;
+                (*block).next  = (*next).next;
+            }
+
+            match prev.is_null() {
+                true  => self.free = block,
+                false => if prev as usize + (*prev).size == block as usize {
+                    (*prev).size += (*block).size;
+                    (*prev).next  = (*block).next;
+                } else {
+                    (*prev).next = block;
+                },
+            }
+        }
+
+        self.stats.used        -= size;
+        self.stats.allocations -= 1;
+    }
+
+    /// Returns: The size and alignment of `layout` in whole units
+    fn unit(layout: Layout) -> (usize, usize) {
+        (layout.size().max(1).next_multiple_of(UNIT), layout.align().max(align_of::<Free>()).max(UNIT))
+    }
+}
+
+/// Called with the layout that did not fit and the statistics before the allocation fails
+pub type OomHook = fn(Layout, &Stats);
+
+/// The `GlobalAlloc` over a `Heap`
+pub struct Allocator {
+    heap: UnsafeCell<Heap>,
+    oom:  Cell<OomHook>,
+}
+
+// There is a single core and allocating from interrupts is not supported
+unsafe impl Sync for Allocator {}
+
+impl Allocator {
+    /// Returns: An allocator without memory that reports failures with `report`
+    pub const fn new() -> Allocator {
+        Allocator{heap: UnsafeCell::new(Heap::empty()), oom: Cell::new(report)}
+    }
+
+    /// Hands `size` bytes from `start` on to the heap, see `Heap::init`
+    ///
+    /// # Safety
+    ///
+    /// Has to be called before the first allocation, the memory has to be unused otherwise
+    pub unsafe fn init(&self, start: *mut u8, size: usize) {
+        unsafe{(*self.heap.get()).init(start, size)};
+    }
+
+    /// Returns: The statistics of the heap
+    pub fn stats(&self) -> Stats {
+        unsafe{(*self.heap.get()).stats()}
+    }
+
+    /// Calls `hook` instead of `report` when an allocation does not fit
+    pub fn set_oom_hook(&self, hook: OomHook) {
+        self.oom.set(hook);
+    }
+}
+
+impl Default for Allocator {
+    fn default() -> Allocator {
+        Allocator::new()
+    }
+}
+
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let heap  = unsafe{&mut *self.heap.get()};
+        let block = heap.allocate(layout);
+        if block.is_null() {
+            (self.oom.get())(layout, &heap.stats);
+        }
+        block
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe{(*self.heap.get()).deallocate(ptr, layout)};
+    }
+}
+
+/// Reports an allocation that does not fit over TTY, `alloc` panics after that
+pub fn report(layout: Layout, stats: &Stats) {
+    #[cfg(target_arch="mips")]
+    unsafe {
+        crate::printf(b"heap: out of memory for %d bytes, %d of %d used\n\0".as_ptr(), layout.size(), stats.used, stats.size);
+    }
+    #[cfg(not(target_arch="mips"))]
+    let _ = (layout, stats);
+}
+
+#[cfg(all(feature="alloc", target_arch="mips"))]
+#[global_allocator]
+static ALLOCATOR: Allocator = Allocator::new();
+
+/// Hands the RAM between `__heap_start` and `__stack_guard` to the global allocator
+///
+/// # Safety
+///
+/// Has to run once after `.bss` was cleared, before the first allocation
+#[cfg(all(feature="alloc", target_arch="mips"))]
+pub(crate) unsafe fn init() {
+    unsafe extern "C" {
+        static mut __heap_start:  u8;
+        static mut __stack_guard: u8;
+    }
+
+    unsafe {
+        let start = &raw mut __heap_start;
+        ALLOCATOR.init(start, (&raw mut __stack_guard).offset_from_unsigned(start));
+    }
+}
+
+/// Returns: The statistics of the global allocator
+#[cfg(all(feature="alloc", target_arch="mips"))]
+pub fn stats() -> Stats {
+    ALLOCATOR.stats()
+}
+
+/// Calls `hook` instead of `report` when an allocation of the global allocator does not fit
+#[cfg(all(feature="alloc", target_arch="mips"))]
+pub fn set_oom_hook(hook: OomHook) {
+    ALLOCATOR.set_oom_hook(hook);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(align(64))]
+    struct Memory([u8; 1024]);
+
+    #[test]
+    fn allocate_and_merge() {
+        let mut memory = Memory([0; 1024]);
+        let mut heap   = Heap::empty();
+        unsafe{heap.init(memory.0.as_mut_ptr(), 1024)};
+
+        let small = Layout::from_size_align(3, 1).unwrap();
+        let large = Layout::from_size_align(200, 8).unwrap();
+        let a = heap.allocate(small);
+        let b = heap.allocate(large);
+        let c = heap.allocate(small);
+        assert_eq!(b as usize - a as usize, UNIT);
+        assert_eq!(heap.stats(), Stats{size: 1024, used: 2*UNIT + 200usize.next_multiple_of(UNIT), peak: 2*UNIT + 200usize.next_multiple_of(UNIT), allocations: 3, failures: 0});
+
+        // The gap of `b` is reused, by the aligned block too with a free block in front of it
+        unsafe{heap.deallocate(b, large)};
+        assert_eq!(heap.allocate(small), b);
+        let aligned = heap.allocate(Layout::from_size_align(16, 64).unwrap());
+        assert!((aligned as usize).is_multiple_of(64) && aligned > b && aligned < c);
+        assert!(heap.allocate(Layout::from_size_align(1024, 1).unwrap()).is_null());
+        assert_eq!(heap.stats().failures, 1);
+
+        // Freed in any order everything merges back into one block
+        for (ptr, layout) in [(c, small), (aligned, Layout::from_size_align(16, 64).unwrap()), (a, small), (b, small)] {
+            unsafe{heap.deallocate(ptr, layout)};
+        }
+        assert_eq!((heap.stats().used, heap.stats().allocations), (0, 0));
+        assert_eq!(heap.allocate(Layout::from_size_align(1024, 1).unwrap()), memory.0.as_mut_ptr());
+    }
+}
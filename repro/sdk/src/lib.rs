@@ -1,18 +1,28 @@
-#![no_std]
-#![feature(asm_experimental_arch)]
+#![cfg_attr(not(host), no_std)]
+#![cfg_attr(target_arch="mips", feature(asm_experimental_arch))]
 #![feature(optimize_attribute)]
-#![feature(unsafe_cell_access)]
-#![feature(ptr_as_ref_unchecked)]
+// `printf` takes `*const u8`, so the strings stay byte strings
+#![allow(clippy::manual_c_str_literals)]
 
+#[cfg(any(feature="alloc", test))]
+pub mod heap;
+#[cfg(any(not(host), test))]
+pub mod panic;
 pub mod peripheral;
+#[cfg(any(feature="selftest", test))]
+pub mod selftest;
+#[cfg(any(feature="stackcheck", test))]
+pub mod stack;
 
 #[cfg(target_arch="riscv64")]
 mod riscv_startup;
+#[cfg(target_arch="mips")]
+mod runtime;
 
-use core::{arch::{asm, global_asm}, panic::PanicInfo};
+use core::arch::asm;
 
 #[cfg(target_arch="mips")]
-global_asm!(include_str!("printf.s"));
+core::arch::global_asm!(include_str!("printf.s"), record = const cfg!(feature="selftest") as u32);
 #[cfg(target_arch="mips")]
 unsafe extern "C" {
     #[link_name = "tty_printf"]
@@ -34,6 +44,21 @@ pub unsafe fn printf(mut str: *const u8) -> i32 {
     0
 }
 
+/// Writes the nul-terminated `str` to stdout when running on the host
+///
+/// # Safety
+///
+/// `str` has to point to a nul-terminated string
+#[cfg(host)]
+pub unsafe fn printf(str: *const u8) -> i32 {
+    let str = unsafe{core::ffi::CStr::from_ptr(str.cast())};
+
+    #[cfg(any(feature="selftest", test))]
+    selftest::record(str.as_ptr().cast());
+    print!("{}", str.to_string_lossy());
+    str.to_bytes().len() as i32
+}
+
 #[optimize(size)]
 pub fn busy_wait(cycles: usize) {
     for _ in 0..cycles {
@@ -42,19 +67,26 @@ pub fn busy_wait(cycles: usize) {
 }
 
 /// The entry point for every PSX application
+#[cfg(not(host))]
 #[unsafe(no_mangle)]
 extern "C" fn __startup() {
     unsafe extern "Rust" {fn main();}
 
+    #[cfg(target_arch="mips")]
+    unsafe{runtime::init()};
+
+    // Running `main` on miscompiled code would only hide what the checks found
+    #[cfg(feature="selftest")]
+    match selftest::run() {
+        0      => (),
+        failed => panic!("selftest: {failed} checks failed"),
+    }
+
     unsafe{
         main();
+        #[cfg(target_arch="mips")]
+        runtime::fini();
         printf(b"Unexpected end of main\n\0".as_ptr());
     };
     loop {};
 }
-
-#[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    loop {
-    }
-}
@@ -0,0 +1,310 @@
+//! The panic handler of the console builds
+//!
+//! It prints the location and the message of the panic over TTY, on the PSX followed by the
+//! registers of the handler and the calls leading to it, then calls the hook registered with
+//! `set_hook` or halts. There is no unwind information in the EXE, so the calls are found like
+//! a debugger without DWARF would: every function on the way starts with `addiu sp, sp, -size`
+//! and saves `ra` with `sw ra, offset(sp)`, scanning back from the return address to the
+//! prologue gives the frame of the caller. Where that fails the walk ends with an
+//! `unwind stopped` line naming the address and the reason.
+
+use core::{fmt, ops::Range};
+
+/// `addiu sp, sp, imm`, the prologue and epilogue
+const ADDIU_SP: u32 = 0x27BD_0000;
+/// `sw ra, imm(sp)`
+const SW_RA:    u32 = 0xAFBF_0000;
+/// How far back a prologue is searched for, the largest function has about 900 instructions
+const MAX_SCAN: u32 = 0x4000;
+
+/// The frame of a function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    /// The bytes the prologue reserves
+    pub size: u32,
+    /// Where `ra` is saved relative to `sp`, `None` if it is not
+    pub ra:   Option<u32>,
+}
+
+/// Why a backtrace ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum End {
+    /// The return address leaves the code, the walk returned to the BIOS
+    Entry,
+    /// There is no room for more calls
+    Full,
+    /// No prologue is in reach of `pc`
+    NoPrologue{pc: u32},
+    /// The function at `pc` does not save `ra`, it never returns
+    Unsaved{pc: u32},
+    /// The function at `pc` saves `ra` at `address`, which is not on the stack
+    OutsideStack{pc: u32, address: u32},
+}
+
+impl fmt::Display for End {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            End::Entry                     => write!(f, "returned to the BIOS"),
+            End::Full                      => write!(f, "unwind stopped: no room for more calls"),
+            End::NoPrologue{pc}            => write!(f, "unwind stopped at {pc:08x}: no prologue in reach"),
+            End::Unsaved{pc}               => write!(f, "unwind stopped at {pc:08x}: ra is not saved, the function never returns"),
+            End::OutsideStack{pc, address} => write!(f, "unwind stopped at {pc:08x}: ra is saved at {address:08x} outside of the stack"),
+        }
+    }
+}
+
+/// Finds the frame of the function executing at `pc` by scanning back to its prologue
+///
+/// Arguments:
+/// * `code`: Reads the instruction at an address
+/// * `text`: The addresses of the code
+/// * `pc`: An address in the function after its prologue
+///
+/// Returns: The `Frame` or `None` if no prologue is in reach
+pub fn frame(code: impl Fn(u32) -> u32, text: &Range<u32>, pc: u32) -> Option<Frame> {
+    let start = pc.saturating_sub(MAX_SCAN).max(text.start);
+    let mut prologue = pc & !3;
+    loop {
+        if prologue <= start {
+            return None;
+        }
+        prologue -= 4;
+
+        // The epilogue adds to `sp` again
+        let word = code(prologue);
+        if word & 0xFFFF_0000 == ADDIU_SP && word & 0x8000 != 0 {
+            break;
+        }
+    }
+
+    let size = (code(prologue) as u16 as i16).unsigned_abs() as u32;
+    let ra   = (prologue..pc).step_by(4).map(&code).find(|word| word & 0xFFFF_0000 == SW_RA).map(|word| word & 0xFFFF);
+    Some(Frame{size, ra})
+}
+
+/// Collects the calls leading to `pc`
+///
+/// Arguments:
+/// * `code`: Reads the instruction at an address
+/// * `stack`: Reads the word at an address of the stack, `None` outside of it
+/// * `text`: The addresses of the code
+/// * `pc`, `sp`, `ra`: The registers where the walk starts, `ra` is used if the function
+///   there did not save it, like functions that never return
+/// * `calls`: Receives the addresses of the calls, innermost first
+///
+/// Returns: The number of calls found and why the walk ended
+pub fn backtrace(code: impl Fn(u32) -> u32, stack: impl Fn(u32) -> Option<u32>, text: Range<u32>, (mut pc, mut sp, ra): (u32, u32, u32), calls: &mut [u32]) -> (usize, End) {
+    let mut count = 0;
+    while count < calls.len() {
+        let Some(Frame{size, ra: offset}) = frame(&code, &text, pc) else {
+            return (count, End::NoPrologue{pc});
+        };
+        let ra = match offset {
+            Some(offset)        => {
+                let address = sp.wrapping_add(offset);
+                stack(address).ok_or(End::OutsideStack{pc, address})
+            },
+            None if count == 0  => Ok(ra),
+            None                => Err(End::Unsaved{pc}),
+        };
+        let ra = match ra {
+            Ok(ra) if text.contains(&ra) && ra.is_multiple_of(4) => ra,
+            Ok(_)                                                => return (count, End::Entry),
+            Err(end)                                             => return (count, end),
+        };
+
+        // `ra` points behind the delay slot of the `jal`
+        calls[count] = ra - 8;
+        count += 1;
+        pc  = ra - 8;
+        sp  = sp.wrapping_add(size);
+    }
+    (count, End::Full)
+}
+
+#[cfg(not(host))]
+mod console {
+    use core::{cell::Cell, fmt::{self, Write}, panic::PanicInfo};
+
+    use crate::printf;
+
+    /// Called with the panic after it was reported, the console halts if it returns
+    pub type Hook = fn(&PanicInfo);
+
+    struct HookCell(Cell<Option<Hook>>);
+
+    // There is a single core and panics from interrupts halt before the hook
+    unsafe impl Sync for HookCell {}
+
+    static HOOK: HookCell = HookCell(Cell::new(None));
+
+    /// Calls `hook` after a panic was reported instead of halting right away
+    pub fn set_hook(hook: Hook) {
+        HOOK.0.set(Some(hook));
+    }
+
+    /// Writes to the TTY through `printf` in nul-terminated pieces
+    struct Tty;
+
+    impl Write for Tty {
+        fn write_str(&mut self, text: &str) -> fmt::Result {
+            let mut buffer = [0u8; 64];
+            for chunk in text.as_bytes().chunks(buffer.len() - 1) {
+                buffer[..chunk.len()].copy_from_slice(chunk);
+                buffer[chunk.len()] = 0;
+                // A `%` of the text must not reach the format string
+                #[cfg(target_arch="mips")]
+                unsafe{printf(b"%s\0".as_ptr(), buffer.as_ptr())};
+                #[cfg(target_arch="riscv64")]
+                unsafe{printf(buffer.as_ptr())};
+            }
+            Ok(())
+        }
+    }
+
+    #[panic_handler]
+    fn panic(info: &PanicInfo) -> ! {
+        // Before printing changes them
+        #[cfg(target_arch="mips")]
+        let registers = registers();
+
+        let _ = match info.location() {
+            Some(location) => writeln!(Tty, "panic at {location}: {}", info.message()),
+            None           => writeln!(Tty, "panic: {}", info.message()),
+        };
+        #[cfg(target_arch="mips")]
+        print_registers(registers);
+
+        if let Some(hook) = HOOK.0.get() {
+            hook(info);
+        }
+        loop {}
+    }
+
+    /// The registers at the start of the panic handler
+    #[cfg(target_arch="mips")]
+    #[derive(Clone, Copy)]
+    struct Registers {
+        pc:  u32,
+        ra:  u32,
+        sp:  u32,
+        epc: u32,
+    }
+
+    /// Returns: The registers of the function it is inlined into
+    #[cfg(target_arch="mips")]
+    #[inline(always)]
+    fn registers() -> Registers {
+        let (pc, ra, sp, epc): (u32, u32, u32, u32);
+        // Explicit registers, `reg` could be `at`, which the assembler wants for itself. `bal`
+        // puts the address of `1:` into `ra`, which is restored after.
+        unsafe {
+            core::arch::asm!(
+                "move  $9, $31",
+                "move  $10, $sp",
+                "mfc0  $11, $14",
+                "bal   1f",
+                "nop",
+                "1:",
+                "move  $8, $31",
+                "move  $31, $9",
+                out("$8") pc, out("$9") ra, out("$10") sp, out("$11") epc,
+            );
+        }
+        Registers{pc, ra, sp, epc}
+    }
+
+    /// Prints the last exception PC, RA and SP and the calls leading to the panic handler
+    #[cfg(target_arch="mips")]
+    fn print_registers(Registers{pc, ra, sp, epc}: Registers) {
+        use core::ptr::read_volatile;
+
+        use super::{End, backtrace};
+
+        unsafe extern "C" {
+            static __text_start: u8;
+            static __text_end:   u8;
+            static STACK_INIT:   u8;
+        }
+
+        // EPC is where the last exception was taken, the panic itself is none
+        let _ = writeln!(Tty, "last exception pc {epc:08x}, ra {ra:08x}, sp {sp:08x}");
+
+        let text  = &raw const __text_start as u32..&raw const __text_end as u32;
+        let stack = sp..&raw const STACK_INIT as u32;
+        let mut calls = [0; 16];
+        let (count, end) = backtrace(|address| unsafe{read_volatile(address as *const u32)},
+            |address| stack.contains(&address).then(|| unsafe{read_volatile(address as *const u32)}), text, (pc, sp, ra), &mut calls);
+
+        let _ = write!(Tty, "backtrace:");
+        for call in &calls[..count] {
+            let _ = write!(Tty, " {call:08x}");
+        }
+        let _ = writeln!(Tty);
+        if !matches!(end, End::Entry) {
+            let _ = writeln!(Tty, "{end}");
+        }
+    }
+}
+
+#[cfg(not(host))]
+pub use console::{Hook, set_hook};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_frames() {
+        // main calls f at 0x1010, f calls g at 0x1038, g is where the walk starts
+        let code = [
+            0x27BD_FFE8, // 1000 main: addiu sp,sp,-24
+            0xAFBF_0014, // 1004       sw    ra,20(sp)
+            0x0000_0000, // 1008
+            0x0000_0000, // 100c
+            0x0C00_0406, // 1010       jal   f
+            0x0000_0000, // 1014
+            0x27BD_0018, // 1018       addiu sp,sp,24
+            0x03E0_0008, // 101c       jr    ra
+            0x27BD_FFE0, // 1020 f:    addiu sp,sp,-32
+            0xAFB0_0018, // 1024       sw    s0,24(sp)
+            0xAFBF_001C, // 1028       sw    ra,28(sp)
+            0x0000_0000, // 102c
+            0x0000_0000, // 1030
+            0x0000_0000, // 1034
+            0x0C00_0410, // 1038       jal   g
+            0x0000_0000, // 103c
+            0x27BD_FFF0, // 1040 g:    addiu sp,sp,-16
+            0xAFBF_000C, // 1044       sw    ra,12(sp)
+            0x0000_0000, // 1048       <- pc
+        ];
+        let text  = 0x1000..0x1000 + 4*code.len() as u32;
+        let read  = |address: u32| code[(address - 0x1000) as usize/4];
+        assert_eq!(frame(read, &text, 0x1048), Some(Frame{size: 16, ra: Some(12)}));
+        assert_eq!(frame(read, &text, 0x1040), Some(Frame{size: 32, ra: Some(28)}));
+
+        // The stack of g at 0x100, f at 0x110 and main at 0x130, main was called from outside
+        let stack = |address: u32| match address {
+            0x10C => Some(0x1040),
+            0x12C => Some(0x1018),
+            0x144 => Some(0x8000),
+            _     => Some(0),
+        };
+        let mut calls = [0; 4];
+        assert_eq!(backtrace(read, stack, text.clone(), (0x1048, 0x100, 0), &mut calls), (2, End::Entry));
+        assert_eq!(calls[..2], [0x1038, 0x1010]);
+        assert_eq!(backtrace(read, stack, text.clone(), (0x1048, 0x100, 0), &mut calls[..1]), (1, End::Full));
+        assert_eq!(backtrace(read, |_| None, text.clone(), (0x1048, 0x100, 0), &mut calls), (0, End::OutsideStack{pc: 0x1048, address: 0x10C}));
+        assert_eq!(backtrace(read, stack, text.clone(), (0x1000, 0x100, 0), &mut calls), (0, End::NoPrologue{pc: 0x1000}));
+
+        // Without `sw ra` in g the walk takes `ra` from the register
+        let read = |address: u32| if address == 0x1044 {0} else {code[(address - 0x1000) as usize/4]};
+        assert_eq!(backtrace(read, stack, text.clone(), (0x1048, 0x100, 0x1040), &mut calls), (2, End::Entry));
+        assert_eq!(calls[..2], [0x1038, 0x1010]);
+
+        // Without `sw ra` in f the caller of f is lost, like below `panic_fmt` of release builds
+        let read = |address: u32| if address == 0x1028 {0} else {code[(address - 0x1000) as usize/4]};
+        assert_eq!(backtrace(read, stack, text, (0x1048, 0x100, 0), &mut calls), (1, End::Unsaved{pc: 0x1038}));
+        assert_eq!(calls[..1], [0x1038]);
+    }
+}
@@ -51,6 +51,7 @@ impl DigitalController {
 }
 
 /// The default digital button representation
+#[derive(Debug, Clone, Copy)]
 pub enum DigitalButton {
     /// The `select` button on a regular controller
     Select,
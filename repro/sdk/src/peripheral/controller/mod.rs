@@ -30,6 +30,12 @@ impl RawController {
     pub(super) fn get_state(&self) -> ControllerState {
         self.state
     }
+
+    /// Puts the controller into `state`, for checks driving the state machine from elsewhere
+    #[cfg(any(feature="selftest", test))]
+    pub(crate) fn set_state(&mut self, state: ControllerState) {
+        self.state = state;
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -117,10 +123,15 @@ impl RawButtonStates {
     }    
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The configuration a controller should be put into when it is connected
 pub struct Configuration(u8);
 
 impl Configuration {
+    const ANALOG: u8 = 1 << 0;
+    const LOCKED: u8 = 1 << 1;
+    const RUMBLE: u8 = 1 << 2;
+
     pub const fn new() -> Configuration {
         Configuration(0)
     }
@@ -129,10 +140,58 @@ impl Configuration {
     pub fn raw(&self) -> bool {
         self.0 != 0
     }
+
+    /// Returns: A copy of this `Configuration` with the analog mode set to `enabled`
+    pub const fn with_analog(self, enabled: bool) -> Configuration {
+        self.with(Self::ANALOG, enabled)
+    }
+
+    /// Returns: A copy of this `Configuration` with the analog button locked if `enabled`
+    pub const fn with_lock(self, enabled: bool) -> Configuration {
+        self.with(Self::LOCKED, enabled)
+    }
+
+    /// Returns: A copy of this `Configuration` with the rumble motors mapped if `enabled`
+    pub const fn with_rumble(self, enabled: bool) -> Configuration {
+        self.with(Self::RUMBLE, enabled)
+    }
+
+    /// Returns: `true` if the controller should be in analog mode
+    pub const fn is_analog(&self) -> bool {
+        self.0 & Self::ANALOG != 0
+    }
+
+    /// Returns: `true` if the analog button of the controller should be locked
+    pub const fn is_locked(&self) -> bool {
+        self.0 & Self::LOCKED != 0
+    }
+
+    /// Returns: `true` if the rumble motors of the controller should be mapped
+    pub const fn has_rumble(&self) -> bool {
+        self.0 & Self::RUMBLE != 0
+    }
+
+    const fn with(self, flag: u8, enabled: bool) -> Configuration {
+        if enabled {
+            Configuration(self.0 | flag)
+        }
+
+        else {
+            Configuration(self.0 & !flag)
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(super) enum ControllerState {
+impl Default for Configuration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How far a controller got in being set up, `serial_bus::protocol::Protocol::update` moves
+/// it on while the repro path leaves every controller `New`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControllerState {
     New,
 
     // This needs to be like this for the issue to occur
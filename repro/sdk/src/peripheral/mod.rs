@@ -1,8 +1,9 @@
 pub mod controller;
+pub mod serial_bus;
 mod serial_connection;
 
 use crate::{
-    peripheral::{controller::{Configuration, ControllerID, ControllerState, RawButtonStates}, serial_connection::{SerialConnection, SerialConnectionError}}, printf
+    peripheral::{controller::{Configuration, ControllerID, ControllerState, RawButtonStates}, serial_bus::SerialBus, serial_connection::{SerialConnection, SerialConnectionError}}, printf
 };
 pub use controller::RawController;
 
@@ -22,15 +23,25 @@ impl ControllerSlot {
 }
 
 
-#[allow(static_mut_refs)]
 #[inline(never)]
 pub fn update_controller() {
-    let mut serial_connection = SerialConnection::activate();
+    update_controller_on(serial_bus::PlatformSerialBus::new());
+}
+
+/// Updates the controllers like `update_controller` but over the specified `bus`
+///
+/// Arguments:
+/// * `bus`: The serial backend to talk to the controllers over
+///
+/// Returns: The `bus` after all controllers were processed
+#[allow(static_mut_refs)]
+pub fn update_controller_on<B: SerialBus>(bus: B) -> B {
+    let mut serial_connection = SerialConnection::activate(bus);
         process_port(&mut serial_connection, unsafe{&mut CONTROLLERS_A});
-    serial_connection.deactivate();
+    serial_connection.deactivate()
 }
 
-fn process_port(serial_connection: &mut SerialConnection, port_slots: &mut [ControllerSlot; CONTROLLER_SLOT_COUNT]) {
+fn process_port<B: SerialBus>(serial_connection: &mut SerialConnection<B>, port_slots: &mut [ControllerSlot; CONTROLLER_SLOT_COUNT]) {
     //>>> Working <<<
     //for idx in 0..CONTROLLER_SLOT_COUNT {
     //    let slot = &mut port_slots[idx];
@@ -44,7 +55,9 @@ fn process_port(serial_connection: &mut SerialConnection, port_slots: &mut [Cont
 }
 
 
-fn process_controller(serial_connection: &mut SerialConnection, controller: &mut Option<RawController>, configuration: &Option<Configuration>) {
+// This needs to be like this for the issue to occur
+#[allow(clippy::collapsible_if, clippy::redundant_pattern_matching)]
+fn process_controller<B: SerialBus>(serial_connection: &mut SerialConnection<B>, controller: &mut Option<RawController>, configuration: &Option<Configuration>) {
     if let Some(existing_controller) = controller {
         if let Err(_) = process_existing_controller(serial_connection, existing_controller, configuration) {
             *controller = None;
@@ -52,7 +65,8 @@ fn process_controller(serial_connection: &mut SerialConnection, controller: &mut
     }
 }
 
-fn process_existing_controller(serial_connection: &mut SerialConnection, controller: &mut RawController, configuration: &Option<Configuration>) -> Result<(), SerialConnectionError> {
+#[allow(clippy::collapsible_if)]
+fn process_existing_controller<B: SerialBus>(serial_connection: &mut SerialConnection<B>, controller: &mut RawController, configuration: &Option<Configuration>) -> Result<(), SerialConnectionError> {
     match controller.get_state() {
         ControllerState::New    => {
             if configuration.is_some() {
@@ -80,4 +94,148 @@ fn process_existing_controller(serial_connection: &mut SerialConnection, control
             Ok(())
         },
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(any(feature="selftest", test))]
+impl ControllerSlot {
+    /// Creates a slot with its controller in `state`, for checking the transitions
+    ///
+    /// Arguments:
+    /// * `state`: The state of the controller, `None` for an empty slot
+    /// * `configuration`: The configuration requested for the slot
+    ///
+    /// Returns: The new `ControllerSlot`
+    fn with_state(state: Option<ControllerState>, configuration: Option<Configuration>) -> ControllerSlot {
+        let controller = state.map(|state| {
+            let mut controller = RawController::new(ControllerID{id: 0}, RawButtonStates::new(0));
+            controller.set_state(state);
+            controller
+        });
+        ControllerSlot{controller, configuration}
+    }
+}
+
+/// Runs `update` with the first slot of port A in `state` and puts the slot back afterwards
+///
+/// Arguments:
+/// * `state`: The state of the controller in the slot, `None` for an empty slot
+/// * `configuration`: The configuration requested for the slot
+/// * `update`: Updates the controllers, like `update_controller`
+///
+/// Returns: What `update` returned and the state of the controller after it
+#[cfg(any(feature="selftest", test))]
+#[allow(static_mut_refs)]
+pub(crate) fn update_slot<R>(state: Option<ControllerState>, configuration: Option<Configuration>, update: impl FnOnce() -> R) -> (R, Option<ControllerState>) {
+    let saved  = core::mem::replace(unsafe{&mut CONTROLLERS_A[0]}, ControllerSlot::with_state(state, configuration));
+    let result = update();
+    let slot   = core::mem::replace(unsafe{&mut CONTROLLERS_A[0]}, saved);
+
+    (result, slot.controller.map(|controller| controller.get_state()))
+}
+
+/// The bytes `SerialConnection::enter_config_mode` sends
+#[cfg(any(feature="selftest", test))]
+const ENTER_CONFIG_MODE: [u8; 5] = [0; 5];
+
+#[cfg(any(feature="selftest", test))]
+const REQUESTED: Configuration = Configuration::new();
+#[cfg(any(feature="selftest", test))]
+const OTHER:     Configuration = Configuration::new().with_analog(true);
+
+/// What one update of a slot has to do
+#[cfg(any(feature="selftest", test))]
+pub(crate) enum Expected {
+    /// Print this line and send nothing
+    Printed(&'static [u8]),
+    /// Send these bytes
+    Sent(&'static [u8]),
+}
+
+/// Name, state, requested configuration and what one update has to do, the name is NUL
+/// terminated for `printf`
+#[cfg(any(feature="selftest", test))]
+pub(crate) type Transition = (&'static str, Option<ControllerState>, Option<Configuration>, Expected);
+
+#[cfg(any(feature="selftest", test))]
+macro_rules! transition {
+    ($name:ident, $state:expr, $configuration:expr, $expected:expr) => {
+        (concat!(stringify!($name), "\0"), $state, $configuration, $expected)
+    };
+}
+
+/// Every state of the slot of `process_existing_controller` with and without a requested
+/// configuration, no update may change the state. The selftest and `tests::state_transitions`
+/// both run these.
+#[cfg(any(feature="selftest", test))]
+pub(crate) const TRANSITIONS: [Transition; 8] = [
+    transition!(new_with_configuration,                Some(ControllerState::New),                     Some(REQUESTED), Expected::Printed(b"Good!\n")),
+    transition!(new_without_configuration,             Some(ControllerState::New),                     None,            Expected::Printed(b"Bad...\n")),
+    transition!(config_mode_with_the_requested_config, Some(ControllerState::InConfigMode(REQUESTED)), Some(REQUESTED), Expected::Sent(&ENTER_CONFIG_MODE)),
+    transition!(config_mode_with_a_changed_request,    Some(ControllerState::InConfigMode(REQUESTED)), Some(OTHER),     Expected::Sent(&[])),
+    transition!(config_mode_without_configuration,     Some(ControllerState::InConfigMode(REQUESTED)), None,            Expected::Sent(&ENTER_CONFIG_MODE)),
+    transition!(stable_stays_stable,                   Some(ControllerState::Stable),                  Some(REQUESTED), Expected::Sent(&[])),
+    transition!(stable_without_configuration,          Some(ControllerState::Stable),                  None,            Expected::Sent(&[])),
+    transition!(empty_slot_stays_empty,                None,                                           Some(REQUESTED), Expected::Sent(&[])),
+];
+
+#[cfg(test)]
+mod property_tests;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::peripheral::{controller::digital_controller::DigitalController, serial_bus::{HostSerialBus, MockSerialBus}};
+
+    /// Held by the tests updating `CONTROLLERS_A`
+    pub(crate) static CONTROLLERS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// Runs `process_port` once over `bus`
+    fn poll<'a>(bus: MockSerialBus<'a>, slots: &mut [ControllerSlot; CONTROLLER_SLOT_COUNT]) -> MockSerialBus<'a> {
+        let mut serial_connection = SerialConnection::activate(bus);
+        process_port(&mut serial_connection, slots);
+        serial_connection.deactivate()
+    }
+
+    fn state(slot: &ControllerSlot) -> Option<ControllerState> {
+        slot.controller.as_ref().map(|controller| controller.get_state())
+    }
+
+    #[test]
+    fn state_transitions() {
+        for (name, initial, configuration, expected) in TRANSITIONS {
+            let name      = name.trim_end_matches('\0');
+            let mut slots = [ControllerSlot::with_state(initial, configuration)];
+            let bus       = poll(MockSerialBus::new(&[]), &mut slots);
+
+            // What is printed is checked by the selftest, which reads back `printf`
+            let sent: &[u8] = match expected {
+                Expected::Printed(_) => &[],
+                Expected::Sent(sent) => sent,
+            };
+            assert_eq!(bus.sent(), sent, "{name}");
+            assert_eq!(state(&slots[0]), initial, "{name}");
+        }
+    }
+
+    #[test]
+    fn silent_port_keeps_the_slot() {
+        // Nothing answers, but `SerialConnectionError` has no variants to fail with
+        let mut slots = [ControllerSlot::with_state(Some(ControllerState::InConfigMode(Configuration::new())), None)];
+        for _ in 0..3 {
+            poll(MockSerialBus::new(&[]), &mut slots);
+        }
+        assert_eq!(state(&slots[0]), Some(ControllerState::InConfigMode(Configuration::new())));
+    }
+
+    #[test]
+    fn update_controller_on_host() {
+        let _controllers = CONTROLLERS_LOCK.lock().unwrap();
+        HostSerialBus::with_port(|port| port.plug(None));
+        update_controller();
+        update_controller();
+
+        // The slot starts out `New` with an ID of type `Unknown`
+        assert!(DigitalController::from_port_a().is_ok());
+    }
+}
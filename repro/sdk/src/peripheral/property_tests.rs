@@ -0,0 +1,234 @@
+//! Property tests for the `ControllerState` machine
+//!
+//! The repro path of `process_existing_controller` never moves a controller on, it only
+//! prints and sends, and `tests::state_transitions` covers every state of it. The invariants
+//! are checked on the driver of `serial_bus::protocol` instead: every case requests a random
+//! `Configuration` and runs `Protocol::update` over a `VirtualPort` while controllers and
+//! multitaps are plugged in and out and buttons are pressed between the updates. A failing
+//! case reports the seed it was generated from.
+
+use super::*;
+use crate::peripheral::{controller::digital_controller::DigitalButton, serial_bus::{VirtualPort, protocol::Protocol, virtual_port::{VirtualDevice, VirtualDigitalPad, VirtualDualShock, VirtualMultitap, VirtualPad}}};
+
+/// The number of generated cases
+const CASES: u64 = 2000;
+/// The most updates in a case
+const MAX_POLLS: usize = 24;
+/// The updates a controller takes from being plugged in to `Stable`, over `New` and `InConfigMode`
+const POLLS_TO_STABLE: usize = 3;
+/// The buttons pressed and released between updates
+const BUTTONS: [DigitalButton; 4] = [DigitalButton::Start, DigitalButton::Cross, DigitalButton::L1, DigitalButton::Left];
+
+/// xorshift64*, good enough to generate test cases
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // Mix the seed so neighbouring seeds don't start out alike
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn configuration(&mut self) -> Configuration {
+        Configuration::new().with_analog(self.chance(50)).with_lock(self.chance(50)).with_rumble(self.chance(50))
+    }
+
+    fn pad(&mut self) -> Pad {
+        match self.chance(50) {
+            true  => Pad::Digital,
+            false => Pad::DualShock,
+        }
+    }
+}
+
+/// A controller that can be plugged in
+#[derive(Debug, Clone, Copy)]
+enum Pad {
+    Digital,
+    DualShock,
+}
+
+impl Pad {
+    fn create(self) -> VirtualPad {
+        match self {
+            Pad::Digital   => VirtualPad::DigitalPad(VirtualDigitalPad::new()),
+            Pad::DualShock => VirtualPad::DualShock(VirtualDualShock::new()),
+        }
+    }
+}
+
+/// What happens to the port before an update
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    /// Nothing changes
+    Keep,
+    /// The device is pulled out of the port
+    Unplug,
+    /// A new controller is plugged into the port
+    Plug(Pad),
+    /// A new multitap is plugged into the port, with a controller in slot A or without
+    PlugMultitap(Option<Pad>),
+    /// The analog button of the controller is pressed, only a DualShock has one
+    PressAnalog,
+    /// A button of the controller is pressed or released
+    Button(DigitalButton, bool),
+}
+
+impl Event {
+    /// Changes the devices of `port`
+    fn apply(self, port: &mut VirtualPort) {
+        match self {
+            Event::Keep                    => {},
+            Event::Unplug                  => {
+                port.plug(None);
+            },
+            Event::Plug(pad)               => {
+                port.plug(Some(VirtualDevice::Pad(pad.create())));
+            },
+            Event::PlugMultitap(pad)       => {
+                let mut multitap = VirtualMultitap::new();
+                multitap.plug(0, pad.map(Pad::create));
+                port.plug(Some(VirtualDevice::Multitap(multitap)));
+            },
+            Event::PressAnalog             => {
+                if let Some(VirtualPad::DualShock(pad)) = pad_mut(port) {
+                    pad.press_analog_button();
+                }
+            },
+            Event::Button(button, pressed) => {
+                if let Some(pad) = pad_mut(port) {
+                    pad.set_button(button, pressed);
+                }
+            },
+        }
+    }
+}
+
+/// Returns: The controller answering to slot `0` of `port`, plugged in directly or into slot A of a multitap
+fn pad(port: &VirtualPort) -> Option<&VirtualPad> {
+    match port.device() {
+        Some(VirtualDevice::Pad(pad))           => Some(pad),
+        Some(VirtualDevice::Multitap(multitap)) => multitap.slot(0),
+        None                                    => None,
+    }
+}
+
+fn pad_mut(port: &mut VirtualPort) -> Option<&mut VirtualPad> {
+    match port.device_mut() {
+        Some(VirtualDevice::Pad(pad))           => Some(pad),
+        Some(VirtualDevice::Multitap(multitap)) => multitap.slot_mut(0),
+        None                                    => None,
+    }
+}
+
+#[derive(Debug)]
+struct Case {
+    configuration: Option<Configuration>,
+    events:        Vec<Event>,
+}
+
+impl Case {
+    fn generate(rng: &mut Rng) -> Case {
+        let configuration = match rng.chance(25) {
+            true  => None,
+            false => Some(rng.configuration()),
+        };
+        let events = (0..rng.below(MAX_POLLS) + 1).map(|_| match rng.below(100) {
+            0..40  => Event::Keep,
+            40..50 => Event::Unplug,
+            50..65 => Event::Plug(rng.pad()),
+            65..75 => Event::PlugMultitap(rng.chance(75).then(|| rng.pad())),
+            75..85 => Event::PressAnalog,
+            _      => Event::Button(BUTTONS[rng.below(BUTTONS.len())], rng.chance(50)),
+        }).collect();
+
+        Case{configuration, events}
+    }
+}
+
+/// Returns: A description of how `pad` differs from `configuration`, `None` if it matches
+fn configuration_mismatch(pad: &VirtualDualShock, configuration: Configuration) -> Option<String> {
+    let motors = match configuration.has_rumble() {
+        true  => [0x00, 0x01, 0xFF, 0xFF, 0xFF, 0xFF],
+        false => [0xFF; 6],
+    };
+
+    let matches = !pad.is_config_mode()
+        && pad.is_analog() == configuration.is_analog()
+        && pad.is_locked() == configuration.is_locked()
+        && pad.motors() == motors;
+    (!matches).then(|| format!(
+        "config mode {}, analog {}, locked {}, motors {:02x?}", pad.is_config_mode(), pad.is_analog(), pad.is_locked(), pad.motors()
+    ))
+}
+
+/// Runs `case` and returns a description of the first violated invariant
+fn check(case: &Case) -> Result<(), String> {
+    let mut protocol  = Protocol::new(VirtualPort::new(None));
+    let mut state     = None;
+    // The updates in a row a controller answered to
+    let mut connected = 0;
+
+    for (idx, event) in case.events.iter().enumerate() {
+        event.apply(protocol.bus_mut());
+        let before = state;
+        let result = protocol.update(0, &mut state, case.configuration);
+
+        // A disconnect always clears the slot
+        if pad(protocol.bus()).is_none() {
+            if result.is_ok() || state.is_some() {
+                return Err(format!("Update {idx}: nothing is plugged in, but the slot is {state:?} after {result:?}"));
+            }
+            connected = 0;
+            continue;
+        }
+
+        connected += 1;
+        if let Err(error) = result {
+            return Err(format!("Update {idx}: the controller did not answer: {error}"));
+        }
+
+        // Only a requested configuration puts a controller into config mode, and only that one
+        if let Some(ControllerState::InConfigMode(current)) = state && Some(current) != case.configuration {
+            return Err(format!("Update {idx}: in config mode with {current:?}, but {:?} was requested", case.configuration));
+        }
+
+        // A configured slot always ends up `Stable`
+        if connected >= POLLS_TO_STABLE && state != Some(ControllerState::Stable) {
+            return Err(format!("Update {idx}: still {state:?} after {connected} updates"));
+        }
+
+        // Leaving config mode means the controller was configured as requested if it can be
+        if let (Some(ControllerState::InConfigMode(requested)), Some(ControllerState::Stable)) = (before, state)
+            && let Some(VirtualPad::DualShock(dual_shock)) = pad(protocol.bus())
+            && let Some(mismatch) = configuration_mismatch(dual_shock, requested) {
+            return Err(format!("Update {idx}: {requested:?} was not applied, {mismatch}"));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn controller_state_invariants() {
+    for seed in 0..CASES {
+        let case = Case::generate(&mut Rng::new(seed));
+
+        if let Err(error) = check(&case) {
+            panic!("Seed {seed}: {error}\n{case:#?}");
+        }
+    }
+}
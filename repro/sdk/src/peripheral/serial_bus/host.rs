@@ -0,0 +1,56 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::peripheral::serial_bus::{SerialBus, SerialBusError, VirtualPort, virtual_port::{VirtualDevice, VirtualDigitalPad, VirtualPad}};
+
+/// The simulated port A, a digital pad is plugged in until something else is plugged in
+static PORT: Mutex<VirtualPort> = Mutex::new(VirtualPort::new(Some(VirtualDevice::Pad(VirtualPad::DigitalPad(VirtualDigitalPad::new())))));
+
+/// Port A of the host build, backed by a process wide `VirtualPort`
+pub struct HostSerialBus {
+}
+
+impl HostSerialBus {
+    /// Returns: The new `HostSerialBus`
+    pub fn new() -> HostSerialBus {
+        HostSerialBus{}
+    }
+
+    /// Runs `f` on the simulated port, for example to plug in devices or press buttons
+    ///
+    /// Arguments:
+    /// * `f`: The function to run
+    ///
+    /// Returns: The result of `f`
+    pub fn with_port<R>(f: impl FnOnce(&mut VirtualPort) -> R) -> R {
+        f(&mut Self::port())
+    }
+
+    fn port() -> MutexGuard<'static, VirtualPort> {
+        // The port stays usable even if a test panicked while holding it
+        PORT.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for HostSerialBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialBus for HostSerialBus {
+    fn select(&mut self) {
+        Self::port().select();
+    }
+
+    fn exchange(&mut self, byte: u8) -> Result<u8, SerialBusError> {
+        Self::port().exchange(byte)
+    }
+
+    fn wait_ack(&mut self) -> bool {
+        Self::port().wait_ack()
+    }
+
+    fn deselect(&mut self) {
+        Self::port().deselect();
+    }
+}
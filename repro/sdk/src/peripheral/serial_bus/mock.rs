@@ -0,0 +1,90 @@
+use crate::peripheral::serial_bus::{SerialBus, SerialBusError};
+
+/// How many sent bytes a `MockSerialBus` remembers
+const SENT_CAPACITY: usize = 64;
+
+/// The byte read from a port nothing drives
+const HIGH_Z: u8 = 0xFF;
+
+/// An in-memory `SerialBus` which replays scripted responses
+///
+/// Every byte exchanged while selected consumes the next scripted response and is acknowledged
+/// as long as further responses are left. Once the script is exhausted or while deselected the
+/// bus reads as high-Z. Every sent byte is recorded, selected or not.
+pub struct MockSerialBus<'a> {
+    /// The bytes to answer with, in order
+    responses:     &'a [u8],
+    /// The index of the next response to answer with
+    next_response: usize,
+    /// The bytes that were sent
+    sent:          [u8; SENT_CAPACITY],
+    /// The number of valid bytes in `sent`
+    sent_len:      usize,
+    /// `true` between `select` and `deselect`
+    selected:      bool,
+    /// The number of completed `select`/`deselect` pairs
+    transfers:     usize,
+}
+
+impl<'a> MockSerialBus<'a> {
+    /// Creates a new `MockSerialBus` answering with `responses`
+    ///
+    /// Arguments:
+    /// * `responses`: The bytes to answer with, in order
+    ///
+    /// Returns: The new `MockSerialBus`
+    pub const fn new(responses: &'a [u8]) -> MockSerialBus<'a> {
+        MockSerialBus{responses, next_response: 0, sent: [0; SENT_CAPACITY], sent_len: 0, selected: false, transfers: 0}
+    }
+
+    /// Returns: The bytes sent, up to the first 64
+    pub fn sent(&self) -> &[u8] {
+        &self.sent[..self.sent_len]
+    }
+
+    /// Returns: The number of completed transfers
+    pub fn transfers(&self) -> usize {
+        self.transfers
+    }
+
+    /// Returns: `true` if the bus is currently selected
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+}
+
+impl SerialBus for MockSerialBus<'_> {
+    fn select(&mut self) {
+        self.selected = true;
+    }
+
+    fn exchange(&mut self, byte: u8) -> Result<u8, SerialBusError> {
+        if self.sent_len < SENT_CAPACITY {
+            self.sent[self.sent_len] = byte;
+            self.sent_len += 1;
+        }
+
+        if !self.selected {
+            return Ok(HIGH_Z);
+        }
+
+        match self.responses.get(self.next_response) {
+            Some(response) => {
+                self.next_response += 1;
+                Ok(*response)
+            },
+            None => Ok(HIGH_Z),
+        }
+    }
+
+    fn wait_ack(&mut self) -> bool {
+        self.selected && self.next_response < self.responses.len()
+    }
+
+    fn deselect(&mut self) {
+        if self.selected {
+            self.selected = false;
+            self.transfers += 1;
+        }
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(host)]
+mod host;
+mod mock;
+pub mod protocol;
+pub mod virtual_port;
+
+#[cfg(target_arch="mips")]
+mod psx;
+
+#[cfg(target_arch="riscv64")]
+mod riscv;
+
+#[cfg(host)]
+pub use host::HostSerialBus;
+pub use mock::MockSerialBus;
+pub use virtual_port::VirtualPort;
+
+#[cfg(target_arch="mips")]
+pub use psx::PsxSerialBus;
+#[cfg(target_arch="mips")]
+pub(crate) use psx::init;
+
+#[cfg(target_arch="riscv64")]
+pub use riscv::RiscvSerialBus;
+
+/// The `SerialBus` used by `update_controller` on the current target
+#[cfg(target_arch="mips")]
+pub type PlatformSerialBus = PsxSerialBus;
+
+/// The `SerialBus` used by `update_controller` on the current target
+#[cfg(target_arch="riscv64")]
+pub type PlatformSerialBus = RiscvSerialBus;
+
+/// The `SerialBus` used by `update_controller` on the current target
+#[cfg(host)]
+pub type PlatformSerialBus = HostSerialBus;
+
+/// Why a byte could not be exchanged over a `SerialBus`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SerialBusError {
+    /// The port did not finish the transfer of the byte in time, nothing answers
+    NoAcknowledge,
+}
+
+impl core::fmt::Display for SerialBusError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SerialBusError::NoAcknowledge => write!(f, "the port did not finish the transfer"),
+        }
+    }
+}
+
+/// A serial backend that talks to the devices plugged into a controller port
+///
+/// A transfer always looks the same: `select`, then an `exchange` for every byte followed
+/// by a `wait_ack` as long as the device wants to continue, and finally `deselect`.
+pub trait SerialBus {
+    /// Selects the devices on the port
+    fn select(&mut self);
+
+    /// Sends `byte` to the selected device
+    ///
+    /// Arguments:
+    /// * `byte`: The byte to send
+    ///
+    /// Returns: The byte the device sent at the same time or `NoAcknowledge` if the port
+    /// did not finish the transfer in time
+    fn exchange(&mut self, byte: u8) -> Result<u8, SerialBusError>;
+
+    /// Waits for the selected device to acknowledge the last exchanged byte
+    ///
+    /// Returns: `true` if the device acknowledged the byte before timing out
+    fn wait_ack(&mut self) -> bool;
+
+    /// Deselects the devices on the port
+    fn deselect(&mut self);
+}
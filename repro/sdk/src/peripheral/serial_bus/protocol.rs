@@ -0,0 +1,343 @@
+//! The command protocol of controllers over any `SerialBus`
+//!
+//! `SerialConnection` only sends the bytes the repro needs. This is what a complete driver
+//! sends instead: `0x42` reads the ID and buttons, `0x43` enters and leaves config mode and
+//! in config mode `0x44` switches the analog mode, `0x45` reads it back and `0x4D` maps the
+//! rumble motors. The tests run it against the devices of `virtual_port`.
+
+use core::fmt;
+
+use crate::peripheral::{controller::{Configuration, ControllerState, digital_controller::DigitalButton}, serial_bus::{SerialBus, SerialBusError}};
+
+/// The first byte of every transfer addressed to the controller in the first slot
+const ADDRESS_CONTROLLER: u8 = 0x01;
+/// The byte every controller answers with after its ID
+const DATA_START: u8 = 0x5A;
+/// The most data bytes a controller answers with after `DATA_START`
+const MAX_DATA: usize = 6;
+
+const CMD_READ:         u8 = 0x42;
+const CMD_CONFIG_MODE:  u8 = 0x43;
+const CMD_SET_MODE:     u8 = 0x44;
+const CMD_GET_STATUS:   u8 = 0x45;
+const CMD_MAP_MOTORS:   u8 = 0x4D;
+
+/// Argument of `CMD_SET_MODE` locking the analog button
+const MODE_LOCK:        u8 = 0x03;
+/// Argument of `CMD_MAP_MOTORS` leaving a motor unmapped
+const MOTOR_UNMAPPED:   u8 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProtocolError {
+    /// The device stopped acknowledging before the command was sent completely
+    NoAcknowledge,
+    /// The device answered with something else than `0x5A` after its ID
+    InvalidResponse,
+}
+
+impl From<SerialBusError> for ProtocolError {
+    fn from(error: SerialBusError) -> ProtocolError {
+        match error {
+            SerialBusError::NoAcknowledge => ProtocolError::NoAcknowledge,
+        }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::NoAcknowledge   => write!(f, "the device did not acknowledge"),
+            ProtocolError::InvalidResponse => write!(f, "the device answered without 0x5A"),
+        }
+    }
+}
+
+/// The answer of a controller to a command
+#[derive(Debug)]
+pub struct Response {
+    /// The ID the controller answered with
+    id:   u8,
+    /// The data bytes following `DATA_START`
+    data: [u8; MAX_DATA],
+}
+
+impl Response {
+    /// Returns: The ID the controller answered with
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns: The raw, active low button states
+    pub fn buttons(&self) -> u16 {
+        u16::from_le_bytes([self.data[0], self.data[1]])
+    }
+
+    /// Returns: `true` if `button` was down
+    pub fn is_down(&self, button: DigitalButton) -> bool {
+        self.buttons() & (1 << button as u16) == 0
+    }
+}
+
+/// Sends the commands of the protocol over a `SerialBus`
+pub struct Protocol<B: SerialBus> {
+    /// The backend the bytes are exchanged over
+    bus: B,
+}
+
+impl<B: SerialBus> Protocol<B> {
+    /// Returns: A new `Protocol` over `bus`
+    pub const fn new(bus: B) -> Protocol<B> {
+        Protocol{bus}
+    }
+
+    /// Returns: The bus the protocol runs over
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Returns: The bus the protocol runs over
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Reads the ID and button states of the controller in `slot`
+    pub fn read(&mut self, slot: u8) -> Result<Response, ProtocolError> {
+        self.transfer(slot, CMD_READ, [0; MAX_DATA])
+    }
+
+    pub fn enter_config_mode(&mut self, slot: u8) -> Result<(), ProtocolError> {
+        self.transfer(slot, CMD_CONFIG_MODE, [0x01, 0, 0, 0, 0, 0])?;
+        Ok(())
+    }
+
+    pub fn exit_config_mode(&mut self, slot: u8) -> Result<(), ProtocolError> {
+        self.transfer(slot, CMD_CONFIG_MODE, [0x00, 0, 0, 0, 0, 0])?;
+        Ok(())
+    }
+
+    /// Switches the controller in `slot` between digital and analog mode (config mode only)
+    pub fn set_analog_mode(&mut self, slot: u8, analog: bool, locked: bool) -> Result<(), ProtocolError> {
+        let lock = if locked {MODE_LOCK} else {0};
+
+        self.transfer(slot, CMD_SET_MODE, [analog as u8, lock, 0, 0, 0, 0])?;
+        Ok(())
+    }
+
+    /// Reads whether the analog LED of the controller in `slot` is on (config mode only)
+    pub fn is_analog_mode(&mut self, slot: u8) -> Result<bool, ProtocolError> {
+        let response = self.transfer(slot, CMD_GET_STATUS, [0x5A; MAX_DATA])?;
+        Ok(response.data[2] != 0)
+    }
+
+    /// Maps the small and big rumble motor of the controller in `slot` or unmaps them (config mode only)
+    pub fn map_motors(&mut self, slot: u8, rumble: bool) -> Result<(), ProtocolError> {
+        let cmd = if rumble {
+            [0x00, 0x01, MOTOR_UNMAPPED, MOTOR_UNMAPPED, MOTOR_UNMAPPED, MOTOR_UNMAPPED]
+        }
+
+        else {
+            [MOTOR_UNMAPPED; MAX_DATA]
+        };
+
+        self.transfer(slot, CMD_MAP_MOTORS, cmd)?;
+        Ok(())
+    }
+
+    /// Puts the controller in `slot` into `configuration` and leaves config mode again
+    ///
+    /// Arguments:
+    /// * `slot`: The slot of the controller, `0` unless it is on a multitap
+    /// * `configuration`: The configuration to apply
+    ///
+    /// Returns: `true` if the mode stuck, `false` if the controller is still in config mode
+    /// and the configuration has to be tried again
+    pub fn configure(&mut self, slot: u8, configuration: Configuration) -> Result<bool, ProtocolError> {
+        self.enter_config_mode(slot)?;
+        self.set_analog_mode(slot, configuration.is_analog(), configuration.is_locked())?;
+        self.map_motors(slot, configuration.has_rumble())?;
+
+        if self.is_analog_mode(slot)? != configuration.is_analog() {
+            return Ok(false);
+        }
+        self.exit_config_mode(slot)?;
+        Ok(true)
+    }
+
+    /// Moves the controller in `slot` one step from `New` over `InConfigMode` to `Stable`
+    ///
+    /// Every update reads the controller first. A controller that does not answer clears the
+    /// slot and an empty slot that answers again starts over as `New`. Controllers without
+    /// config mode, like the digital pad, go from `InConfigMode` to `Stable` unconfigured.
+    ///
+    /// Arguments:
+    /// * `slot`: The slot of the controller, `0` unless it is on a multitap
+    /// * `state`: The state of the controller, `None` for an empty slot
+    /// * `configuration`: The configuration requested for the slot, `None` to leave the controller as it is
+    ///
+    /// Returns: The answer to the read or why the controller stopped answering
+    pub fn update(&mut self, slot: u8, state: &mut Option<ControllerState>, configuration: Option<Configuration>) -> Result<Response, ProtocolError> {
+        let response = match self.read(slot) {
+            Ok(response) => response,
+            Err(error)   => {
+                *state = None;
+                return Err(error);
+            },
+        };
+
+        *state = Some(match (*state, configuration) {
+            (None, _)                                           => ControllerState::New,
+            (Some(ControllerState::New), Some(requested))       => ControllerState::InConfigMode(requested),
+            (Some(ControllerState::New), None)                  => ControllerState::Stable,
+            (Some(ControllerState::InConfigMode(requested)), _) => match self.configure(slot, requested) {
+                Ok(false)         => ControllerState::InConfigMode(requested),
+                Ok(true) | Err(_) => ControllerState::Stable,
+            },
+            (Some(ControllerState::Stable), _)                  => ControllerState::Stable,
+        });
+        Ok(response)
+    }
+
+    fn transfer(&mut self, slot: u8, cmd: u8, args: [u8; MAX_DATA]) -> Result<Response, ProtocolError> {
+        self.bus.select();
+        let result = self.exchange_selected(slot, cmd, args);
+        self.bus.deselect();
+
+        result
+    }
+
+    fn exchange_selected(&mut self, slot: u8, cmd: u8, args: [u8; MAX_DATA]) -> Result<Response, ProtocolError> {
+        self.exchange_byte(ADDRESS_CONTROLLER + slot, true)?;
+        let id = self.exchange_byte(cmd, true)?;
+        let data_start = self.exchange_byte(0x00, true)?;
+        if data_start != DATA_START {
+            return Err(ProtocolError::InvalidResponse);
+        }
+
+        // The lower nibble of the ID is the number of halfwords following
+        let length = (((id & 0xF) as usize)*2).min(MAX_DATA);
+        let mut data = [0; MAX_DATA];
+        for (idx, instruction) in args.into_iter().take(length).enumerate() {
+            // The device does not acknowledge the last byte of a transfer
+            data[idx] = self.exchange_byte(instruction, idx + 1 < length)?;
+        }
+        Ok(Response{id, data})
+    }
+
+    fn exchange_byte(&mut self, byte: u8, needs_ack: bool) -> Result<u8, ProtocolError> {
+        let received = self.bus.exchange(byte)?;
+
+        if needs_ack && !self.bus.wait_ack() {
+            return Err(ProtocolError::NoAcknowledge);
+        }
+        Ok(received)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peripheral::serial_bus::{VirtualPort, virtual_port::{VirtualDevice, VirtualDigitalPad, VirtualDualShock, VirtualMultitap, VirtualPad}};
+
+    fn port(device: VirtualDevice) -> Protocol<VirtualPort> {
+        Protocol::new(VirtualPort::new(Some(device)))
+    }
+
+    fn digital_pad() -> VirtualDevice {
+        VirtualDevice::Pad(VirtualPad::DigitalPad(VirtualDigitalPad::new()))
+    }
+
+    fn dual_shock() -> VirtualDevice {
+        VirtualDevice::Pad(VirtualPad::DualShock(VirtualDualShock::new()))
+    }
+
+    fn dual_shock_of(protocol: &Protocol<VirtualPort>) -> &VirtualDualShock {
+        match protocol.bus().device() {
+            Some(VirtualDevice::Pad(VirtualPad::DualShock(pad))) => pad,
+            Some(VirtualDevice::Multitap(multitap))             => match multitap.slot(0) {
+                Some(VirtualPad::DualShock(pad)) => pad,
+                _                                => panic!("No DualShock in slot A"),
+            },
+            _                                                    => panic!("No DualShock plugged in"),
+        }
+    }
+
+    #[test]
+    fn buttons_are_read() {
+        let mut protocol = port(digital_pad());
+        if let Some(VirtualDevice::Pad(pad)) = protocol.bus_mut().device_mut() {
+            pad.set_button(DigitalButton::Left, true);
+            pad.set_button(DigitalButton::Circle, true);
+        }
+
+        let response = protocol.read(0).unwrap();
+        assert_eq!(response.id(), 0x41);
+        assert!(response.is_down(DigitalButton::Left));
+        assert!(response.is_down(DigitalButton::Circle));
+        assert!(!response.is_down(DigitalButton::Right));
+        assert!(!response.is_down(DigitalButton::Start));
+    }
+
+    #[test]
+    fn dual_shock_is_configured() {
+        let cases = [
+            (Configuration::new(),                                        false, false, [0xFF; 6]),
+            (Configuration::new().with_analog(true),                      true,  false, [0xFF; 6]),
+            (Configuration::new().with_analog(true).with_lock(true),      true,  true,  [0xFF; 6]),
+            (Configuration::new().with_rumble(true),                      false, false, [0x00, 0x01, 0xFF, 0xFF, 0xFF, 0xFF]),
+        ];
+
+        for (configuration, analog, locked, motors) in cases {
+            let mut protocol = port(dual_shock());
+            assert_eq!(protocol.configure(0, configuration), Ok(true), "{configuration:?}");
+
+            let pad = dual_shock_of(&protocol);
+            assert!(!pad.is_config_mode(),     "{configuration:?} left in config mode");
+            assert_eq!(pad.is_analog(), analog, "{configuration:?} analog mode");
+            assert_eq!(pad.is_locked(), locked, "{configuration:?} lock");
+            assert_eq!(pad.motors(), motors,    "{configuration:?} motors");
+        }
+    }
+
+    #[test]
+    fn analog_mode_changes_the_id() {
+        let mut protocol = port(dual_shock());
+        assert_eq!(protocol.read(0).unwrap().id(), 0x41);
+
+        protocol.configure(0, Configuration::new().with_analog(true)).unwrap();
+        assert_eq!(protocol.read(0).unwrap().id(), 0x73);
+
+        // The analog button isn't locked, so it switches back to digital mode
+        if let Some(VirtualDevice::Pad(VirtualPad::DualShock(pad))) = protocol.bus_mut().device_mut() {
+            pad.press_analog_button();
+        }
+        assert_eq!(protocol.read(0).unwrap().id(), 0x41);
+    }
+
+    #[test]
+    fn digital_pad_cant_be_configured() {
+        let mut protocol = port(digital_pad());
+
+        assert_eq!(protocol.configure(0, Configuration::new().with_analog(true)), Err(ProtocolError::NoAcknowledge));
+        assert_eq!(protocol.read(0).unwrap().id(), 0x41);
+    }
+
+    #[test]
+    fn multitap_slots_are_addressed() {
+        let mut multitap = VirtualMultitap::new();
+        multitap.plug(0, Some(VirtualPad::DualShock(VirtualDualShock::new())));
+        let mut protocol = port(VirtualDevice::Multitap(multitap));
+
+        assert_eq!(protocol.configure(0, Configuration::new().with_analog(true)), Ok(true));
+        assert!(dual_shock_of(&protocol).is_analog());
+        assert_eq!(protocol.read(1).unwrap_err(), ProtocolError::NoAcknowledge);
+    }
+
+    #[test]
+    fn unplugged_port_does_not_acknowledge() {
+        let mut protocol = port(dual_shock());
+        assert!(protocol.read(0).is_ok());
+
+        protocol.bus_mut().plug(None);
+        assert_eq!(protocol.read(0).unwrap_err(), ProtocolError::NoAcknowledge);
+    }
+}
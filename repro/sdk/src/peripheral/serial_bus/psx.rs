@@ -0,0 +1,101 @@
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::{busy_wait, peripheral::serial_bus::{SerialBus, SerialBusError}};
+
+const JOY_DATA: *mut u8  = 0x1F80_1040 as _;
+const JOY_STAT: *const u32 = 0x1F80_1044 as _;
+const JOY_MODE: *mut u16 = 0x1F80_1048 as _;
+const JOY_CTRL: *mut u16 = 0x1F80_104A as _;
+const JOY_BAUD: *mut u16 = 0x1F80_104E as _;
+
+const STAT_TX_READY:    u32 = 1 << 0;
+const STAT_RX_NOT_EMPTY: u32 = 1 << 1;
+const STAT_IRQ:         u32 = 1 << 9;
+
+const CTRL_TX_ENABLE:   u16 = 1 << 1 | 1 << 0;
+const CTRL_ACK_RESET:   u16 = 1 << 4;
+const CTRL_RESET:       u16 = 1 << 6;
+const CTRL_ACK_IRQ:     u16 = 1 << 12;
+
+/// 8 bit, no parity, 1 stop bit at a reload factor of 1
+const MODE_8N1:         u16 = 0x000D;
+/// 250 kHz
+const BAUD_250K:        u16 = 0x0088;
+
+/// How often `JOY_STAT` is polled before giving up on an acknowledge
+const ACK_TIMEOUT:      usize = 1000;
+/// How often `JOY_STAT` is polled before giving up on sending or receiving a byte, a byte
+/// takes about 32 µs at 250 kHz
+const TRANSFER_TIMEOUT: usize = 1000;
+/// Cycles the device gets to notice it was selected
+const SELECT_DELAY:     usize = 10;
+
+/// The controller and memory card port A of the PlayStation, set up once by `init`
+pub struct PsxSerialBus {
+}
+
+impl PsxSerialBus {
+    /// Returns: The new `PsxSerialBus`
+    pub fn new() -> PsxSerialBus {
+        PsxSerialBus{}
+    }
+}
+
+/// Resets and configures the serial interface for port A
+///
+/// # Safety
+///
+/// Has to run once at startup, before the first transfer
+pub(crate) unsafe fn init() {
+    unsafe {
+        write_volatile(JOY_CTRL, CTRL_RESET);
+        write_volatile(JOY_MODE, MODE_8N1);
+        write_volatile(JOY_BAUD, BAUD_250K);
+        write_volatile(JOY_CTRL, 0);
+    }
+}
+
+/// Returns: `true` once `JOY_STAT` has `flag` set, `false` if it didn't within `TRANSFER_TIMEOUT` polls
+fn wait_for(flag: u32) -> bool {
+    (0..TRANSFER_TIMEOUT).any(|_| unsafe{read_volatile(JOY_STAT)} & flag != 0)
+}
+
+impl Default for PsxSerialBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialBus for PsxSerialBus {
+    fn select(&mut self) {
+        unsafe{write_volatile(JOY_CTRL, CTRL_TX_ENABLE | CTRL_ACK_IRQ)};
+        busy_wait(SELECT_DELAY);
+    }
+
+    fn exchange(&mut self, byte: u8) -> Result<u8, SerialBusError> {
+        // An absent or unplugged port never gets ready or never answers
+        if !wait_for(STAT_TX_READY) {
+            return Err(SerialBusError::NoAcknowledge);
+        }
+        unsafe{write_volatile(JOY_DATA, byte)};
+
+        if !wait_for(STAT_RX_NOT_EMPTY) {
+            return Err(SerialBusError::NoAcknowledge);
+        }
+        Ok(unsafe{read_volatile(JOY_DATA)})
+    }
+
+    fn wait_ack(&mut self) -> bool {
+        for _ in 0..ACK_TIMEOUT {
+            if unsafe{read_volatile(JOY_STAT)} & STAT_IRQ != 0 {
+                unsafe{write_volatile(JOY_CTRL, read_volatile(JOY_CTRL) | CTRL_ACK_RESET)};
+                return true;
+            }
+        }
+        false
+    }
+
+    fn deselect(&mut self) {
+        unsafe{write_volatile(JOY_CTRL, 0)};
+    }
+}
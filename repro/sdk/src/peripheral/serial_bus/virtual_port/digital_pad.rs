@@ -0,0 +1,54 @@
+use crate::peripheral::{controller::digital_controller::DigitalButton, serial_bus::virtual_port::{ADDRESS_CONTROLLER, DATA_START, Device, FIRST_DATA, HIGH_Z, with_button}};
+
+/// The ID of a digital pad, type 4 with one halfword of data
+const ID: u8 = 0x41;
+/// The only command a digital pad understands
+const CMD_READ: u8 = 0x42;
+
+/// A simulated SCPH-1080 digital pad
+pub struct VirtualDigitalPad {
+    /// The raw, active low button states
+    buttons: u16,
+}
+
+impl VirtualDigitalPad {
+    /// Creates a new `VirtualDigitalPad` with no button pressed
+    ///
+    /// Returns: The new `VirtualDigitalPad`
+    pub const fn new() -> VirtualDigitalPad {
+        VirtualDigitalPad{buttons: 0xFFFF}
+    }
+
+    /// Presses or releases `button`
+    ///
+    /// Arguments:
+    /// * `button`: The button to change
+    /// * `pressed`: `true` to press the button, `false` to release it
+    pub fn set_button(&mut self, button: DigitalButton, pressed: bool) {
+        self.buttons = with_button(self.buttons, button, pressed);
+    }
+}
+
+impl Default for VirtualDigitalPad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for VirtualDigitalPad {
+    fn exchange(&mut self, index: usize, byte: u8) -> (u8, bool) {
+        let [low, high] = self.buttons.to_le_bytes();
+
+        match index {
+            0 => (HIGH_Z, byte == ADDRESS_CONTROLLER),
+            // The ID goes out while the command comes in, so it is answered to every command
+            1 => (ID, byte == CMD_READ),
+            2 => (DATA_START, true),
+            FIRST_DATA => (low, true),
+            _ => (high, false),
+        }
+    }
+
+    fn deselect(&mut self) {
+    }
+}
@@ -0,0 +1,177 @@
+use crate::peripheral::{controller::digital_controller::DigitalButton, serial_bus::virtual_port::{ADDRESS_CONTROLLER, DATA_START, Device, FIRST_DATA, HIGH_Z, with_button}};
+
+/// The ID in digital mode, type 4 with one halfword of data
+const ID_DIGITAL: u8 = 0x41;
+/// The ID in analog mode, type 7 with three halfwords of data
+const ID_ANALOG:  u8 = 0x73;
+/// The ID in config mode, always three halfwords of data
+const ID_CONFIG:  u8 = 0xF3;
+
+const CMD_READ:         u8 = 0x42;
+const CMD_CONFIG_MODE:  u8 = 0x43;
+const CMD_SET_MODE:     u8 = 0x44;
+const CMD_GET_STATUS:   u8 = 0x45;
+const CMD_MAP_MOTORS:   u8 = 0x4D;
+
+/// Argument of `CMD_SET_MODE` locking the analog button
+const MODE_LOCK:        u8 = 0x03;
+/// The position of a centered analog stick
+const STICK_CENTER:     u8 = 0x80;
+
+/// A simulated SCPH-1200 DualShock
+///
+/// The controller starts in digital mode. Config mode is entered and left with `0x43`,
+/// and only in config mode `0x44`, `0x45` and `0x4D` are acknowledged.
+pub struct VirtualDualShock {
+    /// The raw, active low button states
+    buttons:     u16,
+    /// The right X, right Y, left X and left Y stick positions
+    sticks:      [u8; 4],
+    /// `true` if the analog LED is on
+    analog:      bool,
+    /// `true` if the analog button is locked
+    locked:      bool,
+    /// `true` while in config mode
+    config_mode: bool,
+    /// The motor mapping set with `0x4D`
+    motors:      [u8; 6],
+    /// The command of the current transfer
+    command:     u8,
+    /// The ID answered in the current transfer
+    id:          u8,
+    /// The data bytes received in the current transfer
+    args:        [u8; 6],
+    /// The number of valid bytes in `args`
+    args_len:    usize,
+}
+
+impl VirtualDualShock {
+    /// Creates a new `VirtualDualShock` in digital mode with no button pressed
+    ///
+    /// Returns: The new `VirtualDualShock`
+    pub const fn new() -> VirtualDualShock {
+        VirtualDualShock{
+            buttons: 0xFFFF, sticks: [STICK_CENTER; 4], analog: false, locked: false, config_mode: false,
+            motors: [0xFF; 6], command: 0, id: ID_DIGITAL, args: [0; 6], args_len: 0
+        }
+    }
+
+    /// Presses or releases `button`
+    ///
+    /// Arguments:
+    /// * `button`: The button to change
+    /// * `pressed`: `true` to press the button, `false` to release it
+    pub fn set_button(&mut self, button: DigitalButton, pressed: bool) {
+        self.buttons = with_button(self.buttons, button, pressed);
+    }
+
+    /// Presses the analog button which toggles the analog mode unless it is locked
+    pub fn press_analog_button(&mut self) {
+        if !self.locked {
+            self.analog = !self.analog;
+        }
+    }
+
+    /// Returns: `true` if the analog LED is on
+    pub fn is_analog(&self) -> bool {
+        self.analog
+    }
+
+    /// Returns: `true` if the analog button is locked
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Returns: `true` while in config mode
+    pub fn is_config_mode(&self) -> bool {
+        self.config_mode
+    }
+
+    /// Returns: The motor mapping set with `0x4D`
+    pub fn motors(&self) -> [u8; 6] {
+        self.motors
+    }
+
+    fn current_id(&self) -> u8 {
+        if self.config_mode {ID_CONFIG} else if self.analog {ID_ANALOG} else {ID_DIGITAL}
+    }
+
+    fn understands(&self, command: u8) -> bool {
+        match command {
+            CMD_READ | CMD_CONFIG_MODE                  => true,
+            CMD_SET_MODE | CMD_GET_STATUS | CMD_MAP_MOTORS => self.config_mode,
+            _                                           => false,
+        }
+    }
+
+    fn data(&self, idx: usize) -> u8 {
+        let [low, high] = self.buttons.to_le_bytes();
+
+        match (self.config_mode, self.command) {
+            (false, _) | (true, CMD_READ) => match idx {
+                0 => low,
+                1 => high,
+                _ => self.sticks[idx - 2],
+            },
+            (true, CMD_GET_STATUS)        => [0x01, 0x02, self.analog as u8, 0x02, 0x01, 0x00][idx],
+            (true, CMD_MAP_MOTORS)        => self.motors[idx],
+            _                             => 0x00,
+        }
+    }
+
+    /// Applies the effects of the completed transfer
+    fn apply(&mut self) {
+        if self.args_len == 0 {
+            return;
+        }
+
+        match self.command {
+            CMD_CONFIG_MODE => self.config_mode = self.args[0] == 0x01,
+            CMD_SET_MODE if self.config_mode => {
+                self.analog = self.args[0] == 0x01;
+                self.locked = self.args_len > 1 && self.args[1] == MODE_LOCK;
+            },
+            CMD_MAP_MOTORS if self.config_mode => {
+                self.motors[..self.args_len].copy_from_slice(&self.args[..self.args_len]);
+            },
+            _ => {},
+        }
+    }
+}
+
+impl Default for VirtualDualShock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for VirtualDualShock {
+    fn exchange(&mut self, index: usize, byte: u8) -> (u8, bool) {
+        match index {
+            0 => (HIGH_Z, byte == ADDRESS_CONTROLLER),
+            1 => {
+                self.command  = byte;
+                self.id       = self.current_id();
+                self.args_len = 0;
+                (self.id, self.understands(byte))
+            },
+            2 => (DATA_START, true),
+            _ => {
+                let idx    = index - FIRST_DATA;
+                let length = ((self.id & 0xF) as usize)*2;
+                if idx >= length {
+                    return (HIGH_Z, false);
+                }
+
+                self.args[idx] = byte;
+                self.args_len  = idx + 1;
+                (self.data(idx), idx + 1 < length)
+            },
+        }
+    }
+
+    fn deselect(&mut self) {
+        self.apply();
+        self.args_len = 0;
+    }
+}
@@ -0,0 +1,287 @@
+//! Byte accurate simulations of the devices that can be plugged into a controller port
+//!
+//! Every device sees the transfer as a sequence of `exchange` calls with the index of the
+//! byte within the transfer (`0` being the address byte) and decides per byte what to
+//! answer and whether to acknowledge it. A device that did not acknowledge a byte is not
+//! listening anymore until the port is deselected.
+
+mod digital_pad;
+mod dual_shock;
+mod multitap;
+
+pub use digital_pad::VirtualDigitalPad;
+pub use dual_shock::VirtualDualShock;
+pub use multitap::{MULTITAP_SLOTS, VirtualMultitap};
+
+use crate::peripheral::{controller::digital_controller::DigitalButton, serial_bus::{SerialBus, SerialBusError}};
+
+/// The byte read from a port nothing drives
+const HIGH_Z: u8 = 0xFF;
+/// The address byte of a controller
+const ADDRESS_CONTROLLER: u8 = 0x01;
+/// The byte every controller answers with after its ID
+const DATA_START: u8 = 0x5A;
+/// The index of the first data byte within a transfer
+const FIRST_DATA: usize = 3;
+
+/// A device that answers the bytes of a transfer
+trait Device {
+    /// Answers the byte at `index` of the current transfer
+    ///
+    /// Arguments:
+    /// * `index`: The index of the byte within the transfer, `0` is the address
+    /// * `byte`: The byte sent by the console
+    ///
+    /// Returns: The answer and `true` if the device acknowledges the byte
+    fn exchange(&mut self, index: usize, byte: u8) -> (u8, bool);
+
+    /// Ends the current transfer
+    fn deselect(&mut self);
+}
+
+/// Returns: The raw, active low states with `button` pressed or released
+const fn with_button(states: u16, button: DigitalButton, pressed: bool) -> u16 {
+    let mask = 1 << button as u16;
+
+    if pressed {states & !mask} else {states | mask}
+}
+
+/// A controller that can be plugged into a port or multitap slot
+pub enum VirtualPad {
+    DigitalPad(VirtualDigitalPad),
+    DualShock(VirtualDualShock),
+}
+
+impl VirtualPad {
+    /// Presses or releases `button`
+    ///
+    /// Arguments:
+    /// * `button`: The button to change
+    /// * `pressed`: `true` to press the button, `false` to release it
+    pub fn set_button(&mut self, button: DigitalButton, pressed: bool) {
+        match self {
+            VirtualPad::DigitalPad(pad)  => pad.set_button(button, pressed),
+            VirtualPad::DualShock(pad)   => pad.set_button(button, pressed),
+        }
+    }
+}
+
+impl Device for VirtualPad {
+    fn exchange(&mut self, index: usize, byte: u8) -> (u8, bool) {
+        match self {
+            VirtualPad::DigitalPad(pad)  => pad.exchange(index, byte),
+            VirtualPad::DualShock(pad)   => pad.exchange(index, byte),
+        }
+    }
+
+    fn deselect(&mut self) {
+        match self {
+            VirtualPad::DigitalPad(pad)  => pad.deselect(),
+            VirtualPad::DualShock(pad)   => pad.deselect(),
+        }
+    }
+}
+
+/// Everything that can be plugged into a port directly
+pub enum VirtualDevice {
+    Pad(VirtualPad),
+    Multitap(VirtualMultitap),
+}
+
+impl Device for VirtualDevice {
+    fn exchange(&mut self, index: usize, byte: u8) -> (u8, bool) {
+        match self {
+            VirtualDevice::Pad(pad)           => pad.exchange(index, byte),
+            VirtualDevice::Multitap(multitap) => multitap.exchange(index, byte),
+        }
+    }
+
+    fn deselect(&mut self) {
+        match self {
+            VirtualDevice::Pad(pad)           => pad.deselect(),
+            VirtualDevice::Multitap(multitap) => multitap.deselect(),
+        }
+    }
+}
+
+/// A controller port with an optional simulated device plugged in
+pub struct VirtualPort {
+    /// The plugged in device
+    device:    Option<VirtualDevice>,
+    /// `true` between `select` and `deselect`
+    selected:  bool,
+    /// The index of the next byte within the current transfer
+    index:     usize,
+    /// `true` while the device acknowledged every byte of the current transfer
+    listening: bool,
+}
+
+impl VirtualPort {
+    /// Creates a new `VirtualPort` with `device` plugged in
+    ///
+    /// Arguments:
+    /// * `device`: The device plugged into the port, `None` for an empty port
+    ///
+    /// Returns: The new `VirtualPort`
+    pub const fn new(device: Option<VirtualDevice>) -> VirtualPort {
+        VirtualPort{device, selected: false, index: 0, listening: false}
+    }
+
+    /// Returns: The plugged in device
+    pub fn device(&self) -> Option<&VirtualDevice> {
+        self.device.as_ref()
+    }
+
+    /// Returns: The plugged in device
+    pub fn device_mut(&mut self) -> Option<&mut VirtualDevice> {
+        self.device.as_mut()
+    }
+
+    /// Plugs `device` into the port, replacing the current device
+    ///
+    /// Returns: The device that was plugged in before
+    pub fn plug(&mut self, device: Option<VirtualDevice>) -> Option<VirtualDevice> {
+        core::mem::replace(&mut self.device, device)
+    }
+}
+
+impl SerialBus for VirtualPort {
+    fn select(&mut self) {
+        self.selected  = true;
+        self.index     = 0;
+        self.listening = true;
+    }
+
+    fn exchange(&mut self, byte: u8) -> Result<u8, SerialBusError> {
+        // Without a device listening the byte is still sent, the pull-up answers
+        if !self.selected || !self.listening {
+            return Ok(HIGH_Z);
+        }
+
+        let (answer, ack) = match &mut self.device {
+            Some(device) => device.exchange(self.index, byte),
+            None         => (HIGH_Z, false),
+        };
+        self.index    += 1;
+        self.listening = ack;
+        Ok(answer)
+    }
+
+    fn wait_ack(&mut self) -> bool {
+        self.selected && self.listening
+    }
+
+    fn deselect(&mut self) {
+        if self.selected && let Some(device) = &mut self.device {
+            device.deselect();
+        }
+        self.selected = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a complete transfer of `cmd` and returns every answer and acknowledge
+    fn transfer(port: &mut VirtualPort, cmd: &[u8]) -> Vec<(u8, bool)> {
+        port.select();
+        let result = cmd.iter().map(|byte| (port.exchange(*byte).unwrap(), port.wait_ack())).collect();
+        port.deselect();
+
+        result
+    }
+
+    fn dual_shock() -> VirtualPort {
+        VirtualPort::new(Some(VirtualDevice::Pad(VirtualPad::DualShock(VirtualDualShock::new()))))
+    }
+
+    fn answers(result: &[(u8, bool)]) -> Vec<u8> {
+        result.iter().map(|(answer, _)| *answer).collect()
+    }
+
+    #[test]
+    fn empty_port_is_high_z() {
+        let mut port = VirtualPort::new(None);
+
+        assert_eq!(transfer(&mut port, &[0x01, 0x42]), [(0xFF, false), (0xFF, false)]);
+    }
+
+    #[test]
+    fn digital_pad_read() {
+        let mut pad = VirtualDigitalPad::new();
+        pad.set_button(DigitalButton::Start, true);
+        pad.set_button(DigitalButton::Cross, true);
+        let mut port = VirtualPort::new(Some(VirtualDevice::Pad(VirtualPad::DigitalPad(pad))));
+
+        assert_eq!(transfer(&mut port, &[0x01, 0x42, 0x00, 0x00, 0x00]), [
+            (0xFF, true), (0x41, true), (0x5A, true), (0xF7, true), (0xBF, false)
+        ]);
+    }
+
+    #[test]
+    fn digital_pad_ignores_config_commands() {
+        let mut port = VirtualPort::new(Some(VirtualDevice::Pad(VirtualPad::DigitalPad(VirtualDigitalPad::new()))));
+
+        assert_eq!(transfer(&mut port, &[0x01, 0x43, 0x00, 0x01, 0x00]), [
+            (0xFF, true), (0x41, false), (0xFF, false), (0xFF, false), (0xFF, false)
+        ]);
+    }
+
+    #[test]
+    fn wrong_address_is_ignored() {
+        let mut port = dual_shock();
+
+        assert_eq!(transfer(&mut port, &[0x81, 0x42]), [(0xFF, false), (0xFF, false)]);
+    }
+
+    #[test]
+    fn dual_shock_config_mode() {
+        let mut port = dual_shock();
+
+        // Enter config mode, the answer is still a regular read
+        assert_eq!(answers(&transfer(&mut port, &[0x01, 0x43, 0x00, 0x01, 0x00])), [0xFF, 0x41, 0x5A, 0xFF, 0xFF]);
+        assert_eq!(answers(&transfer(&mut port, &[0x01, 0x42, 0x00])), [0xFF, 0xF3, 0x5A]);
+
+        // Switch to analog and lock it
+        assert_eq!(transfer(&mut port, &[0x01, 0x44, 0x00, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00]).last(), Some(&(0x00, false)));
+        assert_eq!(answers(&transfer(&mut port, &[0x01, 0x45, 0x00, 0x5A, 0x5A, 0x5A, 0x5A, 0x5A, 0x5A])), [
+            0xFF, 0xF3, 0x5A, 0x01, 0x02, 0x01, 0x02, 0x01, 0x00
+        ]);
+
+        // Map the motors, the old mapping is answered
+        assert_eq!(answers(&transfer(&mut port, &[0x01, 0x4D, 0x00, 0x00, 0x01, 0xFF, 0xFF, 0xFF, 0xFF])), [
+            0xFF, 0xF3, 0x5A, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+        ]);
+        assert_eq!(answers(&transfer(&mut port, &[0x01, 0x4D, 0x00, 0x00, 0x01, 0xFF, 0xFF, 0xFF, 0xFF])), [
+            0xFF, 0xF3, 0x5A, 0x00, 0x01, 0xFF, 0xFF, 0xFF, 0xFF
+        ]);
+
+        // Leave config mode, the controller is analog now
+        assert_eq!(answers(&transfer(&mut port, &[0x01, 0x43, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])), [
+            0xFF, 0xF3, 0x5A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+        ]);
+        assert_eq!(transfer(&mut port, &[0x01, 0x42, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]), [
+            (0xFF, true), (0x73, true), (0x5A, true), (0xFF, true), (0xFF, true), (0x80, true), (0x80, true), (0x80, true), (0x80, false)
+        ]);
+    }
+
+    #[test]
+    fn dual_shock_rejects_config_commands_outside_config_mode() {
+        let mut port = dual_shock();
+
+        assert_eq!(transfer(&mut port, &[0x01, 0x44, 0x00, 0x01])[1..], [(0x41, false), (0xFF, false), (0xFF, false)]);
+    }
+
+    #[test]
+    fn multitap_forwards_slots() {
+        let mut multitap = VirtualMultitap::new();
+        multitap.plug(1, Some(VirtualPad::DigitalPad(VirtualDigitalPad::new())));
+        let mut port = VirtualPort::new(Some(VirtualDevice::Multitap(multitap)));
+
+        assert_eq!(transfer(&mut port, &[0x01, 0x42, 0x00]), [(0xFF, false), (0xFF, false), (0xFF, false)]);
+        assert_eq!(transfer(&mut port, &[0x02, 0x42, 0x00, 0x00, 0x00]), [
+            (0xFF, true), (0x41, true), (0x5A, true), (0xFF, true), (0xFF, false)
+        ]);
+    }
+}
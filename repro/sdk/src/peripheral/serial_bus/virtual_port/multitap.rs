@@ -0,0 +1,77 @@
+use crate::peripheral::serial_bus::virtual_port::{ADDRESS_CONTROLLER, Device, HIGH_Z, VirtualPad};
+
+/// The number of slots on a multitap
+pub const MULTITAP_SLOTS: usize = 4;
+
+/// A simulated SCPH-1070 multitap
+///
+/// The slots are addressed with `0x01` to `0x04` instead of `0x01`, the transfer is then
+/// forwarded to the controller in that slot as if it was plugged into the port directly.
+pub struct VirtualMultitap {
+    /// The controllers plugged into the slots
+    slots:  [Option<VirtualPad>; MULTITAP_SLOTS],
+    /// The slot the current transfer is forwarded to
+    target: Option<usize>,
+}
+
+impl VirtualMultitap {
+    /// Creates a new `VirtualMultitap` with all slots empty
+    ///
+    /// Returns: The new `VirtualMultitap`
+    pub const fn new() -> VirtualMultitap {
+        VirtualMultitap{slots: [const {None}; MULTITAP_SLOTS], target: None}
+    }
+
+    /// Plugs `pad` into `slot`, replacing the current controller
+    ///
+    /// Arguments:
+    /// * `slot`: The slot from `0` to `3`
+    /// * `pad`: The controller, `None` to leave the slot empty
+    ///
+    /// Returns: The controller that was plugged in before
+    pub fn plug(&mut self, slot: usize, pad: Option<VirtualPad>) -> Option<VirtualPad> {
+        core::mem::replace(&mut self.slots[slot], pad)
+    }
+
+    /// Returns: The controller in `slot`
+    pub fn slot(&self, slot: usize) -> Option<&VirtualPad> {
+        self.slots[slot].as_ref()
+    }
+
+    /// Returns: The controller in `slot`
+    pub fn slot_mut(&mut self, slot: usize) -> Option<&mut VirtualPad> {
+        self.slots[slot].as_mut()
+    }
+}
+
+impl Default for VirtualMultitap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for VirtualMultitap {
+    fn exchange(&mut self, index: usize, byte: u8) -> (u8, bool) {
+        if index == 0 {
+            let slot = (byte as usize).wrapping_sub(ADDRESS_CONTROLLER as usize);
+
+            self.target = (slot < MULTITAP_SLOTS).then_some(slot);
+            return match self.target.and_then(|slot| self.slots[slot].as_mut()) {
+                Some(pad) => pad.exchange(0, ADDRESS_CONTROLLER),
+                None      => (HIGH_Z, false),
+            };
+        }
+
+        match self.target.and_then(|slot| self.slots[slot].as_mut()) {
+            Some(pad) => pad.exchange(index, byte),
+            None      => (HIGH_Z, false),
+        }
+    }
+
+    fn deselect(&mut self) {
+        if let Some(pad) = self.target.and_then(|slot| self.slots[slot].as_mut()) {
+            pad.deselect();
+        }
+        self.target = None;
+    }
+}
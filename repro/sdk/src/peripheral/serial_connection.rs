@@ -1,26 +1,28 @@
-use core::{ptr::read_volatile, usize};
+use crate::peripheral::serial_bus::SerialBus;
 
 pub enum SerialConnectionError {
 }
 
-pub struct SerialConnection {
-} 
+pub struct SerialConnection<B: SerialBus> {
+    /// The backend the bytes are exchanged over
+    bus: B,
+}
 
-impl SerialConnection {
-    pub fn activate() -> Self {
-        let serial_connection = SerialConnection{};
-        serial_connection
+impl<B: SerialBus> SerialConnection<B> {
+    pub fn activate(bus: B) -> Self {
+        SerialConnection{bus}
     }
 
-    pub fn deactivate(self) {
+    pub fn deactivate(self) -> B {
+        self.bus
     }
 
-    pub fn enter_config_mode(&self) -> Result<(), SerialConnectionError> {
+    pub fn enter_config_mode(&mut self) -> Result<(), SerialConnectionError> {
         self.send_cmd_seq_impl([0, 0, 0x0, 0x0, 0x00])?;
         Ok(())
     }
 
-    fn send_cmd_seq_impl<const N: usize>(&self, cmd: [u8; N]) -> Result<[u8; N], SerialConnectionError> {
+    fn send_cmd_seq_impl<const N: usize>(&mut self, cmd: [u8; N]) -> Result<[u8; N], SerialConnectionError> {
         let mut result = [0; N];
 
         for (idx, instruction) in cmd.into_iter().enumerate() {
@@ -29,12 +31,10 @@ impl SerialConnection {
         Ok(result)
     }
 
-    fn exchange_byte(&self, _byte: u8) -> u8 {
-        // Actual serial IO port, but not configured. Should return 255 always.
-        #[cfg(target_arch="riscv64")]
-        unsafe{read_volatile(0x1000_0000 as *const u8)}
-
-        #[cfg(target_arch="mips")]
-        unsafe{read_volatile(0x1F801040 as *const u8)}
+    fn exchange_byte(&mut self, byte: u8) -> u8 {
+        // The port is set up at startup, but on purpose never selected: the repro only needs the
+        // volatile transfer, selecting would address a real pad and change the code around it.
+        // Nothing answers on an unselected port, every byte reads as 255.
+        self.bus.exchange(byte).unwrap_or(0xFF)
     }
 }
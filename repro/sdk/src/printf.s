@@ -6,6 +6,11 @@
     .type tty_printf, @function
 
 tty_printf:
+.if {record}
+    /* The selftest reads what the controller code printed from `__tty_last` */
+    lui   $t2, %hi(__tty_last)
+    sw    $a0, %lo(__tty_last)($t2)
+.endif
     li    $t2, 0xa0
     jr    $t2
     li    $t1, 0x3f
\ No newline at end of file
@@ -0,0 +1,92 @@
+//! Sets up the memory `ps-exe.ld` lays out before `main` and tears it down after
+//!
+//! The BIOS only copies the text segment of the EXE into the RAM, `.bss` is whatever the RAM
+//! held before. Constructors and destructors are the function pointers `ld.lld` collects in
+//! `.ctors`, `.init_array`, `.fini_array` and `.dtors` between the symbols of the linker
+//! script. The constructors run in the order they were linked, the destructors the other way
+//! around.
+
+use core::ptr;
+
+type Function = extern "C" fn();
+
+unsafe extern "C" {
+    static mut __bss_start:    u8;
+    static mut __bss_end:      u8;
+    static mut __data_start:   u8;
+    static __data_end:         u8;
+    static __data_load:        u8;
+    static __ctors_start:      Function;
+    static __ctors_end:        Function;
+    static __init_array_start: Function;
+    static __init_array_end:   Function;
+    static __fini_array_start: Function;
+    static __fini_array_end:   Function;
+    static __dtors_start:      Function;
+    static __dtors_end:        Function;
+}
+
+/// Clears `.bss`, copies `.data` to its address if it was loaded elsewhere, sets up the
+/// controller port, the heap and the stack checks of the `alloc` and `stackcheck` features and
+/// runs the constructors in the order they were linked
+///
+/// # Safety
+///
+/// Has to run once before anything reads a static
+pub(crate) unsafe fn init() {
+    unsafe {
+        let bss_start = &raw mut __bss_start;
+        ptr::write_bytes(bss_start, 0, (&raw mut __bss_end).offset_from_unsigned(bss_start));
+
+        // A PS-X EXE is loaded as a whole, only an image in ROM keeps `.data` apart. LLVM takes
+        // two statics for two objects and would always copy, the linker script says otherwise.
+        let data_start = &raw mut __data_start;
+        let data_load  = core::hint::black_box(&raw const __data_load);
+        if !ptr::eq(data_start, data_load) {
+            ptr::copy(data_load, data_start, (&raw const __data_end).offset_from_unsigned(data_start));
+        }
+
+        crate::peripheral::serial_bus::init();
+        #[cfg(feature="alloc")]
+        crate::heap::init();
+        #[cfg(feature="stackcheck")]
+        crate::stack::init();
+
+        run(&raw const __ctors_start, &raw const __ctors_end);
+        run(&raw const __init_array_start, &raw const __init_array_end);
+    }
+}
+
+/// Runs the destructors in the reverse order they were linked
+///
+/// # Safety
+///
+/// Has to run once after `main` returned
+pub(crate) unsafe fn fini() {
+    unsafe {
+        run_reverse(&raw const __fini_array_start, &raw const __fini_array_end);
+        run_reverse(&raw const __dtors_start, &raw const __dtors_end);
+    }
+}
+
+/// Calls the functions from `start` up to `end`
+unsafe fn run(start: *const Function, end: *const Function) {
+    let mut function = start;
+    while function < end {
+        unsafe {
+            (*function)();
+            function = function.add(1);
+        }
+    }
+}
+
+/// Calls the functions from `end` down to `start`
+unsafe fn run_reverse(start: *const Function, end: *const Function) {
+    let mut function = end;
+    while function > start {
+        unsafe {
+            function = function.sub(1);
+            (*function)();
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Invariant checks for code generation the controller handling depends on
+//!
+//! Every check puts the slot of port A into a `ControllerState` of `peripheral::TRANSITIONS`,
+//! runs the real controller code and compares what it did to what it has to do.
+//! `ControllerState::New` only prints, so its checks run `update_controller` as it ships and
+//! read back what `tty_printf` was called with. The other states talk to the port, their
+//! checks run `update_controller_on` over a silent `MockSerialBus` and compare the bytes sent.
+//! No check may change the state. With the `selftest` feature the checks run in `__startup`
+//! before `main`, which halts if one fails.
+
+use core::ffi::CStr;
+
+use crate::{peripheral::{Expected, TRANSITIONS, serial_bus::MockSerialBus, update_controller, update_controller_on, update_slot}, printf};
+
+/// The format string of the last `printf`, stored by `tty_printf` of `printf.s`
+#[cfg(target_arch="mips")]
+#[unsafe(no_mangle)]
+#[allow(non_upper_case_globals)]
+static mut __tty_last: *const u8 = core::ptr::null();
+
+#[cfg(host)]
+std::thread_local! {
+    /// The format string of the last `printf` on this thread
+    static TTY_LAST: core::cell::Cell<*const u8> = const {core::cell::Cell::new(core::ptr::null())};
+}
+
+/// Remembers `str` as the format string of the last `printf`
+#[cfg(host)]
+pub(crate) fn record(str: *const u8) {
+    TTY_LAST.with(|last| last.set(str));
+}
+
+/// Returns: The format string of the last `printf` and forgets it
+fn take_printed() -> &'static [u8] {
+    #[cfg(target_arch="mips")]
+    let str = unsafe{core::ptr::replace(&raw mut __tty_last, core::ptr::null())};
+    #[cfg(host)]
+    let str = TTY_LAST.with(|last| last.replace(core::ptr::null()));
+    #[cfg(target_arch="riscv64")]
+    let str = core::ptr::null::<u8>();
+
+    match str.is_null() {
+        true  => b"",
+        false => unsafe{CStr::from_ptr(str.cast())}.to_bytes(),
+    }
+}
+
+/// Runs all checks and reports every failing one over TTY
+///
+/// Returns: The number of failed checks
+pub fn run() -> usize {
+    let mut failed = 0;
+
+    for (name, state, configuration, expected) in TRANSITIONS {
+        let passed = match expected {
+            Expected::Printed(line) => {
+                take_printed();
+                let ((), after) = update_slot(state, configuration, update_controller);
+                take_printed() == line && after == state
+            },
+            Expected::Sent(bytes)   => {
+                let (bus, after) = update_slot(state, configuration, || update_controller_on(MockSerialBus::new(&[])));
+                bus.sent() == bytes && after == state
+            },
+        };
+
+        if !passed {
+            unsafe {
+                printf(b"selftest: \0".as_ptr());
+                printf(name.as_ptr());
+                printf(b" failed\n\0".as_ptr());
+            }
+            failed += 1;
+        }
+    }
+
+    if failed == 0 {
+        unsafe{printf(b"selftest: passed\n\0".as_ptr())};
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn all_checks_pass() {
+        let _controllers = crate::peripheral::tests::CONTROLLERS_LOCK.lock().unwrap();
+        assert_eq!(super::run(), 0);
+    }
+}
@@ -0,0 +1,169 @@
+//! Stack overflow detection with a canary at the bottom of the stack
+//!
+//! The stack grows down from `STACK_INIT` to `__stack_guard` of `ps-exe.ld`, below it are the
+//! heap and `.bss`. With the `stackcheck` feature `__startup` paints the free stack with
+//! `PAINT`, and the lowest `CANARY_SIZE` bytes are the canary. A handler of the VBlank event
+//! of the BIOS checks them and the stack pointer of the interrupted code 60 times a second and
+//! halts with the PC of that code over TTY once either reached the canary, instead of letting
+//! the stack corrupt what is below. How deep the stack went is where the paint ends.
+
+use core::ptr::read_volatile;
+
+/// What the free stack is filled with
+pub const PAINT: u32 = 0x5AC3_5AC3;
+/// The bytes at the bottom of the stack that have to keep their paint
+pub const CANARY_SIZE: usize = 256;
+
+/// Returns: If the canary at `bottom` still holds the paint
+///
+/// # Safety
+///
+/// `bottom` has to point to at least `CANARY_SIZE` readable bytes
+pub unsafe fn intact(bottom: *const u32) -> bool {
+    (0..CANARY_SIZE/4).all(|idx| unsafe{read_volatile(bottom.add(idx))} == PAINT)
+}
+
+/// Returns: The bytes of the `words` of stack from `bottom` on that lost their paint, counted
+/// from the lowest one up to the top
+///
+/// # Safety
+///
+/// `bottom` has to point to `words` readable words
+pub unsafe fn used(bottom: *const u32, words: usize) -> usize {
+    (0..words).find(|idx| unsafe{read_volatile(bottom.add(*idx))} != PAINT).map_or(0, |idx| (words - idx)*4)
+}
+
+#[cfg(all(feature="stackcheck", target_arch="mips"))]
+core::arch::global_asm!(include_str!("events.s"));
+
+#[cfg(all(feature="stackcheck", target_arch="mips"))]
+mod console {
+    use core::{arch::asm, ptr::{read_volatile, write_volatile}};
+
+    use crate::printf;
+    use super::{CANARY_SIZE, PAINT, intact, used};
+
+    /// The class, spec and mode of the event `OpenEvent` gets for a handler called on VBlank
+    const VBLANK:     u32 = 0xF200_0003;
+    const EV_SP_INT:  u32 = 0x0002;
+    const EV_MD_INTR: u32 = 0x1000;
+
+    const I_MASK:  *mut u32 = 0x1F80_1074 as *mut u32;
+    /// The kernel table entry pointing to the process control block, whose first word points
+    /// to the thread control block of the current thread
+    const PROCESS: *const *const *const u32 = 0x8000_0108 as *const *const *const u32;
+    /// Where the exception handler of the BIOS saves `sp` and `epc` in the thread control block
+    const TCB_SP:  usize = 0x08 + 29*4;
+    const TCB_EPC: usize = 0x88;
+
+    unsafe extern "C" {
+        static __stack_guard: u32;
+        static STACK_INIT:    u32;
+        fn bios_open_event(class: u32, spec: u32, mode: u32, handler: extern "C" fn()) -> u32;
+        fn bios_enable_event(event: u32) -> u32;
+    }
+
+    fn bottom() -> *const u32 {
+        &raw const __stack_guard
+    }
+
+    fn words() -> usize {
+        unsafe{(&raw const STACK_INIT).offset_from_unsigned(bottom())}
+    }
+
+    /// Paints the stack below the stack pointer and checks it on every VBlank
+    ///
+    /// # Safety
+    ///
+    /// Has to run once at startup, before interrupts are enabled
+    pub(crate) unsafe fn init() {
+        unsafe {
+            // A loop in Rust would call `write_volatile` in debug builds and paint over its
+            // frame. Explicit registers, `reg` could be `at`, which the assembler wants for itself.
+            asm!(
+                "1:",
+                "sw    $9, 0($8)",
+                "addiu $8, $8, 4",
+                "bne   $8, $sp, 1b",
+                inout("$8") bottom() => _,
+                in("$9") PAINT,
+            );
+
+            let event = bios_open_event(VBLANK, EV_SP_INT, EV_MD_INTR, on_vblank);
+            bios_enable_event(event);
+            write_volatile(I_MASK, read_volatile(I_MASK) | 1);
+
+            // Like `ExitCriticalSection`: the current interrupt enable and the mask of the
+            // interrupt controller in the status register, `mfc0` has a load delay
+            asm!("mfc0 $8, $12", "nop", "ori $8, $8, 0x401", "mtc0 $8, $12", out("$8") _);
+        }
+    }
+
+    /// Checks the canary and the stack pointer of the code the VBlank interrupted
+    extern "C" fn on_vblank() {
+        unsafe {
+            let tcb = read_volatile(read_volatile(PROCESS));
+            let sp  = tcb.byte_add(TCB_SP).read_volatile();
+            let epc = tcb.byte_add(TCB_EPC).read_volatile();
+            if !intact(bottom()) || (sp as usize) < bottom() as usize + CANARY_SIZE {
+                overflow(epc, sp);
+            }
+        }
+    }
+
+    /// Reports the overflow at `pc` and halts
+    fn overflow(pc: u32, sp: u32) -> ! {
+        unsafe {
+            printf(b"stack: overflow at pc %08x, sp %08x below %08x\n\0".as_ptr(), pc, sp, bottom() as usize + CANARY_SIZE);
+        }
+        loop {}
+    }
+
+    /// Returns: The most bytes of stack used so far
+    pub fn high_water_mark() -> usize {
+        unsafe{used(bottom(), words())}
+    }
+
+    /// Returns: The size of the stack, `STACK_SIZE` of `ps-exe.ld`
+    pub fn size() -> usize {
+        words()*4
+    }
+
+    /// Checks the canary right away instead of waiting for the VBlank, reporting an overflow at
+    /// the caller
+    #[inline(never)]
+    pub fn check() {
+        let (ra, sp): (u32, u32);
+        unsafe {
+            asm!("move $8, $ra", "move $9, $sp", out("$8") ra, out("$9") sp);
+            if !intact(bottom()) {
+                overflow(ra, sp);
+            }
+        }
+    }
+}
+
+#[cfg(all(feature="stackcheck", target_arch="mips"))]
+pub(crate) use console::init;
+#[cfg(all(feature="stackcheck", target_arch="mips"))]
+pub use console::{check, high_water_mark, size};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint() {
+        let mut stack = [PAINT; 256];
+        assert!(unsafe{intact(stack.as_ptr())});
+        assert_eq!(unsafe{used(stack.as_ptr(), stack.len())}, 0);
+
+        stack[200..].fill(0);
+        stack[100] = 0;
+        assert_eq!(unsafe{used(stack.as_ptr(), stack.len())}, 156*4);
+
+        stack[CANARY_SIZE/4 - 1] = 0;
+        assert!(!unsafe{intact(stack.as_ptr())});
+        assert_eq!(unsafe{used(stack.as_ptr(), stack.len())}, (256 - CANARY_SIZE/4 + 1)*4);
+    }
+}
@@ -0,0 +1,238 @@
+//! Checks a PS-X EXE right after the build and sums it up in a JSON report
+//!
+//! The checks are the ones of the other tools in one pass: the header as `psxexe --check`
+//! validates it, the load delay hazards `disasm` finds in `.text` and the RAM `mapsize` sees
+//! used up to the end of `.bss`. The report is for CI and scripts, so it is written by hand
+//! instead of pulling in a JSON library for the tools.
+
+use std::fmt::Write;
+
+use disasm::{disassemble, map::Map};
+use hazard::{Hazard, find_hazards};
+use mapsize::Usage;
+use psxexe::{Exe, Header};
+
+/// The target of the console builds
+pub const TARGET: &str = "mipsel-sony-psx";
+/// The alias of `.cargo/config.toml` that builds, it is the only place of the `-Zbuild-std`
+/// flags and the target
+pub const ALIAS: &str = "psx_build";
+
+/// Returns: The cargo profile `args` build with, `dev` without `--release` or `--profile`
+pub fn profile(args: &[String]) -> String {
+    let named = args.iter().enumerate().find_map(|(idx, arg)| match arg.strip_prefix("--profile") {
+        Some("")      => args.get(idx + 1).cloned(),
+        Some(profile) => profile.strip_prefix('=').map(str::to_owned),
+        None          => None,
+    });
+    match named {
+        Some(profile)                                     => profile,
+        None if args.iter().any(|arg| arg == "--release") => "release".to_owned(),
+        None                                              => "dev".to_owned(),
+    }
+}
+
+/// Returns: The directory below the target directory cargo writes `profile` to
+pub fn profile_dir(profile: &str) -> &str {
+    // The built-in profiles keep the directories of before named profiles
+    match profile {
+        "dev" | "test" => "debug",
+        "bench"        => "release",
+        profile        => profile,
+    }
+}
+
+/// A load delay hazard in the EXE
+#[derive(Debug, Clone)]
+pub struct Site {
+    /// The address of the instruction reading the loaded register
+    pub address: u32,
+    /// The load and the instruction reading the register
+    pub hazard:  Hazard,
+}
+
+/// What the checks found in an EXE
+#[derive(Debug, Default)]
+pub struct Checks {
+    /// The header, `None` if the file has none
+    pub header:  Option<Header>,
+    /// What is wrong with the header
+    pub errors:  Vec<String>,
+    /// The load delay hazards in `.text`
+    pub hazards: Vec<Site>,
+    /// The sizes of the sections and the RAM used
+    pub usage:   Usage,
+}
+
+impl Checks {
+    /// Returns: If the EXE boots, has no hazards and fits into the RAM
+    pub fn ok(&self) -> bool {
+        self.header.is_some() && self.errors.is_empty() && self.hazards.is_empty() && self.usage.ram.is_none_or(|ram| ram.used <= ram.size)
+    }
+}
+
+/// Checks the EXE `file` linked with the map `map`
+///
+/// Arguments:
+/// * `file`: The content of the PS-X EXE
+/// * `map`: The map of the link, can be empty
+///
+/// Returns: The `Checks`
+pub fn check(file: &[u8], map: &Map) -> Checks {
+    let mut checks = Checks{usage: Usage::new(map), ..Checks::default()};
+    let header = match Header::parse(file) {
+        Ok(header) => header,
+        Err(error) => {
+            checks.errors.push(error.to_string());
+            return checks;
+        },
+    };
+    checks.errors.extend(header.validate(file.len()).iter().map(ToString::to_string));
+    checks.header = Some(header);
+
+    // A text segment past the end of the file is already one of the errors
+    let Ok(exe) = Exe::parse(file) else {
+        return checks;
+    };
+    // Only `.text` is code, the rest of the segment is `.data`
+    let end = match map.section(".text") {
+        Some(text) if text.address == exe.header.load_addr => text.address.wrapping_add(text.size),
+        _                                                  => exe.header.load_addr.wrapping_add(exe.text.len() as u32),
+    };
+
+    let listing = disassemble(&exe, map, end);
+    checks.hazards = find_hazards(&listing).into_iter().map(|hazard| {
+        Site{address: listing.instructions[hazard.line - 1].address.unwrap_or_default(), hazard}
+    }).collect();
+    checks
+}
+
+/// The outcome of a build
+#[derive(Debug)]
+pub struct Report {
+    /// The path of the EXE
+    pub exe:     String,
+    /// The cargo profile, like `release`
+    pub profile: String,
+    /// If cargo succeeded
+    pub built:   bool,
+    /// How long cargo took
+    pub seconds: f64,
+    /// What the checks found, `None` if the build failed
+    pub checks:  Option<Checks>,
+}
+
+impl Report {
+    /// Returns: If the build succeeded and the checks found nothing
+    pub fn ok(&self) -> bool {
+        self.built && self.checks.as_ref().is_some_and(Checks::ok)
+    }
+
+    /// Returns: The report as a JSON object, addresses are hex strings
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n");
+        let _ = writeln!(json, "  \"ok\": {},", self.ok());
+        let _ = writeln!(json, "  \"exe\": {},", string(&self.exe));
+        let _ = writeln!(json, "  \"profile\": {},", string(&self.profile));
+        let _ = write!(json, "  \"build\": {{\"success\": {}, \"seconds\": {:.2}}}", self.built, self.seconds);
+
+        if let Some(checks) = &self.checks {
+            json.push_str(",\n  \"header\": ");
+            match &checks.header {
+                Some(header) => {
+                    let region = header.region().map_or("null".to_owned(), |region| string(&region.to_string()));
+                    let _ = write!(json, "{{\"pc\": \"{:08x}\", \"gp\": \"{:08x}\", \"load_address\": \"{:08x}\", \"size\": {}, \"stack\": \"{:08x}\", \"region\": {region}, \"errors\": {}}}",
+                        header.pc, header.gp, header.load_addr, header.size, header.stack.wrapping_add(header.stack_offset), array(checks.errors.iter().map(|error| string(error))));
+                },
+                None         => {
+                    let _ = write!(json, "{{\"errors\": {}}}", array(checks.errors.iter().map(|error| string(error))));
+                },
+            }
+
+            json.push_str(",\n  \"hazards\": ");
+            json.push_str(&array(checks.hazards.iter().map(|site| {
+                format!("{{\"address\": \"{:08x}\", \"function\": {}, \"load\": {}, \"user\": {}, \"register\": {}}}",
+                    site.address, string(&site.hazard.function), string(&site.hazard.load), string(&site.hazard.user), string(&site.hazard.register))
+            })));
+
+            json.push_str(",\n  \"sections\": ");
+            json.push_str(&array(checks.usage.sections.iter().map(|(name, size)| format!("{{\"name\": {}, \"size\": {size}}}", string(name)))));
+
+            json.push_str(",\n  \"ram\": ");
+            match checks.usage.ram {
+                Some(ram) => {
+                    let _ = write!(json, "{{\"base\": \"{:08x}\", \"size\": {}, \"used\": {}, \"free\": {}}}", ram.base, ram.size, ram.used, ram.size as i64 - ram.used as i64);
+                },
+                None      => json.push_str("null"),
+            }
+        }
+        json.push_str("\n}\n");
+        json
+    }
+}
+
+/// Returns: `value` as a JSON string
+fn string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' | '\\'   => {
+                json.push('\\');
+                json.push(c);
+            },
+            '\n'         => json.push_str("\\n"),
+            c if c < ' ' => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            },
+            c            => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Returns: The JSON values of `items` as an array
+fn array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report() {
+        let header = Header{pc: 0x8001_0000, gp: 0, load_addr: 0x8001_0000, size: 0x800, stack: 0x801F_FF00, stack_offset: 0, marker: String::new()};
+        let mut file = header.to_bytes();
+        // lw v0, 0(a0); addu v1, v0, v0; jr ra; nop
+        for word in [0x8C82_0000u32, 0x0042_1821, 0x03E0_0008, 0x0000_0000] {
+            file.extend(word.to_le_bytes());
+        }
+        file.resize(file.len() + 0x800 - 16, 0);
+
+        let checks = check(&file, &Map::default());
+        assert!(checks.errors.is_empty());
+        assert_eq!(checks.hazards.iter().map(|site| (site.address, site.hazard.register.as_str())).collect::<Vec<_>>(), [(0x8001_0004, "v0")]);
+
+        let report = Report{exe: "app.exe".to_owned(), profile: "release".to_owned(), built: true, seconds: 1.0, checks: Some(checks)};
+        let json   = report.to_json();
+        assert!(!report.ok());
+        assert!(json.starts_with("{\n  \"ok\": false,\n  \"exe\": \"app.exe\",\n"));
+        assert!(json.contains("\"header\": {\"pc\": \"80010000\", \"gp\": \"00000000\", \"load_address\": \"80010000\", \"size\": 2048, \"stack\": \"801fff00\", \"region\": null, \"errors\": []}"));
+        assert!(json.contains("{\"address\": \"80010004\", "));
+        assert!(json.ends_with("\"ram\": null\n}\n"));
+
+        assert_eq!(string("a \"b\"\\\n\u{1}"), "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+    }
+
+    #[test]
+    fn profiles() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(profile(&args(&["-p", "app"])), "dev");
+        assert_eq!(profile(&args(&["--release"])), "release");
+        assert_eq!(profile(&args(&["--profile", "dev"])), "dev");
+        assert_eq!(profile(&args(&["--profile=lto"])), "lto");
+
+        assert_eq!(["dev", "test", "release", "bench", "lto"].map(profile_dir), ["debug", "debug", "release", "release", "lto"]);
+    }
+}
@@ -0,0 +1,71 @@
+//! `cargo psx [--report <report.json>] [<cargo build args>...]`
+//!
+//! Builds for the console with the `psx_build` alias, `cargo +nightly psx --release` builds
+//! `target/mipsel-sony-psx/release/app.exe`, then checks its header, scans `.text` for load
+//! delay hazards and compares the sections of the ELF `psx-ld` linked it from with `RAM_SIZE`.
+//! The JSON report goes to stdout or `--report`, the output of cargo to stderr. Exits with `1` if the
+//! build failed or any check did. Installed with `cargo install --path tools/cargo-psx` it
+//! runs as a cargo subcommand, otherwise with `cargo +nightly run -p cargo-psx --`.
+
+use std::{env, fs, path::PathBuf, process::{Command, ExitCode}, time::Instant};
+
+use cargo_psx::{ALIAS, Report, TARGET, check, profile, profile_dir};
+use disasm::{default_symbols, map::Map};
+
+const EXE: &str = "app.exe";
+
+fn main() -> ExitCode {
+    // `cargo psx` runs `cargo-psx psx`
+    let mut args: Vec<String> = env::args().skip(1).skip_while(|arg| arg == "psx").collect();
+
+    let mut report_path = None;
+    if let Some(idx) = args.iter().position(|arg| arg == "--report") {
+        if idx + 1 >= args.len() {
+            eprintln!("Usage: cargo psx [--report <report.json>] [<cargo build args>...]");
+            return ExitCode::from(2);
+        }
+        report_path = Some(args.remove(idx + 1));
+        args.remove(idx);
+    }
+
+    let profile    = profile(&args);
+    let target_dir = env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_owned());
+    let exe_path   = PathBuf::from(target_dir).join(TARGET).join(profile_dir(&profile)).join(EXE);
+
+    // Inside `cargo psx` `CARGO` is the cargo of the toolchain it was started with, which
+    // resolves the alias from the `.cargo/config.toml` of the workspace
+    let cargo  = env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    let start  = Instant::now();
+    let status = Command::new(cargo).arg(ALIAS).args(&args).status();
+    let mut report = Report{exe: exe_path.display().to_string(), profile, built: status.is_ok_and(|status| status.success()), seconds: start.elapsed().as_secs_f64(), checks: None};
+
+    if report.built {
+        let file = match fs::read(&exe_path) {
+            Ok(file)   => file,
+            Err(error) => {
+                eprintln!("{}: {error}", exe_path.display());
+                return ExitCode::FAILURE;
+            },
+        };
+        let map = default_symbols(&exe_path).and_then(|path| Map::load(&path).ok()).unwrap_or_default();
+        report.checks = Some(check(&file, &map));
+    }
+
+    let json = report.to_json();
+    match &report_path {
+        Some(path) => if let Err(error) = fs::write(path, json) {
+            eprintln!("{path}: {error}");
+            return ExitCode::FAILURE;
+        },
+        None       => print!("{json}"),
+    }
+
+    if let Some(checks) = &report.checks {
+        eprintln!("{}: {} header errors, {} hazards, {} of {} bytes of RAM used", report.exe, checks.errors.len(), checks.hazards.len(),
+            checks.usage.ram.map_or(0, |ram| ram.used), checks.usage.ram.map_or(0, |ram| ram.size));
+    }
+    match report.ok() {
+        true  => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
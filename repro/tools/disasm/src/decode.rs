@@ -0,0 +1,190 @@
+//! Decodes R3000A instructions into objdump like text
+
+use hazard::mips::register_name;
+
+/// A decoded instruction
+#[derive(Debug, PartialEq)]
+pub struct Decoded {
+    /// The mnemonic, `.word` if the instruction is unknown
+    pub mnemonic: &'static str,
+    /// The operands except for a branch target
+    pub operands: Vec<String>,
+    /// The address a branch or jump continues at
+    pub target:   Option<u32>,
+}
+
+impl Decoded {
+    fn new(mnemonic: &'static str, operands: Vec<String>) -> Decoded {
+        Decoded{mnemonic, operands, target: None}
+    }
+
+    fn branch(mnemonic: &'static str, operands: Vec<String>, target: u32) -> Decoded {
+        Decoded{mnemonic, operands, target: Some(target)}
+    }
+}
+
+fn reg(number: u32) -> String {
+    register_name(number as u8).to_owned()
+}
+
+fn memory(offset: i16, base: u32) -> String {
+    format!("{offset}({})", reg(base))
+}
+
+/// Decodes the instruction `word` found at `address`
+///
+/// Arguments:
+/// * `address`: The address of the instruction, needed for branch targets
+/// * `word`: The instruction
+///
+/// Returns: The `Decoded` instruction
+pub fn decode(address: u32, word: u32) -> Decoded {
+    let opcode = word >> 26;
+    let rs     = (word >> 21) & 0x1F;
+    let rt     = (word >> 16) & 0x1F;
+    let rd     = (word >> 11) & 0x1F;
+    let shamt  = (word >> 6) & 0x1F;
+    let funct  = word & 0x3F;
+    let imm    = word as u16;
+    let simm   = imm as i16;
+
+    // The delay slot is at `address + 4`, branches are relative to it
+    let branch = address.wrapping_add(4).wrapping_add(((simm as i32) << 2) as u32);
+    let jump   = (address.wrapping_add(4) & 0xF000_0000) | ((word & 0x03FF_FFFF) << 2);
+
+    let unknown = || Decoded::new(".word", vec![format!("0x{word:08x}")]);
+    let arith   = |mnemonic| Decoded::new(mnemonic, vec![reg(rd), reg(rs), reg(rt)]);
+    let shift   = |mnemonic| Decoded::new(mnemonic, vec![reg(rd), reg(rt), shamt.to_string()]);
+    let shiftv  = |mnemonic| Decoded::new(mnemonic, vec![reg(rd), reg(rt), reg(rs)]);
+    let signed  = |mnemonic| Decoded::new(mnemonic, vec![reg(rt), reg(rs), simm.to_string()]);
+    let logical = |mnemonic| Decoded::new(mnemonic, vec![reg(rt), reg(rs), format!("0x{imm:x}")]);
+    let load    = |mnemonic| Decoded::new(mnemonic, vec![reg(rt), memory(simm, rs)]);
+    let cop     = |mnemonic| Decoded::new(mnemonic, vec![reg(rt), format!("${rd}")]);
+
+    match opcode {
+        0x00 => match funct {
+            0x00 if word == 0 => Decoded::new("nop", vec![]),
+            0x00 => shift("sll"),
+            0x02 => shift("srl"),
+            0x03 => shift("sra"),
+            0x04 => shiftv("sllv"),
+            0x06 => shiftv("srlv"),
+            0x07 => shiftv("srav"),
+            0x08 => Decoded::new("jr", vec![reg(rs)]),
+            0x09 if rd == 31 => Decoded::new("jalr", vec![reg(rs)]),
+            0x09 => Decoded::new("jalr", vec![reg(rd), reg(rs)]),
+            0x0C => Decoded::new("syscall", vec![]),
+            0x0D => Decoded::new("break", vec![]),
+            0x10 => Decoded::new("mfhi", vec![reg(rd)]),
+            0x11 => Decoded::new("mthi", vec![reg(rs)]),
+            0x12 => Decoded::new("mflo", vec![reg(rd)]),
+            0x13 => Decoded::new("mtlo", vec![reg(rs)]),
+            0x18 => Decoded::new("mult", vec![reg(rs), reg(rt)]),
+            0x19 => Decoded::new("multu", vec![reg(rs), reg(rt)]),
+            0x1A => Decoded::new("div", vec![reg(rs), reg(rt)]),
+            0x1B => Decoded::new("divu", vec![reg(rs), reg(rt)]),
+            0x20 => arith("add"),
+            0x21 if rt == 0 => Decoded::new("move", vec![reg(rd), reg(rs)]),
+            0x21 => arith("addu"),
+            0x22 => arith("sub"),
+            0x23 => arith("subu"),
+            0x24 => arith("and"),
+            0x25 if rt == 0 => Decoded::new("move", vec![reg(rd), reg(rs)]),
+            0x25 => arith("or"),
+            0x26 => arith("xor"),
+            0x27 => arith("nor"),
+            0x2A => arith("slt"),
+            0x2B => arith("sltu"),
+            _    => unknown(),
+        },
+        0x01 => match rt {
+            0x00 => Decoded::branch("bltz", vec![reg(rs)], branch),
+            0x01 => Decoded::branch("bgez", vec![reg(rs)], branch),
+            0x10 => Decoded::branch("bltzal", vec![reg(rs)], branch),
+            0x11 => Decoded::branch("bgezal", vec![reg(rs)], branch),
+            _    => unknown(),
+        },
+        0x02 => Decoded::branch("j", vec![], jump),
+        0x03 => Decoded::branch("jal", vec![], jump),
+        0x04 if rs == 0 && rt == 0 => Decoded::branch("b", vec![], branch),
+        0x04 if rt == 0 => Decoded::branch("beqz", vec![reg(rs)], branch),
+        0x04 => Decoded::branch("beq", vec![reg(rs), reg(rt)], branch),
+        0x05 if rt == 0 => Decoded::branch("bnez", vec![reg(rs)], branch),
+        0x05 => Decoded::branch("bne", vec![reg(rs), reg(rt)], branch),
+        0x06 => Decoded::branch("blez", vec![reg(rs)], branch),
+        0x07 => Decoded::branch("bgtz", vec![reg(rs)], branch),
+        0x08 => signed("addi"),
+        0x09 if rs == 0 => Decoded::new("li", vec![reg(rt), simm.to_string()]),
+        0x09 => signed("addiu"),
+        0x0A => signed("slti"),
+        0x0B => signed("sltiu"),
+        0x0C => logical("andi"),
+        0x0D => logical("ori"),
+        0x0E => logical("xori"),
+        0x0F => Decoded::new("lui", vec![reg(rt), format!("0x{imm:x}")]),
+        0x10 => match rs {
+            0x00 => cop("mfc0"),
+            0x04 => cop("mtc0"),
+            0x10 if funct == 0x10 => Decoded::new("rfe", vec![]),
+            _    => unknown(),
+        },
+        0x12 => match rs {
+            0x00 => cop("mfc2"),
+            0x02 => cop("cfc2"),
+            0x04 => cop("mtc2"),
+            0x06 => cop("ctc2"),
+            0x10.. => Decoded::new("cop2", vec![format!("0x{:x}", word & 0x01FF_FFFF)]),
+            _    => unknown(),
+        },
+        0x20 => load("lb"),
+        0x21 => load("lh"),
+        0x22 => load("lwl"),
+        0x23 => load("lw"),
+        0x24 => load("lbu"),
+        0x25 => load("lhu"),
+        0x26 => load("lwr"),
+        0x28 => load("sb"),
+        0x29 => load("sh"),
+        0x2A => load("swl"),
+        0x2B => load("sw"),
+        0x2E => load("swr"),
+        0x32 => Decoded::new("lwc2", vec![format!("${rt}"), memory(simm, rs)]),
+        0x3A => Decoded::new("swc2", vec![format!("${rt}"), memory(simm, rs)]),
+        _    => unknown(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(address: u32, word: u32) -> String {
+        let decoded = decode(address, word);
+        let mut operands = decoded.operands;
+        operands.extend(decoded.target.map(|target| format!("{target:08x}")));
+
+        format!("{} {}", decoded.mnemonic, operands.join(",")).trim_end().to_owned()
+    }
+
+    #[test]
+    fn instructions() {
+        assert_eq!(text(0, 0x0000_0000), "nop");
+        assert_eq!(text(0, 0x8FBF_0014), "lw ra,20(sp)");
+        assert_eq!(text(0, 0x27BD_FFE8), "addiu sp,sp,-24");
+        assert_eq!(text(0, 0x3C02_8001), "lui v0,0x8001");
+        assert_eq!(text(0, 0x0062_1021), "addu v0,v1,v0");
+        assert_eq!(text(0, 0x0080_1025), "move v0,a0");
+        assert_eq!(text(0, 0x03E0_0008), "jr ra");
+        assert_eq!(text(0, 0x240A_00A0), "li t2,160");
+        assert_eq!(text(0, 0x4802_4800), "mfc2 v0,$9");
+        assert_eq!(text(0, 0x4A18_0001), "cop2 0x180001");
+        assert_eq!(text(0, 0xFC00_0000), ".word 0xfc000000");
+    }
+
+    #[test]
+    fn branch_targets() {
+        assert_eq!(text(0x8001_0004, 0x1040_0002), "beqz v0,80010010");
+        assert_eq!(text(0x8001_0010, 0x1000_FFFB), "b 80010000");
+        assert_eq!(text(0x8001_0000, 0x0C00_4005), "jal 80010014");
+    }
+}
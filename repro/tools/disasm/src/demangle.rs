@@ -0,0 +1,248 @@
+//! Demangles the symbol names of the ELF the way `ld.lld` does in the map
+//!
+//! `_RNvNtCs1a_3sdk10peripheral17update_controller` of the v0 mangling is
+//! `sdk::peripheral::update_controller` and `_ZN3sdk4main17h0123456789abcdefE` of the legacy one
+//! is `sdk::main`. Of v0 the paths, impl blocks, generic types and back references are
+//! supported, names with constants, function pointers or trait objects are left mangled, they
+//! are unique either way.
+
+/// Returns: The demangled `name` or `name` itself if it is no supported mangled name
+pub fn demangle(name: &str) -> String {
+    let demangled = match name.as_bytes() {
+        [b'_', b'R', rest @ ..]       => V0{bytes: rest, idx: 0}.path(),
+        [b'_', b'Z', b'N', rest @ ..] => legacy(rest),
+        _                             => None,
+    };
+    demangled.unwrap_or_else(|| name.to_owned())
+}
+
+/// A v0 mangled name after the `_R`
+struct V0<'a> {
+    bytes: &'a [u8],
+    idx:   usize,
+}
+
+impl V0<'_> {
+    /// Returns: The path at the cursor
+    fn path(&mut self) -> Option<String> {
+        match self.next()? {
+            b'C' => {
+                self.disambiguator()?;
+                self.ident()
+            },
+            b'N' => {
+                let namespace = self.next()?;
+                let parent    = self.path()?;
+                self.disambiguator()?;
+                let ident     = self.ident()?;
+
+                // Closures and shims are upper case namespaces without a name
+                match (namespace, ident.is_empty()) {
+                    (b'C', true) => Some(format!("{parent}::{{closure}}")),
+                    (_, true)    => Some(format!("{parent}::{{{}}}", namespace as char)),
+                    (_, false)   => Some(format!("{parent}::{ident}")),
+                }
+            },
+            // Inherent impls print as their type, trait impls as the type and the trait
+            b'M' => {
+                self.disambiguator()?;
+                self.path()?;
+                Some(format!("<{}>", self.ty()?))
+            },
+            b'X' => {
+                self.disambiguator()?;
+                self.path()?;
+                let ty = self.ty()?;
+                Some(format!("<{ty} as {}>", self.path()?))
+            },
+            b'Y' => {
+                let ty = self.ty()?;
+                Some(format!("<{ty} as {}>", self.path()?))
+            },
+            b'I' => {
+                let path = self.path()?;
+                let mut args = Vec::new();
+                while self.bytes.get(self.idx) != Some(&b'E') {
+                    // Lifetimes are erased like `ld.lld` does
+                    if self.bytes.get(self.idx) == Some(&b'L') {
+                        self.idx += 1;
+                        self.base62()?;
+                    } else {
+                        args.push(self.ty()?);
+                    }
+                }
+                self.idx += 1;
+                Some(format!("{path}<{}>", args.join(", ")))
+            },
+            b'B' => self.backref(V0::path),
+            _    => None,
+        }
+    }
+
+    /// Returns: The type at the cursor
+    fn ty(&mut self) -> Option<String> {
+        let basic = match self.bytes.get(self.idx)? {
+            b'a' => "i8",
+            b'b' => "bool",
+            b'c' => "char",
+            b'd' => "f64",
+            b'e' => "str",
+            b'f' => "f32",
+            b'h' => "u8",
+            b'i' => "isize",
+            b'j' => "usize",
+            b'l' => "i32",
+            b'm' => "u32",
+            b'n' => "i128",
+            b'o' => "u128",
+            b's' => "i16",
+            b't' => "u16",
+            b'u' => "()",
+            b'x' => "i64",
+            b'y' => "u64",
+            b'z' => "!",
+            b'p' => "_",
+            _    => "",
+        };
+        if !basic.is_empty() {
+            self.idx += 1;
+            return Some(basic.to_owned());
+        }
+
+        match self.bytes[self.idx] {
+            b'R' | b'Q'        => {
+                let reference = if self.next()? == b'R' {"&"} else {"&mut "};
+                if self.bytes.get(self.idx) == Some(&b'L') {
+                    self.idx += 1;
+                    self.base62()?;
+                }
+                Some(format!("{reference}{}", self.ty()?))
+            },
+            b'P' | b'O'        => {
+                let pointer = if self.next()? == b'P' {"*const"} else {"*mut"};
+                Some(format!("{pointer} {}", self.ty()?))
+            },
+            b'S'               => {
+                self.idx += 1;
+                Some(format!("[{}]", self.ty()?))
+            },
+            b'T'               => {
+                self.idx += 1;
+                let mut types = Vec::new();
+                while self.bytes.get(self.idx) != Some(&b'E') {
+                    types.push(self.ty()?);
+                }
+                self.idx += 1;
+                match types.as_slice() {
+                    [ty] => Some(format!("({ty},)")),
+                    _    => Some(format!("({})", types.join(", "))),
+                }
+            },
+            b'B'               => {
+                self.idx += 1;
+                self.backref(V0::ty)
+            },
+            b'A' | b'F' | b'D' => None,
+            _                  => self.path(),
+        }
+    }
+
+    /// Returns: What `parse` reads at the offset of the back reference at the cursor
+    fn backref(&mut self, parse: fn(&mut Self) -> Option<String>) -> Option<String> {
+        let target = self.base62()?;
+        // Back references only point backwards, which also rules out loops
+        if target >= self.idx {
+            return None;
+        }
+        let idx    = std::mem::replace(&mut self.idx, target);
+        let result = parse(self);
+        self.idx   = idx;
+        result
+    }
+
+    /// Skips the optional `s<base62>_` telling items of the same name apart
+    fn disambiguator(&mut self) -> Option<()> {
+        if self.bytes.get(self.idx) == Some(&b's') {
+            self.idx += 1;
+            self.base62()?;
+        }
+        Some(())
+    }
+
+    /// Returns: The number at the cursor, `_` is 0 and `<digits>_` the digits plus one
+    fn base62(&mut self) -> Option<usize> {
+        if self.bytes.get(self.idx) == Some(&b'_') {
+            self.idx += 1;
+            return Some(0);
+        }
+
+        let mut value = 0usize;
+        loop {
+            let digit = match self.next()? {
+                b'_'               => return value.checked_add(1),
+                byte @ b'0'..=b'9' => byte - b'0',
+                byte @ b'a'..=b'z' => byte - b'a' + 10,
+                byte @ b'A'..=b'Z' => byte - b'A' + 36,
+                _                  => return None,
+            };
+            value = value.checked_mul(62)?.checked_add(digit as usize)?;
+        }
+    }
+
+    /// Returns: The identifier at the cursor, punycode is not supported
+    fn ident(&mut self) -> Option<String> {
+        let digits = self.bytes[self.idx..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+        let len: usize = std::str::from_utf8(&self.bytes[self.idx..self.idx + digits]).ok()?.parse().ok()?;
+        self.idx += digits;
+
+        // A `_` separates the length from identifiers starting with a digit or `_`
+        if self.bytes.get(self.idx) == Some(&b'_') {
+            self.idx += 1;
+        }
+        let ident = self.bytes.get(self.idx..self.idx + len)?;
+        self.idx += len;
+        String::from_utf8(ident.to_vec()).ok()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.idx)?;
+        self.idx += 1;
+        Some(byte)
+    }
+}
+
+/// Returns: The legacy mangled path after the `_ZN` without its hash
+fn legacy(mut bytes: &[u8]) -> Option<String> {
+    let mut segments = Vec::new();
+    while bytes.first() != Some(&b'E') {
+        let digits = bytes.iter().take_while(|byte| byte.is_ascii_digit()).count();
+        let len: usize = std::str::from_utf8(&bytes[..digits]).ok()?.parse().ok()?;
+        let segment = std::str::from_utf8(bytes.get(digits..digits + len)?).ok()?;
+        segments.push(segment);
+        bytes = &bytes[digits + len..];
+    }
+
+    if segments.last().is_some_and(|hash| hash.len() == 17 && hash.starts_with('h')) {
+        segments.pop();
+    }
+    (!segments.is_empty() && segments.iter().all(|segment| !segment.contains('$'))).then(|| segments.join("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangle_paths() {
+        assert_eq!(demangle("_RNvNtCsgEGWVYSu1zn_3sdk10peripheral17update_controller"), "sdk::peripheral::update_controller");
+        assert_eq!(demangle("_RNCNvCs1_3app4main0B3_"), "app::main::{closure}");
+        assert_eq!(demangle("_RNvCs1_3app4main"), "app::main");
+        assert_eq!(demangle("_ZN3sdk10peripheral4main17h0123456789abcdefE"), "sdk::peripheral::main");
+        assert_eq!(demangle("_RNvMs_NtNtCsgEGWVYSu1zn_3sdk10peripheral17serial_connectionINtB4_16SerialConnectionNtNtNtB6_10serial_bus3psx12PsxSerialBusE17send_cmd_seq_implB8_"),
+            "<sdk::peripheral::serial_connection::SerialConnection<sdk::peripheral::serial_bus::psx::PsxSerialBus>>::send_cmd_seq_impl");
+        assert_eq!(demangle("_RNvXs5_NtCs2_4core3fmtRhNtB5_7Display3fmt"), "<&u8 as core::fmt::Display>::fmt");
+        assert_eq!(demangle("_RINvCs1_3app3sumTjmEEB2_"), "app::sum<(usize, u32)>");
+        assert_eq!(demangle("_RNvMCs1_3appAhj4_3new"), "_RNvMCs1_3appAhj4_3new");
+        assert_eq!(demangle("__startup"), "__startup");
+    }
+}
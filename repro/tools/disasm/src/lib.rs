@@ -0,0 +1,135 @@
+//! Disassembles the PS-X EXE built by `ps-exe.ld`
+//!
+//! The text segment is decoded at its load address, labelled with the symbols of the linker
+//! map and checked for load delay hazards, which audits what actually ships instead of the
+//! `.s` the compiler emitted. The reader of the map is shared by the other tools, the EXE and
+//! the ELF are read by `psxexe`.
+//!
+//! The symbols come from the ELF `psx-ld` linked `app.exe` from, the linker map is the fallback
+//! for EXEs built before.
+
+pub mod decode;
+pub mod demangle;
+pub mod map;
+
+use std::{fs, path::{Path, PathBuf}};
+
+use hazard::listing::{Instruction, Listing};
+use psxexe::Exe;
+
+use crate::{decode::decode, map::Map};
+
+/// The map the console builds write, where the symbols of EXEs without an ELF come from
+pub const DEFAULT_MAP: &str = "target/app.map";
+
+/// Returns: The ELF `psx-ld` linked the EXE at `exe_path` from, `None` if there is none
+///
+/// `psx-ld` writes `deps/app-<hash>.elf` next to `deps/app-<hash>.exe`. Cargo copies an EXE of
+/// `deps` up to `app.exe` without linking again if an earlier build had the same hash, so the
+/// ELF of `app.exe` is the one next to the EXE in `deps` with the same content. Examples are
+/// linked in `examples` itself, next to their copy.
+pub fn linked_elf(exe_path: &Path) -> Option<PathBuf> {
+    let elf = exe_path.with_extension("elf");
+    if exe_path.parent()?.ends_with("deps") {
+        return elf.exists().then_some(elf);
+    }
+
+    let exe    = fs::read(exe_path).ok()?;
+    let prefix = format!("{}-", exe_path.file_stem()?.to_str()?);
+    let dirs   = [exe_path.parent()?.join("deps"), exe_path.parent()?.to_path_buf()];
+    let mut linked: Vec<PathBuf> = dirs.iter().filter_map(|dir| fs::read_dir(dir).ok()).flatten().flatten().map(|entry| entry.path()).filter(|path| {
+        path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".exe"))
+    }).collect();
+    linked.sort();
+
+    linked.into_iter().find(|path| fs::read(path).is_ok_and(|file| file == exe)).map(|path| path.with_extension("elf")).filter(|elf| elf.exists())
+}
+
+/// Returns: The `linked_elf` of `exe_path` or else `DEFAULT_MAP`, `None` if neither exists
+pub fn default_symbols(exe_path: &Path) -> Option<PathBuf> {
+    linked_elf(exe_path).or_else(|| Some(PathBuf::from(DEFAULT_MAP)).filter(|path| path.exists()))
+}
+
+/// Decodes the text segment of `exe` up to `end` into a `Listing`
+///
+/// Branch and jump targets are appended to the operands in objdump syntax and every
+/// instruction belongs to the symbol of `map` covering it.
+///
+/// Arguments:
+/// * `exe`: The PS-X EXE to decode
+/// * `map`: The symbols, can be empty
+/// * `end`: The first address that is no code anymore, usually the end of `.text`
+///
+/// Returns: The instructions with their `line` being the index of the word plus one
+pub fn disassemble(exe: &Exe, map: &Map, end: u32) -> Listing {
+    let mut listing = Listing::default();
+    for (idx, (address, word)) in exe.words().take_while(|(address, _)| *address < end).enumerate() {
+        let decoded      = decode(address, word);
+        let mut operands = decoded.operands;
+        operands.extend(decoded.target.map(|target| symbolize(map, target)));
+
+        let function = map.lookup(address).map_or("", |(symbol, _)| symbol.name.as_str());
+        listing.push(function, Instruction{
+            line: idx + 1, function: 0, address: Some(address), mnemonic: decoded.mnemonic.to_owned(), operands, reorder: false
+        });
+    }
+    listing
+}
+
+/// Returns: `address` in objdump syntax, e.g. `80010040 <main+0x34>`
+pub fn symbolize(map: &Map, address: u32) -> String {
+    match map.lookup(address) {
+        Some((symbol, 0))      => format!("{address:08x} <{}>", symbol.name),
+        Some((symbol, offset)) => format!("{address:08x} <{}+{offset:#x}>", symbol.name),
+        None                   => format!("{address:08x}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for this process, removed again when dropped, even if a test fails
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            TempDir(std::env::temp_dir().join(format!("{name}-{}", std::process::id())))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn linked_elf_of_uplifted_exe() {
+        let temp = TempDir::new("disasm-linked-elf");
+        let dir  = &temp.0;
+        let deps = dir.join("deps");
+        fs::create_dir_all(&deps).unwrap();
+
+        // Two links of `app`, the older one copied up again without linking
+        for (hash, content) in [("0001", b"first"), ("0002", b"other")] {
+            fs::write(deps.join(format!("app-{hash}.exe")), content).unwrap();
+            fs::write(deps.join(format!("app-{hash}.elf")), hash).unwrap();
+        }
+        fs::write(dir.join("app.exe"), b"first").unwrap();
+
+        assert_eq!(linked_elf(&dir.join("app.exe")), Some(deps.join("app-0001.elf")));
+        assert_eq!(linked_elf(&deps.join("app-0002.exe")), Some(deps.join("app-0002.elf")));
+
+        fs::write(dir.join("app.exe"), b"never linked").unwrap();
+        assert_eq!(linked_elf(&dir.join("app.exe")), None);
+
+        // Examples are linked next to their copy
+        let examples = dir.join("examples");
+        fs::create_dir_all(&examples).unwrap();
+        fs::write(examples.join("panic-0003.exe"), b"example").unwrap();
+        fs::write(examples.join("panic-0003.elf"), b"0003").unwrap();
+        fs::write(examples.join("panic.exe"), b"example").unwrap();
+        assert_eq!(linked_elf(&examples.join("panic.exe")), Some(examples.join("panic-0003.elf")));
+    }
+}
@@ -0,0 +1,84 @@
+//! `disasm <app.exe> [--map <app.elf | app.map>]`
+//!
+//! Prints the code of a PS-X EXE like `objdump -d` would and marks every instruction reading a
+//! register right after it was loaded with `# load delay`. The symbols come from the ELF
+//! `psx-ld` linked the EXE from or `target/app.map` unless `--map` names another ELF or map,
+//! without symbols the whole text segment is decoded. Exits with `1` if any hazards were found.
+
+use std::{collections::HashMap, env, fs, path::{Path, PathBuf}, process::ExitCode};
+
+use disasm::{default_symbols, disassemble, map::Map};
+use hazard::find_hazards;
+use psxexe::Exe;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (exe_path, map_path) = match args.as_slice() {
+        [exe]                               => (exe, default_symbols(Path::new(exe))),
+        [exe, flag, map] if flag == "--map" => (exe, Some(PathBuf::from(map))),
+        _                                   => {
+            eprintln!("Usage: disasm <app.exe> [--map <app.elf | app.map>]");
+            return ExitCode::from(2);
+        },
+    };
+
+    let file = match fs::read(exe_path) {
+        Ok(file)   => file,
+        Err(error) => {
+            eprintln!("{exe_path}: {error}");
+            return ExitCode::from(2);
+        },
+    };
+    let exe = match Exe::parse(&file) {
+        Ok(exe)    => exe,
+        Err(error) => {
+            eprintln!("{exe_path}: {error}");
+            return ExitCode::from(2);
+        },
+    };
+    let map = match map_path.as_deref().map(Map::load).transpose() {
+        Ok(map)    => map.unwrap_or_default(),
+        Err(error) => {
+            eprintln!("{}: {error}", map_path.unwrap_or_default().display());
+            return ExitCode::from(2);
+        },
+    };
+
+    println!("{exe_path}: pc {:08x}, gp {:08x}, load address {:08x}, size {:#x}, sp {:08x}", exe.header.pc, exe.header.gp, exe.header.load_addr, exe.text.len(), exe.header.sp());
+
+    // Only `.text` is code, the rest of the segment is `.data`
+    let end = match map.section(".text") {
+        Some(text) if text.address == exe.header.load_addr => text.address.wrapping_add(text.size),
+        _                                                  => exe.header.load_addr.wrapping_add(exe.text.len() as u32),
+    };
+
+    let listing = disassemble(&exe, &map, end);
+    let words   = exe.words().map(|(_, word)| word);
+
+    let hazards = find_hazards(&listing);
+    let marks: HashMap<usize, Vec<String>> = hazards.iter().fold(HashMap::new(), |mut marks, hazard| {
+        let load = &listing.instructions[hazard.load_line - 1];
+        marks.entry(hazard.line).or_default().push(format!("# load delay: reads {} loaded at {:08x}", hazard.register, load.address.unwrap_or_default()));
+        marks
+    });
+
+    for (instruction, word) in listing.instructions.iter().zip(words) {
+        let address = instruction.address.unwrap_or_default();
+        if let Some(symbol) = map.at(address) {
+            println!("\n{address:08x} <{}>:", symbol.name);
+        }
+
+        let mut line = format!("{address:08x}:\t{word:08x} \t{}\t{}", instruction.mnemonic, instruction.operands.join(","));
+        for mark in marks.get(&instruction.line).into_iter().flatten() {
+            line.push('\t');
+            line.push_str(mark);
+        }
+        println!("{}", line.trim_end());
+    }
+
+    eprintln!("{exe_path}: {} hazards in {} instructions", hazards.len(), listing.instructions.len());
+    match hazards.len() {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
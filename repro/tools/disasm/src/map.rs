@@ -0,0 +1,266 @@
+//! Reads the symbols out of the linker map (`-Map=target/app.map`) or the ELF `psx-ld` linked
+//! `app.exe` from, `deps/app-<hash>.elf`
+
+use std::{fs, io, path::Path};
+
+use psxexe::elf::{Elf, SHF_ALLOC, SHN_ABS, STT_FUNC, STT_OBJECT};
+
+use crate::demangle::demangle;
+
+/// The type of the symbol naming the object file of the link
+const STT_FILE: u8 = 4;
+
+/// A symbol or output section of the map
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// The virtual address
+    pub address: u32,
+    /// The size, for symbols this is the size of their input section
+    pub size:    u32,
+    /// The demangled name
+    pub name:    String,
+}
+
+/// An input section of the map
+#[derive(Debug, Clone, PartialEq)]
+pub struct Input {
+    /// The virtual address
+    pub address: u32,
+    /// The size
+    pub size:    u32,
+    /// The output section it was placed in, like `.text`
+    pub section: String,
+    /// The object file and section, like `app.o:(.text.main)` or `<internal>:(.got)`
+    pub name:    String,
+    /// The demangled name of the first symbol in it
+    pub symbol:  Option<String>,
+}
+
+/// The symbols and output sections of a linker map
+#[derive(Debug, Default)]
+pub struct Map {
+    /// The output sections like `.text`
+    pub sections:    Vec<Symbol>,
+    /// The symbols sorted by address
+    pub symbols:     Vec<Symbol>,
+    /// The input sections in the order of the map
+    pub inputs:      Vec<Input>,
+    /// The assignments of the linker script outside of sections, like `RAM_SIZE` and `2M`
+    pub assignments: Vec<(String, String)>,
+}
+
+impl Map {
+    /// Parses a map written by `ld.lld`
+    ///
+    /// Output sections are not indented, input sections are indented by 8 and symbols by 16
+    /// characters. Assignments are only kept outside of sections, `__text_start = .` is skipped
+    /// like the local labels `.L…` and `$x` of RISC-V maps.
+    ///
+    /// Arguments:
+    /// * `source`: The content of the map
+    ///
+    /// Returns: The parsed `Map`
+    pub fn parse(source: &str) -> Map {
+        let mut map = Map::default();
+        let mut input_size = 0;
+
+        for line in source.lines() {
+            let Some((fields, rest)) = split_fields(line) else {
+                continue;
+            };
+            let (Ok(address), Ok(size)) = (u32::from_str_radix(fields[0], 16), u32::from_str_radix(fields[2], 16)) else {
+                continue;
+            };
+
+            let indent = rest.len() - rest.trim_start().len();
+            let name   = rest.trim();
+
+            if let Some((symbol, expression)) = name.split_once(" = ") {
+                if indent <= 1 {
+                    map.assignments.push((symbol.to_owned(), expression.to_owned()));
+                }
+                continue;
+            }
+
+            if name.is_empty() || name.starts_with(".L") || name.starts_with('$') {
+                continue;
+            }
+
+            match indent {
+                0..=1  => map.sections.push(Symbol{address, size, name: name.to_owned()}),
+                2..=9  => {
+                    let section = map.sections.last().map(|section| section.name.clone()).unwrap_or_default();
+                    map.inputs.push(Input{address, size, section, name: name.to_owned(), symbol: None});
+                    input_size = size;
+                },
+                _      => {
+                    if let Some(input) = map.inputs.last_mut().filter(|input| input.symbol.is_none()) {
+                        input.symbol = Some(name.to_owned());
+                    }
+                    map.symbols.push(Symbol{address, size: if size == 0 {input_size} else {size}, name: name.to_owned()});
+                },
+            }
+        }
+
+        map.symbols.sort_by_key(|symbol| symbol.address);
+        map
+    }
+
+    /// Collects the same symbols from the symbol table of an ELF
+    ///
+    /// Every function and variable is an input section of its own, so the sizes per crate match
+    /// those of the map of a build with `-ffunction-sections`. The absolute symbols of the linker
+    /// script are the assignments. Symbols without a size, like the functions of `global_asm!`,
+    /// reach up to the next symbol.
+    ///
+    /// Arguments:
+    /// * `elf`: The ELF of the link
+    ///
+    /// Returns: The new `Map`
+    pub fn from_elf(elf: &Elf) -> Map {
+        let mut map = Map::default();
+        for section in elf.sections.iter().filter(|section| section.flags & SHF_ALLOC != 0 && section.address != 0) {
+            map.sections.push(Symbol{address: section.address, size: section.size, name: section.name.clone()});
+        }
+        map.sections.sort_by_key(|section| section.address);
+
+        // With LTO there is a single object file, `app.<hash>-cgu.0`
+        let file = elf.symbols.iter().find(|symbol| symbol.kind == STT_FILE).map_or("<elf>", |symbol| symbol.name.as_str());
+        for symbol in &elf.symbols {
+            if symbol.section == SHN_ABS && symbol.kind != STT_FILE {
+                map.assignments.push((symbol.name.clone(), format!("{:#x}", symbol.value)));
+                continue;
+            }
+
+            let Some(section) = elf.sections.get(symbol.section as usize).filter(|_| matches!(symbol.kind, STT_FUNC | STT_OBJECT)) else {
+                continue;
+            };
+            let name = demangle(&symbol.name);
+            map.symbols.push(Symbol{address: symbol.value, size: symbol.size, name: name.clone()});
+            map.inputs.push(Input{address: symbol.value, size: symbol.size, section: section.name.clone(), name: format!("{file}:({}.{})", section.name, symbol.name), symbol: Some(name)});
+        }
+
+        // Both are pushed in the same order, so the stable sorts keep them at the same index
+        map.symbols.sort_by_key(|symbol| symbol.address);
+        map.inputs.sort_by_key(|input| input.address);
+        for idx in 0..map.symbols.len() {
+            if map.symbols[idx].size == 0 {
+                let address = map.symbols[idx].address;
+                let end     = map.symbols[idx + 1..].iter().map(|symbol| symbol.address).find(|next| *next > address)
+                    .or_else(|| map.sections.iter().find(|section| (section.address..section.address + section.size).contains(&address)).map(|section| section.address + section.size))
+                    .unwrap_or(address);
+                map.symbols[idx].size = end - address;
+                map.inputs[idx].size  = end - address;
+            }
+        }
+        map
+    }
+
+    /// Reads the map or the ELF at `path`, whichever it is
+    ///
+    /// Arguments:
+    /// * `path`: The linker map or the ELF
+    ///
+    /// Returns: The `Map` or why it could not be read
+    pub fn load(path: &Path) -> io::Result<Map> {
+        let file = fs::read(path)?;
+        if file.starts_with(b"\x7fELF") {
+            let elf = Elf::parse(&file).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+            return Ok(Map::from_elf(&elf));
+        }
+        Ok(Map::parse(&String::from_utf8_lossy(&file)))
+    }
+
+    /// Returns: The output section named `name`
+    pub fn section(&self, name: &str) -> Option<&Symbol> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// Returns: The symbol `address` belongs to and the offset of `address` in it
+    pub fn lookup(&self, address: u32) -> Option<(&Symbol, u32)> {
+        let idx    = self.symbols.partition_point(|symbol| symbol.address <= address).checked_sub(1)?;
+        let symbol = &self.symbols[idx];
+        let offset = address - symbol.address;
+
+        (offset < symbol.size.max(1)).then_some((symbol, offset))
+    }
+
+    /// Returns: The first symbol starting at `address`
+    pub fn at(&self, address: u32) -> Option<&Symbol> {
+        self.lookup(address).filter(|(_, offset)| *offset == 0).map(|(symbol, _)| symbol)
+    }
+}
+
+/// Splits `line` into the VMA, LMA, size and alignment fields and the indented rest
+fn split_fields(line: &str) -> Option<([&str; 4], &str)> {
+    let mut fields = [""; 4];
+    let mut rest   = line;
+
+    for field in &mut fields {
+        let trimmed = rest.trim_start();
+        let end     = trimmed.find(char::is_whitespace)?;
+
+        *field = &trimmed[..end];
+        // Skip the single space separating the fields from the rest
+        rest = &trimmed[end + 1..];
+    }
+    Some((fields, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use psxexe::elf::{Section, Symbol as ElfSymbol};
+
+    use super::*;
+
+    const MAP: &str = "     VMA      LMA     Size Align Out     In      Symbol
+       0        0        0     1 RAM_BASE = 0x80000000
+80010000 80010000      a20    16 .text
+80010000 80010000        0     1         __text_start = .
+80010000 80010000        c    16         app.o:(.text)
+80010000 80010000        0     1                 tty_printf
+8001000c 8001000c       38     4         app.o:(.text.main)
+8001000c 8001000c       38     1                 main
+80010a20 80010a20      5e0    16 .data
+";
+
+    #[test]
+    fn symbols() {
+        let map = Map::parse(MAP);
+
+        assert_eq!(map.section(".text").map(|text| text.size), Some(0xA20));
+        assert_eq!(map.symbols.len(), 2);
+        assert_eq!(map.symbols[0], Symbol{address: 0x8001_0000, size: 0xC, name: "tty_printf".to_owned()});
+        assert_eq!(map.lookup(0x8001_0010).map(|(symbol, offset)| (symbol.name.as_str(), offset)), Some(("main", 4)));
+        assert_eq!(map.lookup(0x8001_0044), None);
+        assert_eq!(map.at(0x8001_000C).map(|symbol| symbol.name.as_str()), Some("main"));
+        assert_eq!(map.inputs[1], Input{address: 0x8001_000C, size: 0x38, section: ".text".to_owned(), name: "app.o:(.text.main)".to_owned(), symbol: Some("main".to_owned())});
+        assert_eq!(map.assignments, [("RAM_BASE".to_owned(), "0x80000000".to_owned())]);
+    }
+
+    #[test]
+    fn elf_symbols() {
+        let section = |name: &str, address, size| Section{name: name.to_owned(), kind: 1, flags: SHF_ALLOC, address, size, align: 4, data: &[]};
+        let symbol  = |name: &str, value, size, kind, section| ElfSymbol{name: name.to_owned(), value, size, kind, section};
+        let elf = Elf{
+            entry:       0x8001_0000,
+            sections:    vec![Section{flags: 0, ..section("", 0, 0)}, section(".text", 0x8001_0000, 0xA20), section(".data", 0x8001_0A20, 0x5E0)],
+            symbols:     vec![
+                symbol("app.5c1f-cgu.0", 0, 0, STT_FILE, SHN_ABS),
+                symbol("RAM_BASE", 0x8000_0000, 0, 0, SHN_ABS),
+                symbol("main", 0x8001_000C, 0x38, STT_FUNC, 1),
+                symbol("tty_printf", 0x8001_0000, 0, STT_FUNC, 1),
+                symbol("_RNvNtCs1_3sdk10peripheral13CONTROLLERS_A", 0x8001_0A20, 8, STT_OBJECT, 2),
+                symbol(".Ltmp0", 0x8001_0010, 0, 0, 1),
+            ],
+            relocations: Vec::new(),
+        };
+        let map = Map::from_elf(&elf);
+
+        assert_eq!(map.section(".text").map(|text| text.size), Some(0xA20));
+        assert_eq!(map.symbols.iter().map(|symbol| (symbol.name.as_str(), symbol.size)).collect::<Vec<_>>(), [("tty_printf", 0xC), ("main", 0x38), ("sdk::peripheral::CONTROLLERS_A", 8)]);
+        assert_eq!(map.lookup(0x8001_0010).map(|(symbol, offset)| (symbol.name.as_str(), offset)), Some(("main", 4)));
+        assert_eq!(map.inputs[0], Input{address: 0x8001_0000, size: 0xC, section: ".text".to_owned(), name: "app.5c1f-cgu.0:(.text.tty_printf)".to_owned(), symbol: Some("tty_printf".to_owned())});
+        assert_eq!(map.assignments, [("RAM_BASE".to_owned(), "0x80000000".to_owned())]);
+    }
+}
@@ -0,0 +1,132 @@
+//! The BIOS functions behind the `A0` and `B0` vectors, run natively instead of from a BIOS image
+
+use crate::{Fault, Machine};
+
+/// The BIOS function table the `tty_printf` of the sdk jumps into
+pub const A0_VECTOR: u32 = 0xA0;
+/// The BIOS function table with the events the `stackcheck` feature of the sdk opens
+pub const B0_VECTOR: u32 = 0xB0;
+
+const A0_PUTCHAR: u32 = 0x3C;
+const A0_PUTS:    u32 = 0x3E;
+const A0_PRINTF:  u32 = 0x3F;
+
+const B0_OPEN_EVENT:   u32 = 0x08;
+const B0_ENABLE_EVENT: u32 = 0x0C;
+/// The descriptor of the first event, the BIOS counts up from there
+const EVENT: u32 = 0xF100_0000;
+
+const T1: usize = 9;
+const A0: usize = 4;
+const SP: usize = 29;
+const RA: usize = 31;
+const V0: usize = 2;
+
+/// Runs the `A0` function selected by `t1` and returns to `ra` like the BIOS would
+///
+/// Arguments:
+/// * `machine`: The machine that jumped to `A0_VECTOR`
+///
+/// Returns: A `Fault` if the function is not emulated
+pub fn call_a0(machine: &mut Machine) -> Result<(), Fault> {
+    machine.cpu.flush_load();
+    let function = machine.cpu.regs[T1];
+    let arg      = machine.cpu.regs[A0];
+    let bus_fault = |address| Fault::Bus{pc: A0_VECTOR, address};
+
+    let result = match function {
+        A0_PUTCHAR => {
+            machine.tty.push(arg as u8 as char);
+            arg
+        },
+        A0_PUTS    => {
+            let text = machine.bus.read_string(arg).map_err(bus_fault)?;
+            machine.tty.push_str(&text);
+            machine.tty.push('\n');
+            1
+        },
+        A0_PRINTF  => {
+            let text = printf(machine).map_err(bus_fault)?;
+            machine.tty.push_str(&text);
+            text.len() as u32
+        },
+        _          => return Err(Fault::Bios{vector: A0_VECTOR, function}),
+    };
+    finish(machine, result);
+    Ok(())
+}
+
+/// Runs the `B0` function selected by `t1` and returns to `ra` like the BIOS would
+///
+/// There are no interrupts, so events can be opened and enabled but never fire.
+///
+/// Arguments:
+/// * `machine`: The machine that jumped to `B0_VECTOR`
+///
+/// Returns: A `Fault` if the function is not emulated
+pub fn call_b0(machine: &mut Machine) -> Result<(), Fault> {
+    machine.cpu.flush_load();
+    let function = machine.cpu.regs[T1];
+
+    let result = match function {
+        B0_OPEN_EVENT   => {
+            machine.events += 1;
+            EVENT + machine.events - 1
+        },
+        B0_ENABLE_EVENT => 1,
+        _               => return Err(Fault::Bios{vector: B0_VECTOR, function}),
+    };
+    finish(machine, result);
+    Ok(())
+}
+
+/// Returns `result` in `v0` to `ra`
+fn finish(machine: &mut Machine, result: u32) {
+    machine.cpu.set_register(V0, result);
+    machine.cpu.jump(machine.cpu.regs[RA]);
+}
+
+/// Formats the `printf` call of the CPU state
+///
+/// The format string is in `a0`, the arguments in `a1` to `a3` and then on the stack after
+/// the 16 bytes reserved for the register arguments. Flags and widths are skipped.
+fn printf(machine: &mut Machine) -> Result<String, u32> {
+    let format = machine.bus.read_string(machine.cpu.regs[A0])?;
+    let mut args = 1usize..;
+    let mut next_arg = |machine: &mut Machine| -> Result<u32, u32> {
+        match args.next().unwrap() {
+            idx @ 1..=3 => Ok(machine.cpu.regs[A0 + idx]),
+            idx         => machine.bus.load32(machine.cpu.regs[SP].wrapping_add(idx as u32*4)),
+        }
+    };
+
+    let mut result = String::new();
+    let mut chars  = format.chars();
+    while let Some(char) = chars.next() {
+        if char != '%' {
+            result.push(char);
+            continue;
+        }
+
+        let conversion = chars.by_ref().find(|char| !matches!(char, '0'..='9' | '-' | '+' | ' ' | '#' | '.' | 'l' | 'h'));
+        match conversion {
+            Some('%')       => result.push('%'),
+            Some('c')       => result.push(next_arg(machine)? as u8 as char),
+            Some('d' | 'i') => result.push_str(&(next_arg(machine)? as i32).to_string()),
+            Some('u')       => result.push_str(&next_arg(machine)?.to_string()),
+            Some('x')       => result.push_str(&format!("{:x}", next_arg(machine)?)),
+            Some('X')       => result.push_str(&format!("{:X}", next_arg(machine)?)),
+            Some('p')       => result.push_str(&format!("{:08x}", next_arg(machine)?)),
+            Some('s')       => {
+                let address = next_arg(machine)?;
+                result.push_str(&machine.bus.read_string(address)?);
+            },
+            Some(other)     => {
+                result.push('%');
+                result.push(other);
+            },
+            None            => result.push('%'),
+        }
+    }
+    Ok(result)
+}
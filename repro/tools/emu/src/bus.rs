@@ -0,0 +1,202 @@
+//! RAM, scratchpad and the IO registers the sdk touches
+
+use sdk::peripheral::serial_bus::{SerialBus, VirtualPort};
+
+const RAM_SIZE:        usize = 2*1024*1024;
+const SCRATCHPAD:      u32   = 0x1F80_0000;
+const SCRATCHPAD_SIZE: usize = 1024;
+const IO:              u32   = 0x1F80_1000;
+const IO_END:          u32   = 0x1F80_3000;
+const JOY:             u32   = 0x1F80_1040;
+const JOY_END:         u32   = 0x1F80_1050;
+/// The cache control register in KSEG2
+const CACHE_CONTROL:   u32   = 0xFFFE_0130;
+
+const JOY_DATA: u32 = 0x0;
+const JOY_STAT: u32 = 0x4;
+const JOY_MODE: u32 = 0x8;
+const JOY_CTRL: u32 = 0xA;
+const JOY_BAUD: u32 = 0xE;
+
+const STAT_TX_READY:     u32 = 1 << 0;
+const STAT_RX_NOT_EMPTY: u32 = 1 << 1;
+const STAT_TX_FINISHED:  u32 = 1 << 2;
+const STAT_IRQ:          u32 = 1 << 9;
+
+const CTRL_SELECT:       u16 = 1 << 1;
+const CTRL_ACK_RESET:    u16 = 1 << 4;
+const CTRL_RESET:        u16 = 1 << 6;
+
+/// The controller port, every byte written to `JOY_DATA` is exchanged with a `VirtualPort`
+///
+/// Transfers complete instantly, an acknowledge of the device shows up as the IRQ bit of
+/// `JOY_STAT` right away.
+pub struct Joy {
+    /// The devices plugged into port A
+    pub port: VirtualPort,
+    rx:       Option<u8>,
+    irq:      bool,
+    mode:     u16,
+    ctrl:     u16,
+    baud:     u16,
+}
+
+impl Joy {
+    fn new(port: VirtualPort) -> Joy {
+        Joy{port, rx: None, irq: false, mode: 0, ctrl: 0, baud: 0}
+    }
+
+    fn load(&mut self, offset: u32) -> u32 {
+        match offset {
+            JOY_DATA => self.rx.take().map_or(0xFF, u32::from),
+            JOY_STAT => STAT_TX_READY | STAT_TX_FINISHED | if self.rx.is_some() {STAT_RX_NOT_EMPTY} else {0} | if self.irq {STAT_IRQ} else {0},
+            JOY_MODE => self.mode as u32,
+            JOY_CTRL => self.ctrl as u32,
+            JOY_BAUD => self.baud as u32,
+            _        => 0,
+        }
+    }
+
+    fn store(&mut self, offset: u32, value: u32) {
+        match offset {
+            JOY_DATA => {
+                let selected = self.ctrl & CTRL_SELECT != 0;
+                self.rx  = Some(if selected {self.port.exchange(value as u8).unwrap_or(0xFF)} else {0xFF});
+                self.irq = selected && self.port.wait_ack();
+            },
+            JOY_MODE => self.mode = value as u16,
+            JOY_BAUD => self.baud = value as u16,
+            JOY_CTRL => {
+                let value = value as u16;
+                if value & (CTRL_ACK_RESET | CTRL_RESET) != 0 {
+                    self.irq = false;
+                }
+
+                match (self.ctrl & CTRL_SELECT != 0, value & CTRL_SELECT != 0) {
+                    (false, true) => self.port.select(),
+                    (true, false) => self.port.deselect(),
+                    _             => {},
+                }
+                self.ctrl = value & !(CTRL_ACK_RESET | CTRL_RESET);
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Everything the CPU can address, accesses of unmapped addresses fail with the address
+pub struct Bus {
+    ram:        Vec<u8>,
+    scratchpad: Vec<u8>,
+    pub joy:    Joy,
+}
+
+impl Bus {
+    /// Creates a new `Bus` with zeroed memory
+    ///
+    /// Arguments:
+    /// * `port`: What is plugged into controller port A
+    ///
+    /// Returns: The new `Bus`
+    pub fn new(port: VirtualPort) -> Bus {
+        Bus{ram: vec![0; RAM_SIZE], scratchpad: vec![0; SCRATCHPAD_SIZE], joy: Joy::new(port)}
+    }
+
+    /// Copies `data` to `address` in RAM
+    ///
+    /// Returns: `Err` with the first address outside of RAM
+    pub fn write(&mut self, address: u32, data: &[u8]) -> Result<(), u32> {
+        for (idx, byte) in data.iter().enumerate() {
+            self.store8(address.wrapping_add(idx as u32), *byte)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the nul-terminated string at `address`
+    pub fn read_string(&mut self, address: u32) -> Result<String, u32> {
+        let mut bytes = Vec::new();
+        loop {
+            match self.load8(address.wrapping_add(bytes.len() as u32))? {
+                0    => return Ok(String::from_utf8_lossy(&bytes).into_owned()),
+                byte => bytes.push(byte),
+            }
+        }
+    }
+
+    pub fn load8(&mut self, address: u32) -> Result<u8, u32> {
+        self.load(address, 1).map(|value| value as u8)
+    }
+
+    pub fn load16(&mut self, address: u32) -> Result<u16, u32> {
+        self.load(address, 2).map(|value| value as u16)
+    }
+
+    pub fn load32(&mut self, address: u32) -> Result<u32, u32> {
+        self.load(address, 4)
+    }
+
+    pub fn store8(&mut self, address: u32, value: u8) -> Result<(), u32> {
+        self.store(address, 1, value as u32)
+    }
+
+    pub fn store16(&mut self, address: u32, value: u16) -> Result<(), u32> {
+        self.store(address, 2, value as u32)
+    }
+
+    pub fn store32(&mut self, address: u32, value: u32) -> Result<(), u32> {
+        self.store(address, 4, value)
+    }
+
+    fn memory(&mut self, address: u32, size: usize) -> Option<&mut [u8]> {
+        let physical = physical(address);
+
+        match physical {
+            // 2 MiB of RAM mirrored four times
+            0..0x0080_0000 => {
+                let offset = physical as usize % RAM_SIZE;
+                self.ram.get_mut(offset..offset + size)
+            },
+            SCRATCHPAD..0x1F80_0400 => {
+                let offset = (physical - SCRATCHPAD) as usize;
+                self.scratchpad.get_mut(offset..offset + size)
+            },
+            _ => None,
+        }
+    }
+
+    fn load(&mut self, address: u32, size: usize) -> Result<u32, u32> {
+        if let Some(memory) = self.memory(address, size) {
+            return Ok(memory.iter().rev().fold(0, |value, byte| value << 8 | *byte as u32));
+        }
+
+        match physical(address) {
+            physical @ JOY..JOY_END => Ok(self.joy.load(physical - JOY)),
+            IO..IO_END              => Ok(0),
+            _ if address == CACHE_CONTROL => Ok(0),
+            _                       => Err(address),
+        }
+    }
+
+    fn store(&mut self, address: u32, size: usize, value: u32) -> Result<(), u32> {
+        if let Some(memory) = self.memory(address, size) {
+            memory.copy_from_slice(&value.to_le_bytes()[..size]);
+            return Ok(());
+        }
+
+        match physical(address) {
+            physical @ JOY..JOY_END => self.joy.store(physical - JOY, value),
+            IO..IO_END              => {},
+            _ if address == CACHE_CONTROL => {},
+            _                       => return Err(address),
+        }
+        Ok(())
+    }
+}
+
+/// Returns: The physical address behind the KUSEG, KSEG0 or KSEG1 `address`
+fn physical(address: u32) -> u32 {
+    match address {
+        0xC000_0000.. => address,
+        _             => address & 0x1FFF_FFFF,
+    }
+}
@@ -0,0 +1,280 @@
+//! The R3000A integer core with its load delay slot
+
+use crate::{Fault, bus::Bus};
+
+/// The register state of the R3000A
+///
+/// Loads and `mfc0`/`mfc2` don't write their register right away. The value is kept in
+/// `load` and only written while the next instruction executes, which therefore still reads
+/// the old value. Only `lwl` and `lwr` see the pending value, the hardware forwards it to them.
+pub struct Cpu {
+    /// The registers as the current instruction reads them
+    pub regs:    [u32; 32],
+    /// The registers as the current instruction writes them
+    out_regs:    [u32; 32],
+    /// The address of the next instruction
+    pub pc:      u32,
+    /// The address of the instruction after `pc`, a branch changes this to its target
+    pub next_pc: u32,
+    pub hi:      u32,
+    pub lo:      u32,
+    /// The register, value and address of the load waiting for its delay slot to pass
+    load:        Option<(usize, u32, u32)>,
+    /// The registers of the system control coprocessor
+    pub cop0:    [u32; 32],
+    /// The GTE registers, data then control, the GTE commands are not emulated
+    pub cop2:    [u32; 64],
+}
+
+impl Cpu {
+    /// Creates a new `Cpu` starting at `pc`
+    ///
+    /// Returns: The new `Cpu`
+    pub fn new(pc: u32) -> Cpu {
+        Cpu{regs: [0; 32], out_regs: [0; 32], pc, next_pc: pc.wrapping_add(4), hi: 0, lo: 0, load: None, cop0: [0; 32], cop2: [0; 64]}
+    }
+
+    /// Sets register `idx` right away, e.g. to set up GP and SP
+    pub fn set_register(&mut self, idx: usize, value: u32) {
+        self.regs[idx]     = value;
+        self.out_regs[idx] = value;
+        self.regs[0]       = 0;
+        self.out_regs[0]   = 0;
+    }
+
+    /// Writes a pending load right away, e.g. before leaving to emulated BIOS code
+    pub fn flush_load(&mut self) {
+        if let Some((idx, value, _)) = self.load.take() {
+            self.set_register(idx, value);
+        }
+    }
+
+    /// Returns: The register, value and address of the load landing with the next instruction
+    pub fn pending_load(&self) -> Option<(usize, u32, u32)> {
+        self.load
+    }
+
+    /// Continues at `address` as if jumped to without a delay slot
+    pub fn jump(&mut self, address: u32) {
+        self.pc      = address;
+        self.next_pc = address.wrapping_add(4);
+    }
+
+    /// Executes the instruction at `pc`
+    ///
+    /// Arguments:
+    /// * `bus`: The memory and IO the instruction accesses
+    ///
+    /// Returns: A `Fault` if the instruction can't be executed
+    pub fn step(&mut self, bus: &mut Bus) -> Result<(), Fault> {
+        let pc = self.pc;
+        if !pc.is_multiple_of(4) {
+            return Err(Fault::Unaligned{pc, address: pc});
+        }
+
+        let word = bus.load32(pc).map_err(|address| Fault::Bus{pc, address})?;
+        self.pc      = self.next_pc;
+        self.next_pc = self.next_pc.wrapping_add(4);
+
+        // The load of the previous instruction lands now, after this instruction read its operands
+        if let Some((idx, value, _)) = self.load.take() {
+            self.out_regs[idx] = value;
+        }
+
+        let result = self.execute(pc, word, bus);
+
+        self.out_regs[0] = 0;
+        self.regs        = self.out_regs;
+        result
+    }
+
+    fn set(&mut self, idx: u32, value: u32) {
+        self.out_regs[idx as usize] = value;
+    }
+
+    fn delay_load(&mut self, pc: u32, idx: u32, value: u32) {
+        self.load = (idx != 0).then_some((idx as usize, value, pc));
+    }
+
+    fn branch(&mut self, target: u32) {
+        self.next_pc = target;
+    }
+
+    fn execute(&mut self, pc: u32, word: u32, bus: &mut Bus) -> Result<(), Fault> {
+        let opcode = word >> 26;
+        let rs     = (word >> 21) & 0x1F;
+        let rt     = (word >> 16) & 0x1F;
+        let rd     = (word >> 11) & 0x1F;
+        let shamt  = (word >> 6) & 0x1F;
+        let funct  = word & 0x3F;
+        let imm    = word & 0xFFFF;
+        let simm   = word as u16 as i16 as i32 as u32;
+
+        let s = self.regs[rs as usize];
+        let t = self.regs[rt as usize];
+
+        // `self.pc` already points to the delay slot
+        let branch_target = self.pc.wrapping_add(simm << 2);
+        let address       = s.wrapping_add(simm);
+        let unaligned     = |size: u32| if !address.is_multiple_of(size) {Err(Fault::Unaligned{pc, address})} else {Ok(())};
+        let bus_fault     = |address| Fault::Bus{pc, address};
+        let overflow      = |value: Option<i32>| value.map(|value| value as u32).ok_or(Fault::Overflow{pc});
+
+        match opcode {
+            0x00 => match funct {
+                0x00 => self.set(rd, t << shamt),
+                0x02 => self.set(rd, t >> shamt),
+                0x03 => self.set(rd, ((t as i32) >> shamt) as u32),
+                0x04 => self.set(rd, t << (s & 0x1F)),
+                0x06 => self.set(rd, t >> (s & 0x1F)),
+                0x07 => self.set(rd, ((t as i32) >> (s & 0x1F)) as u32),
+                0x08 => self.branch(s),
+                0x09 => {
+                    self.set(rd, self.next_pc);
+                    self.branch(s);
+                },
+                0x0C => return Err(Fault::Syscall{pc}),
+                0x0D => return Err(Fault::Break{pc}),
+                0x10 => self.set(rd, self.hi),
+                0x11 => self.hi = s,
+                0x12 => self.set(rd, self.lo),
+                0x13 => self.lo = s,
+                0x18 => {
+                    let product = (s as i32 as i64)*(t as i32 as i64);
+                    (self.hi, self.lo) = ((product >> 32) as u32, product as u32);
+                },
+                0x19 => {
+                    let product = (s as u64)*(t as u64);
+                    (self.hi, self.lo) = ((product >> 32) as u32, product as u32);
+                },
+                0x1A => {
+                    // Division by zero and overflow give these results on the R3000A
+                    (self.hi, self.lo) = match (s as i32, t as i32) {
+                        (n, 0) if n >= 0    => (s, 0xFFFF_FFFF),
+                        (_, 0)              => (s, 1),
+                        (i32::MIN, -1)      => (0, 0x8000_0000),
+                        (n, d)              => ((n % d) as u32, (n / d) as u32),
+                    };
+                },
+                0x1B => (self.hi, self.lo) = match t {
+                    0 => (s, 0xFFFF_FFFF),
+                    _ => (s % t, s / t),
+                },
+                0x20 => self.set(rd, overflow((s as i32).checked_add(t as i32))?),
+                0x21 => self.set(rd, s.wrapping_add(t)),
+                0x22 => self.set(rd, overflow((s as i32).checked_sub(t as i32))?),
+                0x23 => self.set(rd, s.wrapping_sub(t)),
+                0x24 => self.set(rd, s & t),
+                0x25 => self.set(rd, s | t),
+                0x26 => self.set(rd, s ^ t),
+                0x27 => self.set(rd, !(s | t)),
+                0x2A => self.set(rd, ((s as i32) < (t as i32)) as u32),
+                0x2B => self.set(rd, (s < t) as u32),
+                _    => return Err(Fault::Reserved{pc, word}),
+            },
+            0x01 => {
+                let taken = match rt & 0x01 {
+                    0 => (s as i32) < 0,
+                    _ => (s as i32) >= 0,
+                };
+
+                // `bltzal` and `bgezal` link even if the branch is not taken
+                if rt & 0x1E == 0x10 {
+                    self.set(31, self.next_pc);
+                }
+                if taken {
+                    self.branch(branch_target);
+                }
+            },
+            0x02 => self.branch((self.pc & 0xF000_0000) | ((word & 0x03FF_FFFF) << 2)),
+            0x03 => {
+                self.set(31, self.next_pc);
+                self.branch((self.pc & 0xF000_0000) | ((word & 0x03FF_FFFF) << 2));
+            },
+            0x04 => if s == t {self.branch(branch_target)},
+            0x05 => if s != t {self.branch(branch_target)},
+            0x06 => if (s as i32) <= 0 {self.branch(branch_target)},
+            0x07 => if (s as i32) > 0 {self.branch(branch_target)},
+            0x08 => self.set(rt, overflow((s as i32).checked_add(simm as i32))?),
+            0x09 => self.set(rt, s.wrapping_add(simm)),
+            0x0A => self.set(rt, ((s as i32) < (simm as i32)) as u32),
+            0x0B => self.set(rt, (s < simm) as u32),
+            0x0C => self.set(rt, s & imm),
+            0x0D => self.set(rt, s | imm),
+            0x0E => self.set(rt, s ^ imm),
+            0x0F => self.set(rt, imm << 16),
+            0x10 => match rs {
+                0x00 => self.delay_load(pc, rt, self.cop0[rd as usize]),
+                0x04 => self.cop0[rd as usize] = t,
+                // `rfe` pops the interrupt enable and mode bits
+                0x10 if funct == 0x10 => {
+                    let sr = self.cop0[12];
+                    self.cop0[12] = (sr & !0x0F) | ((sr >> 2) & 0x0F);
+                },
+                _ => return Err(Fault::Reserved{pc, word}),
+            },
+            0x12 => match rs {
+                0x00 => self.delay_load(pc, rt, self.cop2[rd as usize]),
+                0x02 => self.delay_load(pc, rt, self.cop2[32 + rd as usize]),
+                0x04 => self.cop2[rd as usize] = t,
+                0x06 => self.cop2[32 + rd as usize] = t,
+                _    => return Err(Fault::Unsupported{pc, word}),
+            },
+            0x20 => self.delay_load(pc, rt, bus.load8(address).map_err(bus_fault)? as i8 as u32),
+            0x21 => {
+                unaligned(2)?;
+                self.delay_load(pc, rt, bus.load16(address).map_err(bus_fault)? as i16 as u32);
+            },
+            0x22 | 0x26 => {
+                let aligned = bus.load32(address & !3).map_err(bus_fault)?;
+                // Unlike everything else `lwl` and `lwr` merge into the pending load
+                let current = self.out_regs[rt as usize];
+                let shift   = (address & 3)*8;
+
+                let merged = match opcode {
+                    0x22 => (current & (0x00FF_FFFF >> shift)) | (aligned << (24 - shift)),
+                    _    => (current & !(0xFFFF_FFFF >> shift)) | (aligned >> shift),
+                };
+                self.delay_load(pc, rt, merged);
+            },
+            0x23 => {
+                unaligned(4)?;
+                self.delay_load(pc, rt, bus.load32(address).map_err(bus_fault)?);
+            },
+            0x24 => self.delay_load(pc, rt, bus.load8(address).map_err(bus_fault)? as u32),
+            0x25 => {
+                unaligned(2)?;
+                self.delay_load(pc, rt, bus.load16(address).map_err(bus_fault)? as u32);
+            },
+            0x28 => bus.store8(address, t as u8).map_err(bus_fault)?,
+            0x29 => {
+                unaligned(2)?;
+                bus.store16(address, t as u16).map_err(bus_fault)?;
+            },
+            0x2A | 0x2E => {
+                let aligned = bus.load32(address & !3).map_err(bus_fault)?;
+                let shift   = (address & 3)*8;
+
+                let merged = match opcode {
+                    0x2A => (aligned & !(0xFFFF_FFFF >> (24 - shift))) | (t >> (24 - shift)),
+                    _    => (aligned & !(0xFFFF_FFFF << shift)) | (t << shift),
+                };
+                bus.store32(address & !3, merged).map_err(bus_fault)?;
+            },
+            0x2B => {
+                unaligned(4)?;
+                bus.store32(address, t).map_err(bus_fault)?;
+            },
+            0x32 => {
+                unaligned(4)?;
+                self.cop2[rt as usize] = bus.load32(address).map_err(bus_fault)?;
+            },
+            0x3A => {
+                unaligned(4)?;
+                bus.store32(address, self.cop2[rt as usize]).map_err(bus_fault)?;
+            },
+            _ => return Err(Fault::Reserved{pc, word}),
+        }
+        Ok(())
+    }
+}
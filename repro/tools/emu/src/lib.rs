@@ -0,0 +1,305 @@
+//! A headless R3000A interpreter running the PS-X EXE of the app
+//!
+//! Only what the sdk needs is there: the integer core with its load delay slot, RAM, the
+//! scratchpad, the controller port backed by the `VirtualPort` of the sdk, the TTY functions
+//! of the BIOS `A0` table and the events of the `B0` table, which never fire. Reproducing the
+//! bug then comes down to running `app.exe` and looking for `Good!` or `Bad...` in the TTY
+//! output.
+
+pub mod bios;
+pub mod bus;
+pub mod cpu;
+pub mod strict;
+
+use std::fmt;
+
+use psxexe::Exe;
+use sdk::peripheral::serial_bus::VirtualPort;
+
+use crate::{bus::Bus, cpu::Cpu, strict::{StaleRead, Tracker}};
+
+const GP: usize = 28;
+const SP: usize = 29;
+const FP: usize = 30;
+
+/// Why the interpreter stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// The instruction at `pc` accessed `address`, which is not mapped
+    Bus{pc: u32, address: u32},
+    /// The instruction at `pc` accessed the unaligned `address`
+    Unaligned{pc: u32, address: u32},
+    /// `add`, `addi` or `sub` at `pc` overflowed
+    Overflow{pc: u32},
+    /// `syscall` at `pc`, there is no BIOS to handle it
+    Syscall{pc: u32},
+    /// `break` at `pc`
+    Break{pc: u32},
+    /// `word` at `pc` is no R3000A instruction
+    Reserved{pc: u32, word: u32},
+    /// `word` at `pc` is a valid instruction that is not emulated, like GTE commands
+    Unsupported{pc: u32, word: u32},
+    /// The BIOS `function` of the table at `vector` is not emulated
+    Bios{vector: u32, function: u32},
+    /// A branch decided on a value computed from the stale `read`, only raised in strict mode
+    StaleBranch{read: StaleRead},
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::Bus{pc, address}         => write!(f, "{pc:08x}: access of unmapped address {address:08x}"),
+            Fault::Unaligned{pc, address}   => write!(f, "{pc:08x}: unaligned access of {address:08x}"),
+            Fault::Overflow{pc}             => write!(f, "{pc:08x}: arithmetic overflow"),
+            Fault::Syscall{pc}              => write!(f, "{pc:08x}: syscall"),
+            Fault::Break{pc}                => write!(f, "{pc:08x}: break"),
+            Fault::Reserved{pc, word}       => write!(f, "{pc:08x}: reserved instruction {word:08x}"),
+            Fault::Unsupported{pc, word}    => write!(f, "{pc:08x}: instruction {word:08x} is not emulated"),
+            Fault::Bios{vector, function}   => write!(f, "BIOS function {vector:X}h:{function:02X}h is not emulated"),
+            Fault::StaleBranch{read}        => write!(f, "branch on a stale value, {read}"),
+        }
+    }
+}
+
+/// Why `Machine::run` returned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    /// The instruction limit was reached
+    Limit,
+    /// The condition on the TTY output was met
+    Condition,
+}
+
+/// A PlayStation with the app loaded
+pub struct Machine {
+    pub cpu:          Cpu,
+    pub bus:          Bus,
+    /// Everything written to the TTY so far
+    pub tty:          String,
+    /// The number of executed instructions
+    pub instructions: u64,
+    /// The number of events opened with the BIOS
+    pub events:       u32,
+    /// Follows the values read in load delay slots if set, see `enable_strict`
+    pub strict:       Option<Tracker>,
+}
+
+impl Machine {
+    /// Loads `exe` like the BIOS would: the text segment to its load address, PC, GP and SP
+    /// from the header
+    ///
+    /// Arguments:
+    /// * `exe`: The PS-X EXE to run
+    /// * `port`: What is plugged into controller port A
+    ///
+    /// Returns: The `Machine` ready to run or a `Fault` if the text segment is not in RAM
+    pub fn new(exe: &Exe, port: VirtualPort) -> Result<Machine, Fault> {
+        let mut machine = Machine{cpu: Cpu::new(exe.header.pc), bus: Bus::new(port), tty: String::new(), instructions: 0, events: 0, strict: None};
+
+        machine.bus.write(exe.header.load_addr, exe.text).map_err(|address| Fault::Bus{pc: exe.header.pc, address})?;
+        machine.cpu.set_register(GP, exe.header.gp);
+        machine.cpu.set_register(SP, exe.header.sp());
+        machine.cpu.set_register(FP, exe.header.sp());
+        Ok(machine)
+    }
+
+    /// Records every read of a register in the delay slot of its load and stops with
+    /// `Fault::StaleBranch` once a branch depends on a stale value
+    pub fn enable_strict(&mut self) {
+        self.strict = Some(Tracker::default());
+    }
+
+    /// Returns: The address of the current instruction and the calls leading to it, innermost first
+    pub fn backtrace(&self) -> Vec<u32> {
+        let calls = self.strict.iter().flat_map(|tracker| tracker.calls());
+        std::iter::once(self.cpu.pc).chain(calls).collect()
+    }
+
+    /// Executes a single instruction or BIOS call
+    ///
+    /// Returns: A `Fault` if the machine can't continue
+    pub fn step(&mut self) -> Result<(), Fault> {
+        self.instructions += 1;
+
+        match self.cpu.pc & 0x1FFF_FFFF {
+            bios::A0_VECTOR => return bios::call_a0(self),
+            bios::B0_VECTOR => return bios::call_b0(self),
+            _               => {},
+        }
+
+        if let Some(tracker) = &mut self.strict {
+            let pc = self.cpu.pc;
+            // A failing fetch is reported by the `Cpu` right after
+            if let Ok(word) = self.bus.load32(pc) {
+                tracker.observe(&self.cpu, pc, word)?;
+            }
+        }
+        self.cpu.step(&mut self.bus)
+    }
+
+    /// Runs until `limit` instructions were executed or `until` returns `true` for the TTY output
+    ///
+    /// Arguments:
+    /// * `limit`: The most instructions to execute
+    /// * `until`: Checked whenever the TTY output changed
+    ///
+    /// Returns: Why the machine stopped or the `Fault` it stopped with
+    pub fn run(&mut self, limit: u64, mut until: impl FnMut(&str) -> bool) -> Result<Stop, Fault> {
+        for _ in 0..limit {
+            let written = self.tty.len();
+            self.step()?;
+
+            if self.tty.len() != written && until(&self.tty) {
+                return Ok(Stop::Condition);
+            }
+        }
+        Ok(Stop::Limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use psxexe::Header;
+
+    use super::*;
+
+    const LOAD_ADDR: u32 = 0x8001_0000;
+    const DATA:      u32 = 0x8001_0100;
+
+    /// Loads `program` at `LOAD_ADDR` with `data` at `DATA`
+    fn load(program: &[u32], data: &[u8]) -> Machine {
+        let mut text: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        text.resize((DATA - LOAD_ADDR) as usize, 0);
+        text.extend_from_slice(data);
+
+        let header = Header{pc: LOAD_ADDR, gp: 0, load_addr: LOAD_ADDR, size: text.len() as u32, stack: 0x801F_FF00, stack_offset: 0, marker: String::new()};
+        let exe    = Exe{header, text: &text};
+        Machine::new(&exe, VirtualPort::new(None)).unwrap()
+    }
+
+    /// Runs `machine` until it reaches the `jr ra` at the end of its program
+    fn finish(machine: &mut Machine) -> Result<(), Fault> {
+        while machine.cpu.pc != 0 {
+            machine.step()?;
+        }
+        Ok(())
+    }
+
+    fn run(program: &[u32], data: &[u8]) -> Machine {
+        let mut machine = load(program, data);
+        finish(&mut machine).unwrap();
+        machine
+    }
+
+    #[test]
+    fn load_delay() {
+        let machine = run(&[
+            0x3C04_8001, // lui   a0,0x8001
+            0x2402_0007, // li    v0,7
+            0x8C82_0100, // lw    v0,256(a0)
+            0x0040_1825, // move  v1,v0      <- still sees 7
+            0x0040_2825, // move  a1,v0
+            0x03E0_0008, // jr    ra
+            0x0000_0000, // nop
+        ], &0x1234_5678u32.to_le_bytes());
+
+        assert_eq!(machine.cpu.regs[3], 7);
+        assert_eq!(machine.cpu.regs[5], 0x1234_5678);
+    }
+
+    #[test]
+    fn load_in_delay_slot() {
+        let machine = run(&[
+            0x3C04_8001, // lui   a0,0x8001
+            0x1000_0002, // b     +2
+            0x9082_0100, // lbu   v0,256(a0)
+            0x0000_0000, // nop
+            0x0040_1825, // move  v1,v0      <- the branch target still sees 0
+            0x0040_2825, // move  a1,v0
+            0x03E0_0008, // jr    ra
+            0x0000_0000, // nop
+        ], &[0x01]);
+
+        assert_eq!(machine.cpu.regs[3], 0);
+        assert_eq!(machine.cpu.regs[5], 1);
+    }
+
+    #[test]
+    fn unaligned_loads_merge() {
+        let machine = run(&[
+            0x3C04_8001, // lui   a0,0x8001
+            0x8882_0104, // lwl   v0,260(a0)
+            0x9882_0101, // lwr   v0,257(a0)
+            0x0000_0000, // nop
+            0x03E0_0008, // jr    ra
+            0x0000_0000, // nop
+        ], &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77]);
+
+        assert_eq!(machine.cpu.regs[2], 0x4433_2211);
+    }
+
+    #[test]
+    fn tty_printf() {
+        let mut data = b"%s %d%%\n\0".to_vec();
+        data.resize(16, 0);
+        data.extend_from_slice(b"Good!\0");
+
+        let machine = run(&[
+            0x3C04_8001, // lui   a0,0x8001
+            0x2484_0100, // addiu a0,a0,256
+            0x2485_0010, // addiu a1,a0,16
+            0x2406_FFFF, // li    a2,-1
+            0x03E0_8025, // move  s0,ra
+            0x240A_00A0, // li    t2,160
+            0x0140_F809, // jalr  t2
+            0x2409_003F, // li    t1,63
+            0x0200_0008, // jr    s0
+            0x0000_0000, // nop
+        ], &data);
+
+        assert_eq!(machine.tty, "Good! -1%\n");
+    }
+
+    #[test]
+    fn bios_events() {
+        let machine = run(&[
+            0x03E0_8025, // move  s0,ra
+            0x240A_00B0, // li    t2,176
+            0x0140_F809, // jalr  t2
+            0x2409_0008, // li    t1,8
+            0x0040_2025, // move  a0,v0
+            0x0140_F809, // jalr  t2
+            0x2409_000C, // li    t1,12
+            0x0200_0008, // jr    s0
+            0x0000_0000, // nop
+        ], &[]);
+
+        assert_eq!((machine.events, machine.cpu.regs[4], machine.cpu.regs[2]), (1, 0xF100_0000, 1));
+    }
+
+    #[test]
+    fn strict_stale_branch() {
+        let program = |old: u32| [
+            0x3C04_8001,        // lui   a0,0x8001
+            0x2402_0000 | old,  // li    v0,old
+            0x9082_0100,        // lbu   v0,256(a0)
+            0x0040_1825,        // move  v1,v0      <- still sees old
+            0x1460_0001,        // bnez  v1,+1
+            0x0000_0000,        // nop
+            0x03E0_0008,        // jr    ra
+            0x0000_0000,        // nop
+        ];
+
+        let mut machine = load(&program(7), &[0x01]);
+        machine.enable_strict();
+        let read = StaleRead{pc: LOAD_ADDR + 12, load_pc: LOAD_ADDR + 8, register: 2, old: 7, new: 1};
+        assert_eq!(finish(&mut machine), Err(Fault::StaleBranch{read}));
+        assert_eq!(machine.backtrace(), [LOAD_ADDR + 16]);
+
+        // The same read is harmless if the old value happens to be the new one
+        let mut machine = load(&program(1), &[0x01]);
+        machine.enable_strict();
+        assert_eq!(finish(&mut machine), Ok(()));
+        assert_eq!(machine.strict.unwrap().stale_reads.len(), 1);
+    }
+}
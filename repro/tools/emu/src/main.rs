@@ -0,0 +1,134 @@
+//! `emu <app.exe> [--instructions <count>] [--expect <text>] [--unplugged] [--strict] [--map <app.elf | app.map>]`
+//!
+//! Runs a PS-X EXE headless with a digital pad in port A and writes its TTY output to stdout.
+//! With `--expect` the run stops as soon as the output contains the text and fails if it never
+//! does, which turns the reproduction into a test: `emu app.exe --expect Good!`.
+//!
+//! `--strict` fails the run as soon as a branch depends on a register read in the delay slot
+//! of its load, and prints a backtrace with the symbols of the ELF `psx-ld` linked the EXE
+//! from, `target/app.map` or `--map`.
+
+use std::{env, fs, io::Write, path::Path, process::ExitCode};
+
+use disasm::{default_symbols, map::Map};
+use emu::{Fault, Machine, Stop};
+use psxexe::Exe;
+use sdk::peripheral::serial_bus::{VirtualPort, virtual_port::{VirtualDevice, VirtualDigitalPad, VirtualPad}};
+
+/// Enough for a few dozen updates of the app
+const DEFAULT_INSTRUCTIONS: u64 = 100_000_000;
+/// The output is forwarded to stdout in chunks of this many instructions
+const CHUNK: u64 = 100_000;
+
+struct Options {
+    exe:          String,
+    instructions: u64,
+    expect:       Option<String>,
+    unplugged:    bool,
+    strict:       bool,
+    map:          Option<String>,
+}
+
+fn parse_options() -> Option<Options> {
+    let mut args    = env::args().skip(1);
+    let mut options = Options{exe: String::new(), instructions: DEFAULT_INSTRUCTIONS, expect: None, unplugged: false, strict: false, map: None};
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--instructions" => options.instructions = args.next()?.parse().ok()?,
+            "--expect"       => options.expect = Some(args.next()?),
+            "--unplugged"    => options.unplugged = true,
+            "--strict"       => options.strict = true,
+            "--map"          => options.map = Some(args.next()?),
+            _ if options.exe.is_empty() => options.exe = arg,
+            _                => return None,
+        }
+    }
+    (!options.exe.is_empty()).then_some(options)
+}
+
+fn main() -> ExitCode {
+    let Some(options) = parse_options() else {
+        eprintln!("Usage: emu <app.exe> [--instructions <count>] [--expect <text>] [--unplugged] [--strict] [--map <app.elf | app.map>]");
+        return ExitCode::from(2);
+    };
+
+    let file = match fs::read(&options.exe) {
+        Ok(file)   => file,
+        Err(error) => {
+            eprintln!("{}: {error}", options.exe);
+            return ExitCode::from(2);
+        },
+    };
+    let exe = match Exe::parse(&file) {
+        Ok(exe)    => exe,
+        Err(error) => {
+            eprintln!("{}: {error}", options.exe);
+            return ExitCode::from(2);
+        },
+    };
+
+    let port = match options.unplugged {
+        true  => VirtualPort::new(None),
+        false => VirtualPort::new(Some(VirtualDevice::Pad(VirtualPad::DigitalPad(VirtualDigitalPad::new())))),
+    };
+    let mut machine = match Machine::new(&exe, port) {
+        Ok(machine) => machine,
+        Err(fault)  => {
+            eprintln!("{}: {fault}", options.exe);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    if options.strict {
+        machine.enable_strict();
+    }
+
+    let mut printed = 0;
+    let mut result  = Ok(Stop::Limit);
+    while machine.instructions < options.instructions {
+        let chunk = CHUNK.min(options.instructions - machine.instructions);
+        result = machine.run(chunk, |tty| options.expect.as_deref().is_some_and(|expect| tty.contains(expect)));
+
+        print!("{}", &machine.tty[printed..]);
+        printed = machine.tty.len();
+        if result != Ok(Stop::Limit) {
+            break;
+        }
+    }
+    let _ = std::io::stdout().flush();
+
+    if let Some(tracker) = &machine.strict {
+        let stale = tracker.stale_reads.iter().filter(|read| read.old != read.new).count();
+        eprintln!("{}: {} reads in load delay slots, {stale} of them stale", options.exe, tracker.stale_reads.len());
+    }
+
+    match (result, &options.expect) {
+        (Err(fault), _)          => {
+            eprintln!("{}: stopped after {} instructions: {fault}", options.exe, machine.instructions);
+            if matches!(fault, Fault::StaleBranch{..}) {
+                print_backtrace(&machine, &options.exe, options.map.as_deref());
+            }
+            ExitCode::FAILURE
+        },
+        (Ok(Stop::Condition), _) => ExitCode::SUCCESS,
+        (Ok(Stop::Limit), None)  => ExitCode::SUCCESS,
+        (Ok(Stop::Limit), Some(expect)) => {
+            eprintln!("{}: no {expect:?} in the TTY output after {} instructions", options.exe, machine.instructions);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn print_backtrace(machine: &Machine, exe: &str, map: Option<&str>) {
+    let map = map.map(Into::into).or_else(|| default_symbols(Path::new(exe)));
+    let map = map.and_then(|map| Map::load(&map).ok()).unwrap_or_default();
+
+    eprintln!("Backtrace:");
+    for (idx, address) in machine.backtrace().into_iter().enumerate() {
+        match map.lookup(address) {
+            Some((symbol, offset)) => eprintln!("{idx:4}: {address:08x} <{}+{offset:#x}>", symbol.name),
+            None                   => eprintln!("{idx:4}: {address:08x}"),
+        }
+    }
+}
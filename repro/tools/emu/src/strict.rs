@@ -0,0 +1,177 @@
+//! Tracks reads of registers in load delay slots and where the stale values go
+//!
+//! An interlocking MIPS core would stall until the load is done, the R3000A hands out the old
+//! value instead. Every instruction reading a register in the delay slot of its load is
+//! recorded with both values. If they differ the destination of the instruction is marked as
+//! stale, and so is everything computed from it. A branch deciding on a stale register stops
+//! the run with `Fault::StaleBranch`. Values stored to memory are not followed.
+
+use std::fmt;
+
+use hazard::mips::register_name;
+
+use crate::{Fault, cpu::Cpu};
+
+const HI: usize = 32;
+const LO: usize = 33;
+const RA: usize = 31;
+
+/// An instruction that read a register in the delay slot of the load writing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaleRead {
+    /// The address of the instruction reading the register
+    pub pc:       u32,
+    /// The address of the load
+    pub load_pc:  u32,
+    /// The register
+    pub register: usize,
+    /// The value the instruction got
+    pub old:      u32,
+    /// The value an interlocking core would have handed out
+    pub new:      u32,
+}
+
+impl fmt::Display for StaleRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x} reads {} = {:#x} instead of {:#x} loaded at {:08x}", self.pc, register_name(self.register as u8), self.old, self.new, self.load_pc)
+    }
+}
+
+/// The registers an instruction reads and writes, bits `32` and `33` are HI and LO
+struct Operands {
+    reads:   u64,
+    /// Registers written right away, loads are handled through the pending load of the `Cpu`
+    writes:  u64,
+    /// `true` for branches and register jumps, which decide where execution continues
+    decides: bool,
+}
+
+fn operands(word: u32) -> Operands {
+    let opcode = word >> 26;
+    let rs     = 1u64 << ((word >> 21) & 0x1F);
+    let rt     = 1u64 << ((word >> 16) & 0x1F);
+    let rd     = 1u64 << ((word >> 11) & 0x1F);
+    let funct  = word & 0x3F;
+    let (hi, lo, ra) = (1u64 << HI, 1u64 << LO, 1u64 << RA);
+
+    let (reads, writes, decides) = match opcode {
+        0x00 => match funct {
+            0x00 | 0x02 | 0x03 => (rt, rd, false),
+            0x08               => (rs, 0, true),
+            0x09               => (rs, rd, true),
+            0x0C | 0x0D        => (0, 0, false),
+            0x10               => (hi, rd, false),
+            0x11               => (rs, hi, false),
+            0x12               => (lo, rd, false),
+            0x13               => (rs, lo, false),
+            0x18..=0x1B        => (rs | rt, hi | lo, false),
+            _                  => (rs | rt, rd, false),
+        },
+        0x01               => (rs, if word & (0x10 << 16) != 0 {ra} else {0}, true),
+        0x02               => (0, 0, false),
+        0x03               => (0, ra, false),
+        0x04 | 0x05        => (rs | rt, 0, true),
+        0x06 | 0x07        => (rs, 0, true),
+        0x08..=0x0E        => (rs, rt, false),
+        0x0F               => (0, rt, false),
+        0x10 | 0x12        => match (word >> 21) & 0x1F {
+            0x04 | 0x06 => (rt, 0, false),
+            _           => (0, 0, false),
+        },
+        // `lwl` and `lwr` get the pending value forwarded, only their base counts
+        0x20..=0x26 | 0x32 => (rs, 0, false),
+        0x28..=0x2E        => (rs | rt, 0, false),
+        0x3A               => (rs, 0, false),
+        _                  => (0, 0, false),
+    };
+
+    // `$zero` never holds a stale value
+    Operands{reads: reads & !1, writes: writes & !1, decides}
+}
+
+/// Follows stale values through the registers of the `Cpu`
+#[derive(Debug)]
+pub struct Tracker {
+    /// Every read in a load delay slot so far, with equal values too
+    pub stale_reads: Vec<StaleRead>,
+    /// For every register and HI and LO the index of the stale read its value comes from
+    tainted:         [Option<usize>; 34],
+    /// The addresses of the `jal` and `jalr` instructions that were not returned from yet
+    calls:           Vec<u32>,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Tracker{stale_reads: Vec::new(), tainted: [None; 34], calls: Vec::new()}
+    }
+}
+
+impl Tracker {
+    /// Looks at the instruction `word` at `pc` before `cpu` executes it
+    ///
+    /// Arguments:
+    /// * `cpu`: The CPU about to execute `word`
+    /// * `pc`: The address of `word`
+    /// * `word`: The instruction
+    ///
+    /// Returns: `Fault::StaleBranch` if `word` branches on a stale value
+    pub fn observe(&mut self, cpu: &Cpu, pc: u32, word: u32) -> Result<(), Fault> {
+        // Returning to right after the delay slot of the call
+        while self.calls.last().is_some_and(|call| call.wrapping_add(8) == pc) {
+            self.calls.pop();
+        }
+
+        let operands  = operands(word);
+        let mut stale = None;
+        let landing   = cpu.pending_load();
+
+        if let Some((register, new, load_pc)) = landing && operands.reads & (1 << register) != 0 {
+            let old = cpu.regs[register];
+            self.stale_reads.push(StaleRead{pc, load_pc, register, old, new});
+            if old != new {
+                stale = Some(self.stale_reads.len() - 1);
+            }
+        }
+
+        let influence = stale.or_else(|| (0..self.tainted.len()).filter(|idx| operands.reads & (1 << idx) != 0).find_map(|idx| self.tainted[idx]));
+        if let Some(read) = influence && operands.decides {
+            return Err(Fault::StaleBranch{read: self.stale_reads[read].clone()});
+        }
+
+        if let Some((register, _, _)) = landing {
+            self.tainted[register] = None;
+        }
+        for idx in (0..self.tainted.len()).filter(|idx| operands.writes & (1 << idx) != 0) {
+            self.tainted[idx] = influence;
+        }
+
+        if matches!((word >> 26, word & 0x3F), (0x03, _) | (0x00, 0x09)) {
+            self.calls.push(pc);
+        }
+        Ok(())
+    }
+
+    /// Returns: The addresses of the calls leading to the current function, innermost first
+    pub fn calls(&self) -> impl Iterator<Item = u32> + '_ {
+        self.calls.iter().rev().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operand_masks() {
+        // addu v0,v1,a0
+        let addu = operands(0x0064_1021);
+        assert_eq!((addu.reads, addu.writes, addu.decides), (1 << 3 | 1 << 4, 1 << 2, false));
+
+        // beqz v0
+        let beqz = operands(0x1040_0002);
+        assert_eq!((beqz.reads, beqz.writes, beqz.decides), (1 << 2, 0, true));
+
+        // lwl v0,4(a0)
+        assert_eq!(operands(0x8882_0004).reads, 1 << 4);
+    }
+}
@@ -0,0 +1,133 @@
+use std::{fs, path::Path, process::Command};
+
+use disasm::{linked_elf, map::Map, symbolize};
+use emu::{Machine, Stop};
+use psxexe::Exe;
+use sdk::peripheral::serial_bus::{VirtualPort, virtual_port::{VirtualDevice, VirtualDigitalPad, VirtualPad}};
+
+const WORKSPACE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../..");
+
+/// Returns: A machine running `exe` with a digital pad plugged into port A
+fn machine(exe: &Exe) -> Machine {
+    let port = VirtualPort::new(Some(VirtualDevice::Pad(VirtualPad::DigitalPad(VirtualDigitalPad::new()))));
+    Machine::new(exe, port).unwrap()
+}
+
+/// Runs the release build of the app like the `ReadMe` describes it for DuckStation
+///
+/// Build it first with `cargo +nightly psx_build --release`. This fails as long as the
+/// miscompile is there, so it only runs with `--ignored`.
+#[test]
+#[ignore = "reproduces the miscompile, needs `cargo +nightly psx_build --release`"]
+fn app_detects_the_controller() {
+    let path = format!("{WORKSPACE}/target/mipsel-sony-psx/release/app.exe");
+    let file = fs::read(&path).expect("no app.exe, build it with `cargo +nightly psx_build --release`");
+    let exe  = Exe::parse(&file).unwrap();
+
+    let mut machine = machine(&exe);
+    let stop        = machine.run(10_000_000, |tty| tty.contains('\n')).unwrap();
+
+    assert_eq!(stop, Stop::Condition);
+    assert_eq!(machine.tty, "Good!\n");
+}
+
+/// Runs the release build with the `alloc` feature, whose `main` allocates before the first
+/// frame, in a target directory of its own
+#[test]
+#[ignore = "builds the app with `cargo +nightly psx_build --release --features app/alloc`"]
+fn heap_allocates() {
+    let status = Command::new("cargo").args(["+nightly", "psx_build", "-Zbuild-std=core,alloc", "-p", "app", "--release", "--features", "alloc"])
+        .current_dir(WORKSPACE).env("CARGO_TARGET_DIR", "target/heap").status().expect("cargo could not be started");
+    assert!(status.success(), "`cargo +nightly psx_build --features app/alloc` failed");
+
+    let file = fs::read(format!("{WORKSPACE}/target/heap/mipsel-sony-psx/release/app.exe")).unwrap();
+    let exe  = Exe::parse(&file).unwrap();
+
+    let mut machine = machine(&exe);
+    let stop        = machine.run(10_000_000, |tty| tty.contains('\n')).unwrap();
+
+    // A failed allocation reports itself and panics before the controller is read
+    assert_eq!(stop, Stop::Condition);
+    assert!(machine.tty == "Good!\n" || machine.tty == "Bad...\n", "{}", machine.tty);
+}
+
+/// Runs the release build of the `panic` example, which panics two calls below `main`, and
+/// checks the report of the panic handler
+///
+/// `panic_fmt` never returns in release builds and does not save `ra`, so the backtrace has to
+/// end there with an `unwind stopped` line instead of silently.
+#[test]
+#[ignore = "builds the example with `cargo +nightly psx_build --release --example panic`"]
+fn panic_reports_the_backtrace() {
+    let status = Command::new("cargo").args(["+nightly", "psx_build", "-p", "app", "--release", "--example", "panic"])
+        .current_dir(WORKSPACE).env("CARGO_TARGET_DIR", "target/panic").status().expect("cargo could not be started");
+    assert!(status.success(), "`cargo +nightly psx_build --example panic` failed");
+
+    let path = format!("{WORKSPACE}/target/panic/mipsel-sony-psx/release/examples/panic.exe");
+    let file = fs::read(&path).unwrap();
+    let exe  = Exe::parse(&file).unwrap();
+    let map  = Map::load(&linked_elf(Path::new(&path)).expect("no ELF linked panic.exe")).unwrap();
+
+    let mut machine = machine(&exe);
+    let stop        = machine.run(10_000_000, |tty| tty.contains("unwind stopped") && tty.ends_with('\n')).unwrap();
+    assert_eq!(stop, Stop::Condition, "{}", machine.tty);
+
+    let lines: Vec<&str> = machine.tty.lines().collect();
+    assert_eq!(lines[0], "panic at app/examples/panic.rs:13:5: index out of bounds: the len is 3 but the index is 3");
+    assert!(lines[1].starts_with("last exception pc "), "{}", lines[1]);
+
+    let calls: Vec<u32> = lines[2].strip_prefix("backtrace:").unwrap().split_whitespace().map(|call| u32::from_str_radix(call, 16).unwrap()).collect();
+    let last = *calls.last().expect("no calls in the backtrace");
+    assert!(symbolize(&map, calls[0]).contains("<core::panicking::panic_fmt+"), "{}", symbolize(&map, calls[0]));
+    assert_eq!(lines[3], format!("unwind stopped at {last:08x}: ra is not saved, the function never returns"));
+}
+
+/// Runs the release build of the `constructors` example, which registers a function in each of
+/// `.ctors`, `.init_array`, `.fini_array` and `.dtors`, and checks they run in the right order
+#[test]
+#[ignore = "builds the example with `cargo +nightly psx_build --release --example constructors`"]
+fn constructors_run() {
+    let status = Command::new("cargo").args(["+nightly", "psx_build", "-p", "app", "--release", "--example", "constructors"])
+        .current_dir(WORKSPACE).env("CARGO_TARGET_DIR", "target/constructors").status().expect("cargo could not be started");
+    assert!(status.success(), "`cargo +nightly psx_build --example constructors` failed");
+
+    let file = fs::read(format!("{WORKSPACE}/target/constructors/mipsel-sony-psx/release/examples/constructors.exe")).unwrap();
+    let exe  = Exe::parse(&file).unwrap();
+
+    let mut machine = machine(&exe);
+    let stop        = machine.run(10_000_000, |tty| tty.contains("Unexpected end of main\n")).unwrap();
+
+    assert_eq!(stop, Stop::Condition);
+    assert_eq!(machine.tty, "ctors\ninit_array 1\ninit_array 2\nmain\nfini_array 2\nfini_array 1\ndtors 2\ndtors 1\nUnexpected end of main\n");
+}
+
+/// Runs the release build of the `stack_overflow` example with the `stackcheck` feature, which
+/// recurses into the canary and calls `sdk::stack::check`, and checks the overflow report
+///
+/// `emu` has no interrupts, so this covers the check but not its VBlank handler.
+#[test]
+#[ignore = "builds the example with `cargo +nightly psx_build --release --example stack_overflow --features stackcheck`"]
+fn stack_overflow_is_reported() {
+    let status = Command::new("cargo").args(["+nightly", "psx_build", "-p", "app", "--release", "--example", "stack_overflow", "--features", "stackcheck"])
+        .current_dir(WORKSPACE).env("CARGO_TARGET_DIR", "target/stack").status().expect("cargo could not be started");
+    assert!(status.success(), "`cargo +nightly psx_build --example stack_overflow` failed");
+
+    let path = format!("{WORKSPACE}/target/stack/mipsel-sony-psx/release/examples/stack_overflow.exe");
+    let file = fs::read(&path).unwrap();
+    let exe  = Exe::parse(&file).unwrap();
+    let map  = Map::load(&linked_elf(Path::new(&path)).expect("no ELF linked stack_overflow.exe")).unwrap();
+
+    let mut machine = machine(&exe);
+    let stop        = machine.run(10_000_000, |tty| tty.contains('\n')).unwrap();
+    assert_eq!(stop, Stop::Condition);
+
+    // The check in `main` before the recursion must not report anything
+    let report: Vec<u32> = machine.tty.strip_prefix("stack: overflow at pc ").and_then(|report| {
+        let report = report.strip_suffix('\n')?.replace(", sp ", " ").replace(" below ", " ");
+        report.split(' ').map(|value| u32::from_str_radix(value, 16).ok()).collect()
+    }).unwrap_or_else(|| panic!("no overflow report: {}", machine.tty));
+    let [pc, sp, canary] = report[..] else { panic!("{}", machine.tty) };
+
+    assert!(symbolize(&map, pc).contains("descend"), "{}", symbolize(&map, pc));
+    assert!(sp < canary, "{}", machine.tty);
+}
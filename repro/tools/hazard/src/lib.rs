@@ -0,0 +1,235 @@
+//! Finds MIPS I load delay hazards in LLVM assembly and objdump output
+//!
+//! The R3000A of the PlayStation does not interlock loads: the instruction right after a
+//! `lw`, `lbu`, `lh`, `lwl`, `lwr`, `mfc0`, `mfc2` and friends still sees the old value of
+//! the loaded register. A compiler targeting it has to keep the next instruction from reading
+//! that register, which is what went wrong in the `Bad` build.
+
+pub mod listing;
+pub mod mips;
+
+use std::fmt;
+
+use crate::{listing::{Instruction, Listing}, mips::Role};
+
+/// An instruction reading a register that the instruction before it loads
+#[derive(Debug, Clone)]
+pub struct Hazard {
+    /// The function of the instruction reading the register
+    pub function:  String,
+    /// The line of the load
+    pub load_line: usize,
+    /// The load, e.g. `lw $2, 0($4)`
+    pub load:      String,
+    /// The line of the instruction reading the register
+    pub line:      usize,
+    /// The instruction reading the register
+    pub user:      String,
+    /// The register as written in the load, e.g. `$2`
+    pub register:  String,
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: `{}` reads {} before `{}` on line {} loaded it", self.function, self.line, self.user, self.register, self.load, self.load_line)
+    }
+}
+
+/// Returns: The registers `instruction` reads and the register it writes
+pub fn registers(instruction: &Instruction) -> (Vec<u8>, Option<u8>) {
+    let mut reads = Vec::new();
+    let mut write = None;
+
+    for (operand, role) in instruction.operands.iter().zip(mips::roles(&instruction.mnemonic, instruction.operands.len())) {
+        match role {
+            Role::Read      => reads.extend(mips::register(operand)),
+            Role::Write     => write = mips::register(operand),
+            Role::ReadWrite => {
+                reads.extend(mips::register(operand));
+                write = mips::register(operand);
+            },
+            Role::Memory    => reads.extend(memory_base(operand)),
+            Role::Other     => {},
+        }
+    }
+    (reads, write)
+}
+
+/// Finds every instruction that reads a register loaded by the instruction executed right before it
+///
+/// Besides the next instruction in memory this follows branches: a load in the delay slot of a
+/// branch or jump is also checked against the first instruction at the branch target.
+/// Targets of `jr` and `jalr` are not known and not followed. A `lwl` followed by a `lwr` of the
+/// same register (or the other way around) is no hazard, the R3000A merges both.
+///
+/// Arguments:
+/// * `listing`: The instructions to check
+///
+/// Returns: All found hazards in the order of the loads
+pub fn find_hazards(listing: &Listing) -> Vec<Hazard> {
+    let instructions = &listing.instructions;
+    let mut hazards  = Vec::new();
+
+    for (idx, load) in instructions.iter().enumerate() {
+        if load.reorder || !mips::has_load_delay(&load.mnemonic) {
+            continue;
+        }
+
+        let Some(loaded) = registers(load).1.filter(|register| *register != 0) else {
+            continue;
+        };
+
+        for next in successors(listing, idx) {
+            let user = &instructions[next];
+            let merges = matches!((load.mnemonic.as_str(), user.mnemonic.as_str()), ("lwl", "lwr") | ("lwr", "lwl"));
+
+            if !merges && registers(user).0.contains(&loaded) {
+                hazards.push(Hazard{
+                    function:  listing.function_of(user).to_owned(),
+                    load_line: load.line,
+                    load:      load.text(),
+                    line:      user.line,
+                    user:      user.text(),
+                    register:  load.operands[0].clone(),
+                });
+            }
+        }
+    }
+    hazards
+}
+
+/// Returns: The indices of the instructions that can execute right after the one at `idx`
+fn successors(listing: &Listing, idx: usize) -> Vec<usize> {
+    let instructions = &listing.instructions;
+    let Some(branch) = idx.checked_sub(1).map(|previous| &instructions[previous]).filter(|previous| mips::is_branch(&previous.mnemonic)) else {
+        return (idx + 1 < instructions.len()).then_some(idx + 1).into_iter().collect();
+    };
+
+    // `idx` is in a delay slot
+    let mut successors = Vec::new();
+    if mips::is_conditional_branch(&branch.mnemonic) && idx + 1 < instructions.len() {
+        successors.push(idx + 1);
+    }
+
+    if mips::is_jump(&branch.mnemonic) || mips::is_conditional_branch(&branch.mnemonic) {
+        successors.extend(branch.target().and_then(|target| listing.resolve(&target)).filter(|target| !successors.contains(target)));
+    }
+    successors
+}
+
+/// Returns: The base register of a memory operand like `%lo(x)($2)` or `20(sp)`
+fn memory_base(operand: &str) -> Option<u8> {
+    let (_, base) = operand.strip_suffix(')')?.rsplit_once('(')?;
+    mips::register(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(hazards: &[Hazard]) -> Vec<usize> {
+        hazards.iter().map(|hazard| hazard.line).collect()
+    }
+
+    #[test]
+    fn next_instruction() {
+        let listing = Listing::parse_assembly("
+            .set noreorder
+        f:
+            lw      $2, 0($4)
+            addiu   $3, $2, 1
+            lw      $5, 4($4)
+            nop
+            addu    $3, $5, $3
+        ");
+        let hazards = find_hazards(&listing);
+
+        assert_eq!(lines(&hazards), [5]);
+        assert_eq!(hazards[0].function, "f");
+        assert_eq!(hazards[0].register, "$2");
+    }
+
+    #[test]
+    fn memory_operands_and_labels() {
+        let listing = Listing::parse_assembly("
+            .set noreorder
+        f:
+            lbu     $2, %lo(x)($1)
+        $BB0_1:
+            sw      $zero, 0($2)
+            mfc2    $12, $9, 0
+            sh      $12, 0($18)
+        ");
+
+        assert_eq!(lines(&find_hazards(&listing)), [6, 8]);
+    }
+
+    #[test]
+    fn delay_slots() {
+        let listing = Listing::parse_assembly("
+            .set noreorder
+        f:
+            beqz    $4, $BB0_2
+            lw      $2, 0($5)
+            move    $3, $zero
+        $BB0_2:
+            sltu    $3, $2, $4
+        ");
+
+        // The fall through doesn't read `$2`, the branch target does
+        assert_eq!(lines(&find_hazards(&listing)), [8]);
+    }
+
+    #[test]
+    fn jump_in_delay_slot() {
+        let listing = Listing::parse_assembly("
+            .set noreorder
+        f:
+            j       $BB0_1
+            lw      $2, 0($5)
+            addu    $2, $2, $2
+        $BB0_1:
+            sltu    $3, $2, $4
+        ");
+
+        assert_eq!(lines(&find_hazards(&listing)), [8]);
+    }
+
+    #[test]
+    fn exceptions() {
+        let listing = Listing::parse_assembly("
+            .set push
+            .set reorder
+        f:
+            lw      $2, 0($4)
+            addiu   $2, $2, 1
+            .set pop
+            .set noreorder
+            lwl     $3, 3($4)
+            lwr     $3, 0($4)
+            lw      $zero, 0($4)
+            addu    $2, $zero, $zero
+            lw      $5, 0($4)
+            lw      $5, 4($4)
+        ");
+
+        assert!(find_hazards(&listing).is_empty());
+    }
+
+    #[test]
+    fn objdump() {
+        let listing = Listing::parse("
+80010000 <main>:
+80010000:	8fa20010 	lw	v0,16(sp)
+80010004:	10400002 	beqz	v0,80010010 <main+0x10>
+80010008:	8c830000 	lw	v1,0(a0)
+8001000c:	00000000 	nop
+80010010:	00621021 	addu	v0,v1,v0
+        ");
+        let hazards = find_hazards(&listing);
+
+        assert_eq!(lines(&hazards), [4, 7]);
+        assert_eq!(hazards[1].function, "main");
+        assert_eq!(hazards[1].register, "v1");
+    }
+}
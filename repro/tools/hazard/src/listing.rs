@@ -0,0 +1,244 @@
+//! Reads the instructions out of LLVM assembly and objdump output
+
+use std::collections::HashMap;
+
+/// Where a branch continues
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    /// A label of LLVM assembly like `$BB31_1`
+    Label(String),
+    /// An address of objdump output
+    Address(u32),
+}
+
+/// A single instruction of a `Listing`
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    /// The line in the source, starting at `1`
+    pub line:     usize,
+    /// The index of the function in `Listing::functions`
+    pub function: usize,
+    /// The address, only known for objdump output
+    pub address:  Option<u32>,
+    /// The mnemonic, e.g. `lw`
+    pub mnemonic: String,
+    /// The operands without the separating commas
+    pub operands: Vec<String>,
+    /// `true` if the assembler fills the delay slots itself (`.set reorder`)
+    pub reorder:  bool,
+}
+
+impl Instruction {
+    /// Returns: The instruction as it would be written in assembly
+    pub fn text(&self) -> String {
+        match self.operands.is_empty() {
+            true  => self.mnemonic.clone(),
+            false => format!("{} {}", self.mnemonic, self.operands.join(", ")),
+        }
+    }
+
+    /// Returns: Where the instruction continues if it is a branch
+    pub fn target(&self) -> Option<Target> {
+        let operand = self.operands.last()?;
+
+        match self.address {
+            // `80010040 <main+0x40>` or `0x840` without symbols
+            Some(_) => {
+                let address = operand.split_whitespace().next()?;
+                u32::from_str_radix(address.trim_start_matches("0x"), 16).ok().map(Target::Address)
+            },
+            None    => Some(Target::Label(operand.clone())),
+        }
+    }
+}
+
+/// The instructions of one assembly file in the order they are placed in memory
+#[derive(Debug, Default)]
+pub struct Listing {
+    /// The names of the functions, instructions outside any function belong to `""`
+    pub functions:    Vec<String>,
+    /// All instructions
+    pub instructions: Vec<Instruction>,
+    /// The labels with the index of the instruction following them
+    labels:           HashMap<String, usize>,
+    /// The addresses with the index of their instruction
+    addresses:        HashMap<u32, usize>,
+}
+
+impl Listing {
+    /// Parses `source` as objdump output if it looks like it and as LLVM assembly otherwise
+    ///
+    /// Arguments:
+    /// * `source`: The content of the file
+    ///
+    /// Returns: The parsed `Listing`
+    pub fn parse(source: &str) -> Listing {
+        match source.lines().any(|line| objdump_instruction(line).is_some()) {
+            true  => Self::parse_objdump(source),
+            false => Self::parse_assembly(source),
+        }
+    }
+
+    /// Parses LLVM assembly as emitted with `--emit asm`
+    ///
+    /// Arguments:
+    /// * `source`: The content of the `.s` file
+    ///
+    /// Returns: The parsed `Listing`
+    pub fn parse_assembly(source: &str) -> Listing {
+        let mut listing = Listing::default();
+        let mut function = listing.function("");
+        let mut reorder  = true;
+        let mut pushed   = Vec::new();
+
+        for (idx, line) in source.lines().enumerate() {
+            let mut line = strip_comment(line).trim();
+
+            if let Some(directive) = line.strip_prefix('.') {
+                let mut words = directive.split_whitespace();
+                match (words.next(), words.next()) {
+                    (Some("ent"), Some(name))       => function = listing.function(name),
+                    (Some("set"), Some("push"))     => pushed.push(reorder),
+                    (Some("set"), Some("pop"))      => reorder = pushed.pop().unwrap_or(true),
+                    (Some("set"), Some("reorder"))  => reorder = true,
+                    (Some("set"), Some("noreorder")) => reorder = false,
+                    _                               => {},
+                }
+                continue;
+            }
+
+            // Labels can be followed by an instruction on the same line
+            while let Some((label, rest)) = split_label(line) {
+                if !label.starts_with('$') && !label.starts_with(".L") {
+                    function = listing.function(label);
+                }
+                listing.labels.insert(label.to_owned(), listing.instructions.len());
+                line = rest.trim();
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            listing.instructions.push(Instruction{
+                line: idx + 1, function, address: None, mnemonic: mnemonic.to_owned(), operands: split_operands(operands), reorder
+            });
+        }
+        listing
+    }
+
+    /// Parses the output of `objdump -d`
+    ///
+    /// Arguments:
+    /// * `source`: The output of objdump
+    ///
+    /// Returns: The parsed `Listing`
+    pub fn parse_objdump(source: &str) -> Listing {
+        let mut listing = Listing::default();
+        let mut function = listing.function("");
+
+        for (idx, line) in source.lines().enumerate() {
+            let line = line.trim();
+
+            // `80010000 <main>:`
+            if let Some((_, name)) = line.strip_suffix(">:").and_then(|line| line.split_once(" <")) {
+                function = listing.function(name);
+                continue;
+            }
+
+            let Some((address, text)) = objdump_instruction(line) else {
+                continue;
+            };
+            let text = strip_comment(text).trim();
+            let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+
+            listing.addresses.insert(address, listing.instructions.len());
+            listing.instructions.push(Instruction{
+                line: idx + 1, function, address: Some(address), mnemonic: mnemonic.to_owned(), operands: split_operands(operands), reorder: false
+            });
+        }
+        listing
+    }
+
+    /// Appends an instruction decoded from memory, e.g. by a disassembler
+    ///
+    /// Arguments:
+    /// * `function`: The name of the function the instruction belongs to
+    /// * `instruction`: The instruction, its `function` is set from `function`
+    pub fn push(&mut self, function: &str, mut instruction: Instruction) {
+        instruction.function = self.function(function);
+        if let Some(address) = instruction.address {
+            self.addresses.insert(address, self.instructions.len());
+        }
+        self.instructions.push(instruction);
+    }
+
+    /// Returns: The index of the instruction at `target` if it is part of the listing
+    pub fn resolve(&self, target: &Target) -> Option<usize> {
+        match target {
+            Target::Label(label)     => self.labels.get(label).copied(),
+            Target::Address(address) => self.addresses.get(address).copied(),
+        }
+    }
+
+    /// Returns: The name of the function `instruction` belongs to
+    pub fn function_of(&self, instruction: &Instruction) -> &str {
+        &self.functions[instruction.function]
+    }
+
+    fn function(&mut self, name: &str) -> usize {
+        match self.functions.iter().position(|function| function == name) {
+            Some(idx) => idx,
+            None      => {
+                self.functions.push(name.to_owned());
+                self.functions.len() - 1
+            },
+        }
+    }
+}
+
+/// Splits `80010000: 8fbf0014  lw ra,20(sp)` into the address and the instruction
+fn objdump_instruction(line: &str) -> Option<(u32, &str)> {
+    let (address, rest) = line.trim().split_once(':')?;
+    let address = u32::from_str_radix(address, 16).ok()?;
+    let (word, text) = rest.trim_start().split_once(char::is_whitespace)?;
+
+    (word.len() == 8 && word.chars().all(|char| char.is_ascii_hexdigit())).then_some((address, text.trim()))
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split_once('#').map_or(line, |(code, _)| code)
+}
+
+/// Splits `$BB31_1: lw $2, 0($4)` into the label and the rest
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let (label, rest) = line.split_once(':')?;
+
+    label.chars().all(|char| char.is_alphanumeric() || matches!(char, '_' | '$' | '.')).then_some((label, rest))
+}
+
+/// Splits the operands at the commas that are not inside parentheses
+fn split_operands(operands: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut depth  = 0;
+    let mut start  = 0;
+
+    for (idx, char) in operands.char_indices() {
+        match char {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(operands[start..idx].trim().to_owned());
+                start = idx + 1;
+            },
+            _ => {},
+        }
+    }
+
+    let last = operands[start..].trim();
+    if !last.is_empty() {
+        result.push(last.to_owned());
+    }
+    result
+}
@@ -0,0 +1,44 @@
+//! `hazard <file>...`
+//!
+//! Checks LLVM assembly (`--emit asm`) or objdump output for load delay hazards and prints one
+//! line per hazard. Exits with `1` if any were found.
+//!
+//! The objdump of a PS-X EXE can be created with
+//! `mipsel-linux-gnu-objdump -D -b binary -m mips -EL app.exe`.
+
+use std::{env, fs, process::ExitCode};
+
+use hazard::{find_hazards, listing::Listing};
+
+fn main() -> ExitCode {
+    let files: Vec<String> = env::args().skip(1).collect();
+    if files.is_empty() {
+        eprintln!("Usage: hazard <file.s | objdump.txt>...");
+        return ExitCode::from(2);
+    }
+
+    let mut found = 0;
+    for file in &files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{file}: {error}");
+                return ExitCode::from(2);
+            },
+        };
+
+        let listing = Listing::parse(&source);
+        let hazards = find_hazards(&listing);
+        for hazard in &hazards {
+            println!("{file}:{hazard}");
+        }
+
+        found += hazards.len();
+        eprintln!("{file}: {} hazards in {} instructions", hazards.len(), listing.instructions.len());
+    }
+
+    match found {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,127 @@
+//! What the MIPS I instructions do with their operands
+
+/// The conventional names of the general purpose registers, `fp` and `s8` are both `$30`
+const NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3",
+    "t0",   "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+    "s0",   "s1", "s2", "s3", "s4", "s5", "s6", "s7",
+    "t8",   "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+/// How an instruction uses one of its operands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    /// A general purpose register that is read
+    Read,
+    /// A general purpose register that is written
+    Write,
+    /// A general purpose register that is merged into, like the target of `lwl`
+    ReadWrite,
+    /// A memory operand `offset(base)`, the base register is read
+    Memory,
+    /// An immediate, a label or a coprocessor register
+    Other,
+}
+
+/// Parses a general purpose register in LLVM (`$2`, `$sp`) or objdump (`v0`) syntax
+///
+/// Arguments:
+/// * `operand`: The operand to parse
+///
+/// Returns: The number of the register or `None` if `operand` is no register
+pub fn register(operand: &str) -> Option<u8> {
+    let name = operand.trim().trim_start_matches('$');
+
+    if let Ok(number) = name.parse::<u8>() {
+        return (number < 32 && operand.trim().starts_with('$')).then_some(number);
+    }
+
+    match name {
+        "s8" => Some(30),
+        name => NAMES.iter().position(|known| *known == name).map(|number| number as u8),
+    }
+}
+
+/// Returns: The conventional name of register `number`
+pub fn register_name(number: u8) -> &'static str {
+    NAMES[number as usize]
+}
+
+/// Returns: `true` if the result of `mnemonic` is only available one instruction later
+pub fn has_load_delay(mnemonic: &str) -> bool {
+    matches!(mnemonic, "lb" | "lbu" | "lh" | "lhu" | "lw" | "lwl" | "lwr" | "mfc0" | "mfc2" | "cfc0" | "cfc2")
+}
+
+/// Returns: `true` if `mnemonic` has a branch delay slot
+pub fn is_branch(mnemonic: &str) -> bool {
+    is_jump(mnemonic) || is_conditional_branch(mnemonic) || matches!(mnemonic, "jr" | "jalr")
+}
+
+/// Returns: `true` if `mnemonic` always continues at its label operand
+pub fn is_jump(mnemonic: &str) -> bool {
+    matches!(mnemonic, "b" | "bal" | "j" | "jal")
+}
+
+/// Returns: `true` if `mnemonic` continues at its label operand or falls through
+pub fn is_conditional_branch(mnemonic: &str) -> bool {
+    matches!(mnemonic, "beq" | "bne" | "beqz" | "bnez" | "blez" | "bgtz" | "bltz" | "bgez" | "bltzal" | "bgezal")
+}
+
+/// Describes how `mnemonic` uses each of its `count` operands
+///
+/// Anything not listed is assumed to write the first operand and read the others, which is
+/// how most arithmetic instructions and pseudo instructions like `move` and `li` work.
+///
+/// Arguments:
+/// * `mnemonic`: The instruction
+/// * `count`: The number of operands
+///
+/// Returns: The role of every operand
+pub fn roles(mnemonic: &str, count: usize) -> Vec<Role> {
+    use Role::*;
+
+    let roles: &[Role] = match mnemonic {
+        "lb" | "lbu" | "lh" | "lhu" | "lw"              => &[Write, Memory],
+        "lwl" | "lwr"                                   => &[ReadWrite, Memory],
+        "sb" | "sh" | "sw" | "swl" | "swr"              => &[Read, Memory],
+        "lwc0" | "lwc2" | "swc0" | "swc2"               => &[Other, Memory],
+        "mfc0" | "mfc2" | "cfc0" | "cfc2"               => &[Write, Other, Other],
+        "mtc0" | "mtc2" | "ctc0" | "ctc2"               => &[Read, Other, Other],
+        "beq" | "bne"                                   => &[Read, Read, Other],
+        "beqz" | "bnez" | "blez" | "bgtz" | "bltz" | "bgez" | "bltzal" | "bgezal" => &[Read, Other],
+        "b" | "bal" | "j" | "jal"                       => &[Other],
+        "jr" | "mthi" | "mtlo"                          => &[Read],
+        "jalr" if count == 1                            => &[Read],
+        "mult" | "multu" | "div" | "divu" if count == 3 => &[Other, Read, Read],
+        "mult" | "multu" | "div" | "divu" | "teq" | "tne" | "tge" | "tgeu" | "tlt" | "tltu" => &[Read, Read, Other],
+        "nop" | "syscall" | "break" | "rfe" | "cop2"    => &[],
+        _                                               => &[Write, Read, Read],
+    };
+
+    (0..count).map(|idx| roles.get(idx).copied().unwrap_or(if roles.is_empty() {Other} else {Read})).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers() {
+        assert_eq!(register("$2"), Some(2));
+        assert_eq!(register("$sp"), Some(29));
+        assert_eq!(register("v0"), Some(2));
+        assert_eq!(register("s8"), Some(30));
+        assert_eq!(register("$32"), None);
+        assert_eq!(register("12"), None);
+        assert_eq!(register("main"), None);
+    }
+
+    #[test]
+    fn operand_roles() {
+        assert_eq!(roles("lw", 2), [Role::Write, Role::Memory]);
+        assert_eq!(roles("mfc2", 3), [Role::Write, Role::Other, Role::Other]);
+        assert_eq!(roles("addu", 3), [Role::Write, Role::Read, Role::Read]);
+        assert_eq!(roles("jalr", 2), [Role::Write, Role::Read]);
+        assert_eq!(roles("nop", 0), []);
+    }
+}
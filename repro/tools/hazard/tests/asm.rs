@@ -0,0 +1,50 @@
+use std::{fs, process::Command, sync::OnceLock};
+
+use hazard::{Hazard, find_hazards, listing::Listing};
+
+const WORKSPACE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../..");
+/// A target directory of its own, the one of the workspace is locked while the tests run
+const TARGET_DIR: &str = "target/asm";
+/// Where rustc writes the `.s`, instead of `deps/app-<hash>.s` next to the ones of other flags
+const ASSEMBLY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/asm/app.s");
+
+/// Returns: The `.s` of the release build of the app, built once for all tests
+///
+/// The peripheral code is compiled into the app with LTO, which is where the hazard appears,
+/// so the `sdk` is built as part of the app instead of on its own.
+fn assembly() -> &'static str {
+    static SOURCE: OnceLock<String> = OnceLock::new();
+    SOURCE.get_or_init(|| {
+        let status = Command::new("cargo").args(["+nightly", "psx_rustc", "-p", "app", "--release", "--", "--emit", &format!("asm={ASSEMBLY},link")])
+            .current_dir(WORKSPACE).env("CARGO_TARGET_DIR", TARGET_DIR).status().expect("cargo could not be started");
+        assert!(status.success(), "`cargo +nightly psx_rustc -p app --release` failed");
+
+        fs::read_to_string(ASSEMBLY).expect("rustc emitted no .s")
+    })
+}
+
+/// Asserts that no function whose mangled symbol contains `key` reads a register in the delay slot of its load
+///
+/// `key` are the length prefixed path segments, like `3sdk10peripheral17update_controller`,
+/// which the legacy (`_ZN3sdk10peripheral17update_controller17h…E`) and the v0 mangling
+/// (`_RNvNtCs…_3sdk10peripheral17update_controller`) both contain.
+fn assert_no_hazards(key: &str) {
+    let listing = Listing::parse_assembly(assembly());
+    assert!(listing.functions.iter().any(|function| function.contains(key)), "no function matches {key}, it might have been inlined");
+
+    let hazards: Vec<Hazard> = find_hazards(&listing).into_iter().filter(|hazard| hazard.function.contains(key)).collect();
+    assert!(hazards.is_empty(), "{}", hazards.iter().map(Hazard::to_string).collect::<Vec<_>>().join("\n"));
+}
+
+/// `SerialConnection` and the `SerialBus` are inlined into it, so this covers them as well
+#[test]
+#[ignore = "reproduces the miscompile, builds the app with `cargo +nightly psx_rustc`"]
+fn update_controller() {
+    assert_no_hazards("3sdk10peripheral17update_controller");
+}
+
+#[test]
+#[ignore = "reproduces the miscompile, builds the app with `cargo +nightly psx_rustc`"]
+fn peripheral() {
+    assert_no_hazards("3sdk10peripheral");
+}
@@ -0,0 +1,272 @@
+//! Follows a global through the LLVM IR and reports how every access of it is annotated
+//!
+//! The optimizer decides what it may move around a load by the attributes of the pointer
+//! (`noalias`, `dereferenceable`) and the metadata of the load (`!noalias`, `!alias.scope`,
+//! `!range`). `trace` collects both for every load, store and call reaching the global, through
+//! constant `getelementptr`s, `getelementptr i8` instructions and the parameters of the
+//! functions the pointers are passed to.
+
+pub mod module;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::module::{Instruction, Module, matching, split_top_level};
+
+/// What an `Access` does with the pointer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Load,
+    Store,
+    /// The pointer is passed to a function or inline assembly
+    Call,
+}
+
+/// Where the pointer of an `Access` comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    /// The global itself or a constant expression on it
+    Global,
+    /// The parameter `param` of `function`, which the global was passed to
+    Param{function: String, param: String, attributes: String},
+}
+
+/// A load, store or call reaching the global
+#[derive(Debug, Clone, PartialEq)]
+pub struct Access {
+    /// The demangled name of the function
+    pub function:   String,
+    pub line:       usize,
+    pub text:       String,
+    pub kind:       Kind,
+    /// The byte offset into the global
+    pub offset:     i64,
+    /// The type loaded or stored, or the callee
+    pub target:     String,
+    /// Like `volatile` and `align 2` or the attributes of the call argument
+    pub attributes: Vec<String>,
+    /// The kind and node of every attached metadata, like `("!range", "!2")`
+    pub metadata:   Vec<(String, String)>,
+    pub origin:     Origin,
+    /// The instructions using the loaded value
+    pub users:      Vec<Instruction>,
+}
+
+/// A pointer into the global, as known in one function
+type Pointers = HashMap<String, (i64, Origin)>;
+
+/// Checks if the possibly mangled `symbol` names `name`, the length in front of the identifiers
+/// of both manglings keeps `CONTROLLERS_A` from matching `CONTROLLERS_AB`
+pub fn names(symbol: &str, name: &str) -> bool {
+    symbol == name || symbol.contains(&format!("{}{name}", name.len()))
+}
+
+/// Finds every access of the global `global`
+///
+/// Arguments:
+/// * `module`: The parsed `.ll`
+/// * `global`: The symbol of the global, like `@` will refer to it
+///
+/// Returns: The accesses in the order of the functions and lines
+pub fn trace(module: &Module, global: &str) -> Vec<Access> {
+    let mut accesses = Vec::new();
+    let mut visited  = HashSet::new();
+    // Functions and the parameters that point into the global
+    let mut pending: Vec<(String, Pointers)> = module.functions.iter().map(|function| (function.name.clone(), Pointers::new())).collect();
+
+    while let Some((name, seeds)) = pending.pop() {
+        let Some(function) = module.function(&name) else {
+            continue;
+        };
+        let mut pointers = seeds;
+        let display_name = function.display_name().to_string();
+
+        for (idx, instruction) in function.instructions.iter().enumerate() {
+            let (result, body) = match instruction.text.split_once(" = ") {
+                Some((result, body)) if result.starts_with('%') => (Some(result), body),
+                _                                               => (None, instruction.text.as_str()),
+            };
+            let parts   = split_top_level(body);
+            let pointer = |text: &str| resolve(text, global, &pointers);
+            let access  = |kind, target: &str, (offset, origin): (i64, Origin), rest: &[&str]| Access{
+                function: display_name.clone(), line: instruction.line, text: instruction.text.clone(), kind, offset, target: target.to_string(),
+                attributes: rest.iter().filter(|part| !part.starts_with('!')).map(|part| part.to_string()).collect(),
+                metadata: metadata(rest), origin, users: Vec::new(),
+            };
+
+            match parts.as_slice() {
+                [first, base, index] if first.starts_with("getelementptr") && first.ends_with(" i8") => {
+                    let offset = index.strip_prefix("i32 ").and_then(|index| index.parse::<i64>().ok());
+                    if let (Some(result), Some((base, origin)), Some(offset)) = (result, pointer(base), offset) {
+                        pointers.insert(result.to_string(), (base + offset, origin));
+                    }
+                },
+                [first, address, rest @ ..] if first.starts_with("load ") => {
+                    if let Some(found) = pointer(address) {
+                        let (volatile, target) = split_volatile(&first["load ".len()..]);
+                        let mut load = access(Kind::Load, target, found, rest);
+                        load.attributes.splice(0..0, volatile);
+                        load.users = result.map(|result| users(&function.instructions[idx + 1..], result)).unwrap_or_default();
+                        accesses.push(load);
+                    }
+                },
+                [first, address, rest @ ..] if first.starts_with("store ") => {
+                    if let Some(found) = pointer(address) {
+                        let (volatile, value) = split_volatile(&first["store ".len()..]);
+                        let mut store = access(Kind::Store, value.split(' ').next().unwrap_or_default(), found, rest);
+                        store.attributes.splice(0..0, volatile);
+                        accesses.push(store);
+                    }
+                },
+                _ if body.contains("call ") => {
+                    let Some((callee, args, rest)) = call(body) else {
+                        continue;
+                    };
+                    let rest = split_top_level(rest);
+                    for (idx, arg) in args.iter().enumerate() {
+                        let Some(found) = pointer(arg) else {
+                            continue;
+                        };
+                        let mut call = access(Kind::Call, callee, found.clone(), &rest);
+                        call.attributes = Vec::from_iter(Some(attributes(arg)).filter(|attributes| !attributes.is_empty()).map(String::from));
+                        accesses.push(call);
+
+                        let Some(param) = module.function(callee).and_then(|callee| callee.params.get(idx)) else {
+                            continue;
+                        };
+                        if visited.insert((callee.to_string(), idx, found.0)) {
+                            let origin = Origin::Param{function: module.function(callee).map_or(callee, |callee| callee.display_name()).to_string(), param: param.name.clone(), attributes: param.attributes.clone()};
+                            pending.push((callee.to_string(), Pointers::from([(param.name.clone(), (found.0, origin))])));
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    accesses.sort_by_key(|access| (access.line, access.offset));
+    accesses.dedup();
+    accesses
+}
+
+/// Returns: The offset and origin of the pointer operand `text`, like `ptr %5`,
+/// `ptr @global` or `ptr getelementptr inbounds nuw (i8, ptr @global, i32 2)`
+fn resolve(text: &str, global: &str, pointers: &Pointers) -> Option<(i64, Origin)> {
+    if let Some(start) = text.find("getelementptr") {
+        let expression = &text[start..];
+        let open       = expression.find('(')?;
+        let close      = open + matching(&expression[open..])?;
+        return match split_top_level(&expression[open + 1..close]).as_slice() {
+            ["i8", base, index] => {
+                let (base, origin) = resolve(base, global, pointers)?;
+                Some((base + index.strip_prefix("i32 ")?.parse::<i64>().ok()?, origin))
+            },
+            _                   => None,
+        };
+    }
+
+    let value = text.rsplit(' ').next()?;
+    match value.strip_prefix('@') {
+        Some(symbol) => (symbol.trim_matches('"') == global).then_some((0, Origin::Global)),
+        None         => pointers.get(value).cloned(),
+    }
+}
+
+/// Splits off the `volatile` of a load or store
+fn split_volatile(text: &str) -> (Option<String>, &str) {
+    match text.strip_prefix("volatile ") {
+        Some(rest) => (Some(String::from("volatile")), rest),
+        None       => (None, text),
+    }
+}
+
+/// Returns: The parameter attributes of the call argument `arg`, everything between type and value
+fn attributes(arg: &str) -> &str {
+    let end = arg.find("getelementptr").unwrap_or_else(|| arg.rfind(' ').unwrap_or(0));
+    arg[..end].split_once(' ').map_or("", |(_, attributes)| attributes.trim())
+}
+
+/// Returns: The metadata in `parts`, like `("!range", "!2")` for `!range !2`
+fn metadata(parts: &[&str]) -> Vec<(String, String)> {
+    parts.iter().filter_map(|part| part.strip_prefix('!')?.split_once(' ')).map(|(kind, node)| (format!("!{kind}"), node.to_string())).collect()
+}
+
+/// Splits a call into callee, arguments and what comes after the arguments
+///
+/// Returns: `asm` as callee for inline assembly
+fn call(body: &str) -> Option<(&str, Vec<&str>, &str)> {
+    let (callee, open) = match body.find(" @") {
+        Some(at) if !body.contains(" asm ") => {
+            let open = at + body[at..].find('(')?;
+            (body[at + 2..open].trim_matches('"'), open)
+        },
+        _                                   => ("asm", body.rfind("\"(")? + 1),
+    };
+    let close = open + matching(&body[open..])?;
+    Some((callee, split_top_level(&body[open + 1..close]), &body[close + 1..]))
+}
+
+/// Returns: The instructions in `instructions` using the value `result`
+fn users(instructions: &[Instruction], result: &str) -> Vec<Instruction> {
+    instructions.iter().filter(|instruction| {
+        instruction.text.match_indices(result).any(|(idx, _)| {
+            let next = instruction.text[idx + result.len()..].chars().next();
+            !next.is_some_and(|next| next.is_alphanumeric() || next == '.' || next == '_')
+        })
+    }).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IR: &str = r#"@G = internal global <{ [3 x i8], [1 x i8] }> <{ [3 x i8] c"\01\00\00", [1 x i8] undef }>, align 4
+
+; sdk::update
+define internal fastcc void @update() unnamed_addr #0 {
+start:
+  %0 = load i8, ptr getelementptr inbounds nuw (i8, ptr @G, i32 2), align 2, !range !0, !noundef !1
+  %1 = icmp eq i8 %0, -1
+  %10 = load i8, ptr @G, align 4
+  tail call fastcc void @process(ptr noalias noundef align 2 dereferenceable(4) @G, i8 %1) #3, !srcloc !2
+  ret void
+}
+
+; sdk::process
+define internal fastcc void @process(ptr noalias noundef align 2 dereferenceable(4) %slot, i8 %1) unnamed_addr #0 {
+start:
+  %2 = getelementptr inbounds nuw i8, ptr %slot, i32 3
+  store volatile i8 0, ptr %2, align 1, !alias.scope !3
+  ret void
+}
+
+!0 = !{i8 -1, i8 3}
+!1 = !{}
+!2 = !{i64 100}
+!3 = !{!4}
+!4 = distinct !{!4, !"process: argument 0"}
+"#;
+
+    #[test]
+    fn trace_through_parameters() {
+        let module   = Module::parse(IR);
+        let accesses = trace(&module, "G");
+
+        let summary: Vec<(Kind, i64, &str)> = accesses.iter().map(|access| (access.kind, access.offset, access.function.as_str())).collect();
+        assert_eq!(summary, [(Kind::Load, 2, "sdk::update"), (Kind::Load, 0, "sdk::update"), (Kind::Call, 0, "sdk::update"), (Kind::Store, 3, "sdk::process")]);
+
+        assert_eq!(accesses[0].metadata, [("!range".to_string(), "!0".to_string()), ("!noundef".to_string(), "!1".to_string())]);
+        assert_eq!(accesses[0].users.iter().map(|user| user.text.as_str()).collect::<Vec<_>>(), ["%1 = icmp eq i8 %0, -1"]);
+        // `%10` is no use of `%1`
+        assert!(accesses[1].users.is_empty());
+
+        assert_eq!(accesses[2].target, "process");
+        assert_eq!(accesses[2].attributes, ["noalias noundef align 2 dereferenceable(4)"]);
+        assert_eq!(accesses[2].metadata, [("!srcloc".to_string(), "!2".to_string())]);
+
+        let store = &accesses[3];
+        assert_eq!(store.attributes, ["volatile", "align 1"]);
+        assert_eq!(store.origin, Origin::Param{function: "sdk::process".to_string(), param: "%slot".to_string(), attributes: "noalias noundef align 2 dereferenceable(4)".to_string()});
+        assert_eq!(module.scope_names(&store.metadata[0].1), ["process: argument 0"]);
+    }
+}
@@ -0,0 +1,96 @@
+//! `ir <file.ll> [--global <name>] [--offset <byte>]... [--all]`
+//!
+//! Lists the loads of the tag of `Option<Configuration>` in `CONTROLLERS_A` with the attributes
+//! of the pointer they go through and the metadata attached to them. The tag is the first byte
+//! of a `ControllerSlot` as rustc lays it out right now, `-Zprint-type-sizes` shows the offsets
+//! of other layouts to pass with `--offset`, once per slot for more than one. `--all` lists
+//! every load, store and call reaching the global instead.
+
+use std::{env, fs, process::ExitCode};
+
+use ir::{Access, Kind, Origin, module::Module, names, trace};
+
+const DEFAULT_GLOBAL: &str = "CONTROLLERS_A";
+/// The offset of `ControllerSlot::configuration`, which starts with the tag
+const DEFAULT_OFFSET: i64  = 0;
+
+struct Options {
+    file:    String,
+    global:  String,
+    offsets: Vec<i64>,
+    all:     bool,
+}
+
+fn parse_options() -> Option<Options> {
+    let mut args    = env::args().skip(1);
+    let mut options = Options{file: String::new(), global: DEFAULT_GLOBAL.to_string(), offsets: Vec::new(), all: false};
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--global" => options.global = args.next()?,
+            "--offset" => options.offsets.push(args.next()?.parse().ok()?),
+            "--all"    => options.all = true,
+            _ if options.file.is_empty() => options.file = arg,
+            _          => return None,
+        }
+    }
+    if options.offsets.is_empty() {
+        options.offsets.push(DEFAULT_OFFSET);
+    }
+    (!options.file.is_empty()).then_some(options)
+}
+
+fn main() -> ExitCode {
+    let Some(options) = parse_options() else {
+        eprintln!("Usage: ir <file.ll> [--global <name>] [--offset <byte>]... [--all]");
+        return ExitCode::from(2);
+    };
+
+    let text = match fs::read_to_string(&options.file) {
+        Ok(text)   => text,
+        Err(error) => {
+            eprintln!("{}: {error}", options.file);
+            return ExitCode::from(2);
+        },
+    };
+    let module = Module::parse(&text);
+    let Some(global) = module.globals.iter().find(|global| names(&global.name, &options.global)) else {
+        eprintln!("{}: no global {}", options.file, options.global);
+        return ExitCode::FAILURE;
+    };
+    println!("{}:{}: @{} = {}", options.file, global.line, global.name, global.text);
+
+    let accesses = trace(&module, &global.name);
+    for access in accesses.iter().filter(|access| options.all || (access.kind == Kind::Load && options.offsets.contains(&access.offset))) {
+        print_access(&module, &options.global, access);
+    }
+    ExitCode::SUCCESS
+}
+
+fn print_access(module: &Module, global: &str, access: &Access) {
+    let kind = match access.kind {
+        Kind::Load  => "load",
+        Kind::Store => "store",
+        Kind::Call  => "call",
+    };
+    println!();
+    println!("{}:{}: {kind} {} {global}+{} {}", access.function, access.line, access.target, access.offset, access.attributes.join(", "));
+    println!("    {}", access.text);
+
+    if let Origin::Param{function, param, attributes} = &access.origin {
+        println!("    through {param} of {function}: {attributes}");
+    }
+    for (kind, node) in &access.metadata {
+        let scopes = module.scope_names(node);
+        match kind.as_str() {
+            "!noalias" | "!alias.scope" => println!("    {kind} {node} = {}", scopes.join(", ")),
+            _                           => println!("    {kind} {node} = {}", module.metadata.get(node).map_or("?", String::as_str)),
+        }
+    }
+    if access.metadata.is_empty() {
+        println!("    no metadata");
+    }
+    for user in &access.users {
+        println!("    used by {}: {}", user.line, user.text);
+    }
+}
@@ -0,0 +1,212 @@
+//! Just enough of the textual LLVM IR to follow pointers through functions
+
+use std::collections::HashMap;
+
+/// A parameter of a `define`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    /// Like `%0` or `%slot`
+    pub name:       String,
+    /// Everything between the type and the name, like `noalias noundef align 2 dereferenceable(8)`
+    pub attributes: String,
+}
+
+/// A line of a function body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// The line in the `.ll`, starting at 1
+    pub line: usize,
+    pub text: String,
+}
+
+/// A `define`d function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The symbol without the `@` and quotes
+    pub name:         String,
+    /// The demangled name from the comment above the `define` if there is one
+    pub comment:      Option<String>,
+    pub line:         usize,
+    pub params:       Vec<Param>,
+    pub instructions: Vec<Instruction>,
+}
+
+impl Function {
+    /// Returns: The demangled name if known, otherwise the symbol
+    pub fn display_name(&self) -> &str {
+        self.comment.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// A global variable, `text` is everything after the `=`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub line: usize,
+    pub text: String,
+}
+
+/// The globals, functions and metadata of a `.ll`
+#[derive(Debug, Default)]
+pub struct Module {
+    pub globals:   Vec<Global>,
+    pub functions: Vec<Function>,
+    /// The metadata nodes by their name, like `!2` to `!{i8 -1, i8 3}`
+    pub metadata:  HashMap<String, String>,
+}
+
+impl Module {
+    /// Parses the textual IR `text`, lines that are not understood are skipped
+    pub fn parse(text: &str) -> Module {
+        let mut module  = Module::default();
+        let mut comment = None;
+        let mut current: Option<Function> = None;
+
+        for (idx, line) in text.lines().enumerate() {
+            if let Some(function) = &mut current {
+                match line {
+                    "}" => module.functions.extend(current.take()),
+                    _   => function.instructions.push(Instruction{line: idx + 1, text: line.trim().to_string()}),
+                }
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("define ") {
+                current = parse_define(rest, idx + 1, comment.take());
+            }
+            else if let Some(rest) = line.strip_prefix('@') && let Some((name, text)) = rest.split_once(" = ") {
+                module.globals.push(Global{name: unquote(name).to_string(), line: idx + 1, text: text.to_string()});
+            }
+            else if line.starts_with('!') && let Some((name, node)) = line.split_once(" = ") {
+                module.metadata.insert(name.to_string(), node.to_string());
+            }
+
+            // The demangled name comes before the attributes
+            comment = match line.strip_prefix("; ") {
+                Some(text) if text.starts_with("Function Attrs") => comment,
+                text                                             => text.map(String::from),
+            };
+        }
+        module
+    }
+
+    /// Returns: The function with the symbol `name`
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    /// Returns: The strings in the metadata `node` and the nodes it refers to, like the names
+    /// of the scopes in a `!noalias` list
+    pub fn scope_names(&self, node: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut nodes = vec![node.to_string()];
+        let mut next  = 0;
+
+        // Breadth first, every node only once as scopes refer to themselves
+        while let Some(node) = nodes.get(next).cloned() {
+            next += 1;
+            let Some(text) = self.metadata.get(&node) else {
+                continue;
+            };
+
+            for part in split_top_level(text.trim_start_matches("distinct ").trim_start_matches("!{").trim_end_matches('}')) {
+                let known = nodes.iter().any(|node| node == part);
+                match part.strip_prefix("!\"") {
+                    Some(string)                            => names.push(string.trim_end_matches('"').to_string()),
+                    None if part.starts_with('!') && !known => nodes.push(part.to_string()),
+                    None                                    => {},
+                }
+            }
+        }
+        names
+    }
+}
+
+fn unquote(name: &str) -> &str {
+    name.trim_start_matches('"').trim_end_matches('"')
+}
+
+fn parse_define(rest: &str, line: usize, comment: Option<String>) -> Option<Function> {
+    let at     = rest.find('@')?;
+    let open   = at + rest[at..].find('(')?;
+    let close  = open + matching(&rest[open..])?;
+    let params = split_top_level(&rest[open + 1..close]).into_iter().filter_map(|param| {
+        let (rest, name) = param.rsplit_once(' ')?;
+        let attributes   = rest.split_once(' ').map_or("", |(_, attributes)| attributes);
+        Some(Param{name: name.to_string(), attributes: attributes.to_string()})
+    }).collect();
+
+    Some(Function{name: unquote(&rest[at + 1..open]).to_string(), comment, line, params, instructions: Vec::new()})
+}
+
+/// Returns: The index of the parenthesis closing the one `text` starts with
+pub fn matching(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, char) in text.char_indices() {
+        match char {
+            '(' | '{' | '[' | '<' => depth += 1,
+            ')' | '}' | ']' | '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            },
+            _                     => {},
+        }
+    }
+    None
+}
+
+/// Splits `text` at the commas outside of parentheses, brackets, braces and quotes
+pub fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts  = Vec::new();
+    let mut depth  = 0i32;
+    let mut quoted = false;
+    let mut start  = 0;
+
+    for (idx, char) in text.char_indices() {
+        match char {
+            '"'                              => quoted = !quoted,
+            '(' | '{' | '[' | '<' if !quoted => depth += 1,
+            ')' | '}' | ']' | '>' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0     => {
+                parts.push(text[start..idx].trim());
+                start = idx + 1;
+            },
+            _                                => {},
+        }
+    }
+    parts.push(text[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_module() {
+        let module = Module::parse(r#"@"G$1" = internal global <{ [3 x i8] }> <{ [3 x i8] c"\01\00\00" }>, align 4
+
+; sdk::f
+; Function Attrs: nounwind
+define internal fastcc void @f(ptr noalias noundef align 2 dereferenceable(8) %0, i8 %1) unnamed_addr #0 {
+start:
+  ret void
+}
+
+!0 = !{!1}
+!1 = distinct !{!1, !2, !"f: argument 0"}
+!2 = distinct !{!2, !"f"}
+"#);
+
+        assert_eq!(module.globals[0].name, "G$1");
+        let function = module.function("f").unwrap();
+        assert_eq!(function.display_name(), "sdk::f");
+        assert_eq!(function.params[0], Param{name: "%0".to_string(), attributes: "noalias noundef align 2 dereferenceable(8)".to_string()});
+        assert_eq!(function.params[1].attributes, "");
+        assert_eq!(function.instructions.len(), 2);
+        assert_eq!(module.scope_names("!0"), ["f: argument 0", "f"]);
+    }
+}
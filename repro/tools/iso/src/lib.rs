@@ -0,0 +1,371 @@
+//! Masters a bootable disc image of a PS-X EXE
+//!
+//! The image is a single Mode 2 Form 1 data track with an ISO9660 file system whose root
+//! directory holds the `SYSTEM.CNF` the BIOS boots from, the EXE and the asset files. The
+//! layout follows what the BIOS and the mastering tools of the console expect: the empty
+//! system area, the primary volume descriptor with `CD-XA001` at sector 16, the terminator,
+//! the path tables, the root directory and then the files, every directory record carrying
+//! the XA attributes of a Form 1 file. `read` gets the files back out of a BIN.
+
+pub mod read;
+pub mod sector;
+
+use std::{fmt, path::Path};
+
+use psxexe::{Header, HeaderError};
+
+use crate::sector::{DATA, DATA_SIZE, EOF, EOR, RAW_SIZE, encode};
+
+/// The sectors in front of the volume descriptors, the license of `ps-exe.ld` builds goes there
+pub const SYSTEM_AREA: u32 = 16;
+/// The name the BIOS loads its configuration from
+pub const SYSTEM_CNF: &str = "SYSTEM.CNF";
+
+const PVD: u32 = SYSTEM_AREA;
+const L_PATH_TABLE: u32 = PVD + 2;
+const M_PATH_TABLE: u32 = PVD + 3;
+const ROOT: u32 = PVD + 4;
+/// The size of the path tables, a single entry for the root
+const PATH_TABLE_SIZE: u32 = 10;
+
+/// The XA attributes of a readable Form 1 file and of a directory
+const XA_FILE: u16 = 0x0D55;
+const XA_DIRECTORY: u16 = 0x8D55;
+
+/// A file of the root directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct File {
+    /// The ISO9660 name without the `;1` version, like `APP.EXE`
+    pub name: String,
+    /// The content
+    pub data: Vec<u8>,
+}
+
+/// What goes into `SYSTEM.CNF` besides the name of the EXE
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// The number of thread control blocks, written in hex like the BIOS reads it
+    pub tcb:   u32,
+    /// The number of event control blocks, written in hex like the BIOS reads it
+    pub event: u32,
+    /// The initial SP, `STACK_INIT` of the header if `None`
+    pub stack: Option<u32>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config{tcb: 4, event: 16, stack: None}
+    }
+}
+
+/// Why no disc can be mastered
+#[derive(Debug, PartialEq)]
+pub enum IsoError {
+    /// The EXE would not boot
+    Exe{errors: Vec<HeaderError>},
+    /// The name is no upper case 8.3 name of ISO9660 level 1
+    Name{name: String},
+    /// Two files share a name
+    Duplicate{name: String},
+    /// The volume identifier has characters ISO9660 does not allow or is too long
+    Volume{volume: String},
+}
+
+impl fmt::Display for IsoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsoError::Exe{errors}     => write!(f, "the EXE does not boot: {}", errors.iter().map(HeaderError::to_string).collect::<Vec<_>>().join(", ")),
+            IsoError::Name{name}      => write!(f, "{name} is no 8.3 name of upper case letters, digits and underscores"),
+            IsoError::Duplicate{name} => write!(f, "{name} is on the disc twice"),
+            IsoError::Volume{volume}  => write!(f, "{volume} is no volume identifier of up to 32 upper case letters, digits and underscores"),
+        }
+    }
+}
+
+/// A disc image as Form 1 user data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disc {
+    /// The user data and submode of every sector from LBA 0 on
+    pub sectors: Vec<(u8, [u8; DATA_SIZE])>,
+    /// The files with the LBA of their first sector, in the order of the root directory
+    pub files:   Vec<(String, u32)>,
+}
+
+impl Disc {
+    /// Returns: The raw sectors of the track, the content of the BIN
+    pub fn to_bin(&self) -> Vec<u8> {
+        let mut bin = Vec::with_capacity(self.sectors.len()*RAW_SIZE);
+        for (lba, (submode, data)) in self.sectors.iter().enumerate() {
+            bin.extend(encode(lba as u32, *submode, data));
+        }
+        bin
+    }
+}
+
+/// Returns: The `SYSTEM.CNF` booting `exe_name` with the stack of `header` unless `config` has one
+pub fn system_cnf(exe_name: &str, header: &Header, config: &Config) -> String {
+    let stack = config.stack.unwrap_or(header.stack.wrapping_add(header.stack_offset));
+    format!("BOOT = cdrom:\\{exe_name};1\r\nTCB = {:X}\r\nEVENT = {:X}\r\nSTACK = {stack:08X}\r\n", config.tcb, config.event)
+}
+
+/// Returns: The CUE sheet of the single track in `bin_path`
+pub fn cue(bin_path: &Path) -> String {
+    let name = bin_path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    format!("FILE \"{name}\" BINARY\n  TRACK 01 MODE2/{RAW_SIZE}\n    INDEX 01 00:00:00\n")
+}
+
+/// Masters the disc booting `exe`
+///
+/// Arguments:
+/// * `volume`: The volume identifier
+/// * `exe`: The name and content of the PS-X EXE
+/// * `assets`: The other files, in no particular order
+/// * `config`: The values of `SYSTEM.CNF`
+///
+/// Returns: The `Disc` or why it would not boot
+pub fn master(volume: &str, exe: File, assets: Vec<File>, config: &Config) -> Result<Disc, IsoError> {
+    if volume.is_empty() || volume.len() > 32 || !volume.bytes().all(d_character) {
+        return Err(IsoError::Volume{volume: volume.to_owned()});
+    }
+
+    let header = psxexe::check(&exe.data).map_err(|errors| IsoError::Exe{errors})?;
+    let cnf    = File{name: SYSTEM_CNF.to_owned(), data: system_cnf(&exe.name, &header, config).into_bytes()};
+
+    // The directory records are sorted by name, the files are laid out the same way
+    let mut files = vec![cnf, exe];
+    files.extend(assets);
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    for file in &files {
+        if !valid_name(&file.name) {
+            return Err(IsoError::Name{name: file.name.clone()});
+        }
+    }
+    for pair in files.windows(2) {
+        if pair[0].name == pair[1].name {
+            return Err(IsoError::Duplicate{name: pair[0].name.clone()});
+        }
+    }
+
+    let root_size = directory_size(&files);
+    let mut lba   = ROOT + root_size.div_ceil(DATA_SIZE) as u32;
+    let extents: Vec<u32> = files.iter().map(|file| {
+        let extent = lba;
+        lba += file.data.len().div_ceil(DATA_SIZE).max(1) as u32;
+        extent
+    }).collect();
+    let total = lba;
+
+    let mut disc = Disc{sectors: vec![(DATA, [0; DATA_SIZE]); total as usize], files: Vec::new()};
+    disc.sectors[PVD as usize]     = (DATA | EOR, primary_volume_descriptor(volume, total, root_size));
+    disc.sectors[PVD as usize + 1] = (DATA | EOF | EOR, terminator());
+
+    let (little, big) = path_tables();
+    disc.sectors[L_PATH_TABLE as usize] = (DATA | EOF | EOR, little);
+    disc.sectors[M_PATH_TABLE as usize] = (DATA | EOF | EOR, big);
+
+    let mut records = Vec::new();
+    records.push(directory_record(&[0], ROOT, root_size as u32, XA_DIRECTORY));
+    records.push(directory_record(&[1], ROOT, root_size as u32, XA_DIRECTORY));
+    for (file, extent) in files.iter().zip(&extents) {
+        records.push(directory_record(format!("{};1", file.name).as_bytes(), *extent, file.data.len() as u32, XA_FILE));
+    }
+    write_extent(&mut disc, ROOT, &directory(records));
+
+    for (file, extent) in files.iter().zip(extents) {
+        write_extent(&mut disc, extent, &file.data);
+        disc.files.push((file.name.clone(), extent));
+    }
+    Ok(disc)
+}
+
+/// Returns: If `name` is an upper case 8.3 name
+pub fn valid_name(name: &str) -> bool {
+    let (stem, extension) = name.split_once('.').unwrap_or((name, ""));
+    (1..=8).contains(&stem.len()) && extension.len() <= 3 && stem.bytes().chain(extension.bytes()).all(d_character)
+}
+
+/// Returns: If `byte` is one of the d-characters of ISO9660
+fn d_character(byte: u8) -> bool {
+    byte.is_ascii_uppercase() || byte.is_ascii_digit() || byte == b'_'
+}
+
+/// Writes `data` into the sectors from `lba` on, the last one marked as the end of the file
+fn write_extent(disc: &mut Disc, lba: u32, data: &[u8]) {
+    let sectors = data.len().div_ceil(DATA_SIZE).max(1);
+    for idx in 0..sectors {
+        let chunk = data.get(idx*DATA_SIZE..).unwrap_or_default();
+        let chunk = &chunk[..chunk.len().min(DATA_SIZE)];
+
+        let (submode, sector) = &mut disc.sectors[lba as usize + idx];
+        sector[..chunk.len()].copy_from_slice(chunk);
+        if idx == sectors - 1 {
+            *submode = DATA | EOF | EOR;
+        }
+    }
+}
+
+/// Returns: `value` in both byte orders as ISO9660 stores numbers
+fn both_u32(value: u32) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&value.to_le_bytes());
+    bytes[4..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+fn both_u16(value: u16) -> [u8; 4] {
+    let mut bytes = [0; 4];
+    bytes[..2].copy_from_slice(&value.to_le_bytes());
+    bytes[2..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+/// Returns: The directory record of `name` with the XA system use field
+fn directory_record(name: &[u8], extent: u32, size: u32, attributes: u16) -> Vec<u8> {
+    let mut record = vec![0; 33];
+    record[2..10].copy_from_slice(&both_u32(extent));
+    record[10..18].copy_from_slice(&both_u32(size));
+    // The recording date stays zero, an unspecified date keeps the image reproducible
+    record[25] = if attributes == XA_DIRECTORY {0x02} else {0x00};
+    record[28..32].copy_from_slice(&both_u16(1));
+    record[32] = name.len() as u8;
+    record.extend(name);
+    if !record.len().is_multiple_of(2) {
+        record.push(0);
+    }
+
+    record.extend([0, 0, 0, 0]);
+    record.extend(attributes.to_be_bytes());
+    record.extend(b"XA");
+    record.extend([0; 6]);
+    record[0] = record.len() as u8;
+    record
+}
+
+/// Returns: The size of the root directory, whole sectors since records do not cross them
+fn directory_size(files: &[File]) -> usize {
+    let mut records = vec![directory_record(&[0], 0, 0, XA_DIRECTORY), directory_record(&[1], 0, 0, XA_DIRECTORY)];
+    records.extend(files.iter().map(|file| directory_record(format!("{};1", file.name).as_bytes(), 0, 0, XA_FILE)));
+    directory(records).len()
+}
+
+/// Returns: `records` packed into sectors, a record not fitting anymore starts the next one
+fn directory(records: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut directory = Vec::new();
+    for record in records {
+        let used = directory.len() % DATA_SIZE;
+        if used + record.len() > DATA_SIZE {
+            directory.resize(directory.len() + DATA_SIZE - used, 0);
+        }
+        directory.extend(record);
+    }
+    directory.resize(directory.len().next_multiple_of(DATA_SIZE), 0);
+    directory
+}
+
+/// Returns: The path tables in little and big endian, only the root is in them
+fn path_tables() -> ([u8; DATA_SIZE], [u8; DATA_SIZE]) {
+    let (mut little, mut big) = ([0; DATA_SIZE], [0; DATA_SIZE]);
+    for (table, extent, parent) in [(&mut little, ROOT.to_le_bytes(), 1u16.to_le_bytes()), (&mut big, ROOT.to_be_bytes(), 1u16.to_be_bytes())] {
+        table[0] = 1;
+        table[2..6].copy_from_slice(&extent);
+        table[6..8].copy_from_slice(&parent);
+    }
+    (little, big)
+}
+
+/// Returns: The primary volume descriptor of a disc of `total` sectors
+fn primary_volume_descriptor(volume: &str, total: u32, root_size: usize) -> [u8; DATA_SIZE] {
+    let mut pvd = [0; DATA_SIZE];
+    pvd[0] = 1;
+    pvd[1..6].copy_from_slice(b"CD001");
+    pvd[6] = 1;
+
+    let text = |pvd: &mut [u8; DATA_SIZE], offset: usize, len: usize, value: &str| {
+        pvd[offset..offset + len].fill(b' ');
+        pvd[offset..offset + value.len()].copy_from_slice(value.as_bytes());
+    };
+    text(&mut pvd, 8, 32, "PLAYSTATION");
+    text(&mut pvd, 40, 32, volume);
+
+    pvd[80..88].copy_from_slice(&both_u32(total));
+    pvd[120..124].copy_from_slice(&both_u16(1));
+    pvd[124..128].copy_from_slice(&both_u16(1));
+    pvd[128..132].copy_from_slice(&both_u16(DATA_SIZE as u16));
+    pvd[132..140].copy_from_slice(&both_u32(PATH_TABLE_SIZE));
+    pvd[140..144].copy_from_slice(&L_PATH_TABLE.to_le_bytes());
+    pvd[148..152].copy_from_slice(&M_PATH_TABLE.to_be_bytes());
+
+    // The root record of the descriptor has no system use field
+    let root = directory_record(&[0], ROOT, root_size as u32, XA_DIRECTORY);
+    pvd[156..190].copy_from_slice(&root[..34]);
+    pvd[156] = 34;
+
+    text(&mut pvd, 190, 128, "");
+    text(&mut pvd, 318, 128, "");
+    text(&mut pvd, 446, 128, "");
+    text(&mut pvd, 574, 128, "PLAYSTATION");
+    text(&mut pvd, 702, 37*3, "");
+
+    // Creation, modification, expiration and effective date are not specified
+    for offset in [813, 830, 847, 864] {
+        pvd[offset..offset + 16].fill(b'0');
+    }
+    pvd[881] = 1;
+
+    pvd[1024..1032].copy_from_slice(b"CD-XA001");
+    pvd
+}
+
+/// Returns: The volume descriptor set terminator
+fn terminator() -> [u8; DATA_SIZE] {
+    let mut terminator = [0; DATA_SIZE];
+    terminator[0] = 0xFF;
+    terminator[1..6].copy_from_slice(b"CD001");
+    terminator[6] = 1;
+    terminator
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{read::{boot, read}, sector::decode};
+
+    use super::*;
+
+    fn exe() -> File {
+        let header = Header{pc: 0x8001_0000, gp: 0, load_addr: 0x8001_0000, size: 0x800, stack: 0x801F_FF00, stack_offset: 0, marker: String::new()};
+        let mut data = header.to_bytes();
+        data.resize(data.len() + 0x800, 0);
+        File{name: "APP.EXE".to_owned(), data}
+    }
+
+    #[test]
+    fn disc() {
+        let asset = File{name: "LEVEL_1.TIM".to_owned(), data: vec![0xAA; 5000]};
+        let disc  = master("APP", exe(), vec![asset], &Config::default()).unwrap();
+
+        assert_eq!(disc.files, [("APP.EXE".to_owned(), 21), ("LEVEL_1.TIM".to_owned(), 23), (SYSTEM_CNF.to_owned(), 26)]);
+        assert_eq!(disc.sectors.len(), 27);
+        assert_eq!(disc.sectors[25].0, DATA | EOF | EOR);
+        assert_eq!(&disc.sectors[PVD as usize].1[80..84], 27u32.to_le_bytes());
+
+        let bin = disc.to_bin();
+        let cnf = decode(26, bin[26*RAW_SIZE..27*RAW_SIZE].try_into().unwrap()).unwrap().1;
+        assert!(cnf.starts_with(b"BOOT = cdrom:\\APP.EXE;1\r\nTCB = 4\r\nEVENT = 10\r\nSTACK = 801FFF00\r\n\0"));
+
+        let files = read(&bin).unwrap();
+        assert_eq!(files.iter().map(|file| (file.name.as_str(), file.data.len())).collect::<Vec<_>>(), [("APP.EXE", 0x1000), ("LEVEL_1.TIM", 5000), (SYSTEM_CNF, 64)]);
+        assert_eq!(boot(&files), Some(&exe()));
+    }
+
+    #[test]
+    fn invalid() {
+        let asset = |name: &str| vec![File{name: name.to_owned(), data: Vec::new()}];
+        assert_eq!(master("APP", exe(), asset("level1.tim"), &Config::default()), Err(IsoError::Name{name: "level1.tim".to_owned()}));
+        assert_eq!(master("APP", exe(), asset("APP.EXE"), &Config::default()), Err(IsoError::Duplicate{name: "APP.EXE".to_owned()}));
+        assert_eq!(master("My App", exe(), Vec::new(), &Config::default()), Err(IsoError::Volume{volume: "My App".to_owned()}));
+
+        let mut broken = exe();
+        broken.data.truncate(0x900);
+        assert!(matches!(master("APP", broken, Vec::new(), &Config::default()), Err(IsoError::Exe{..})));
+    }
+}
@@ -0,0 +1,81 @@
+//! `iso <app.exe> [<asset>...] [--out <app.bin>] [--volume <name>] [--stack <address>]`
+//!
+//! Masters a BIN/CUE disc image booting `app.exe` like a real disc, without "Start File" in
+//! the emulator. The assets end up next to the EXE in the root directory under their upper
+//! case file names. The BIN goes to `--out`, by default next to `app.exe`, the CUE sheet
+//! next to the BIN. The volume is named after the EXE unless `--volume` says otherwise and
+//! the `STACK` of `SYSTEM.CNF` is `STACK_INIT` of the header unless `--stack` gives a hex
+//! address.
+
+use std::{env, fs, path::{Path, PathBuf}, process::ExitCode};
+
+use iso::{Config, File, cue, master};
+
+fn main() -> ExitCode {
+    let mut paths  = Vec::new();
+    let mut out    = None;
+    let mut volume = None;
+    let mut config = Config::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out"                    => out = args.next().map(PathBuf::from),
+            "--volume"                 => volume = args.next(),
+            "--stack"                  => match args.next().and_then(|stack| u32::from_str_radix(stack.trim_start_matches("0x"), 16).ok()) {
+                Some(stack) => config.stack = Some(stack),
+                None        => return usage(),
+            },
+            _ if arg.starts_with("--") => return usage(),
+            _                          => paths.push(arg),
+        }
+    }
+    let Some((exe_path, asset_paths)) = paths.split_first() else {
+        return usage();
+    };
+
+    let mut files = Vec::new();
+    for path in paths.iter() {
+        match fs::read(path) {
+            Ok(data)   => files.push(File{name: file_name(path), data}),
+            Err(error) => return fail(path, error),
+        }
+    }
+    let exe    = files.remove(0);
+    let volume = volume.unwrap_or_else(|| exe.name.split('.').next().unwrap_or_default().to_owned());
+
+    let disc = match master(&volume, exe, files, &config) {
+        Ok(disc)   => disc,
+        Err(error) => return fail(exe_path, error),
+    };
+
+    let bin_path = out.unwrap_or_else(|| Path::new(exe_path).with_extension("bin"));
+    let cue_path = bin_path.with_extension("cue");
+    if let Err(error) = fs::write(&bin_path, disc.to_bin()) {
+        return fail(&bin_path.display().to_string(), error);
+    }
+    if let Err(error) = fs::write(&cue_path, cue(&bin_path)) {
+        return fail(&cue_path.display().to_string(), error);
+    }
+
+    for (name, lba) in &disc.files {
+        println!("{lba:>6}  {name}");
+    }
+    eprintln!("{}: {} sectors, {} assets, volume {volume}", cue_path.display(), disc.sectors.len(), asset_paths.len());
+    ExitCode::SUCCESS
+}
+
+/// Returns: The upper case file name of `path`, the name on the disc
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map(|name| name.to_string_lossy().to_ascii_uppercase()).unwrap_or_default()
+}
+
+fn usage() -> ExitCode {
+    eprintln!("Usage: iso <app.exe> [<asset>...] [--out <app.bin>] [--volume <name>] [--stack <address>]");
+    ExitCode::from(2)
+}
+
+fn fail(path: &str, error: impl std::fmt::Display) -> ExitCode {
+    eprintln!("{path}: {error}");
+    ExitCode::FAILURE
+}
@@ -0,0 +1,104 @@
+//! Reads the files back from the root directory of a BIN, the inverse of `master`
+
+use std::fmt;
+
+use crate::{File, PVD, SYSTEM_CNF, sector::{DATA_SIZE, RAW_SIZE, SectorError, decode}};
+
+/// Why the files of a BIN can not be read
+#[derive(Debug, PartialEq)]
+pub enum ReadError {
+    /// The BIN is no whole number of raw sectors or ends before the volume descriptor
+    Size{size: usize},
+    /// A sector is damaged or no Form 1 sector
+    Sector(SectorError),
+    /// Sector 16 holds no primary volume descriptor
+    NoVolume,
+    /// A directory record or an extent lies outside of the BIN
+    Record{lba: u32},
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Size{size}    => write!(f, "{size} bytes are no whole number of {RAW_SIZE} byte sectors past the volume descriptor"),
+            ReadError::Sector(error) => write!(f, "{error}"),
+            ReadError::NoVolume      => write!(f, "sector {PVD} holds no primary volume descriptor"),
+            ReadError::Record{lba}   => write!(f, "the directory record in sector {lba} points past the end of the BIN"),
+        }
+    }
+}
+
+impl From<SectorError> for ReadError {
+    fn from(error: SectorError) -> ReadError {
+        ReadError::Sector(error)
+    }
+}
+
+/// Reads the files of the root directory of `bin`
+///
+/// Arguments:
+/// * `bin`: The raw sectors of the track
+///
+/// Returns: The files in the order of the directory or why `bin` has none
+pub fn read(bin: &[u8]) -> Result<Vec<File>, ReadError> {
+    if !bin.len().is_multiple_of(RAW_SIZE) || bin.len() <= PVD as usize*RAW_SIZE {
+        return Err(ReadError::Size{size: bin.len()});
+    }
+
+    let sectors = bin.len()/RAW_SIZE;
+    let sector  = |lba: u32| -> Result<&[u8], ReadError> {
+        let raw = bin.get(lba as usize*RAW_SIZE..(lba as usize + 1)*RAW_SIZE).ok_or(ReadError::Record{lba})?;
+        Ok(decode(lba, raw.try_into().unwrap())?.1)
+    };
+    let extent = |lba: u32, size: u32| -> Result<Vec<u8>, ReadError> {
+        if lba as usize + (size as usize).div_ceil(DATA_SIZE) > sectors {
+            return Err(ReadError::Record{lba});
+        }
+        let mut data = Vec::with_capacity(size as usize);
+        for idx in 0..(size as usize).div_ceil(DATA_SIZE) as u32 {
+            data.extend(sector(lba + idx)?);
+        }
+        data.truncate(size as usize);
+        Ok(data)
+    };
+
+    let pvd = sector(PVD)?;
+    if pvd[0] != 1 || &pvd[1..6] != b"CD001" {
+        return Err(ReadError::NoVolume);
+    }
+    let word = |bytes: &[u8], offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let root = extent(word(pvd, 156 + 2), word(pvd, 156 + 10))?;
+
+    let mut files = Vec::new();
+    let mut idx   = 0;
+    while idx < root.len() {
+        // A record never crosses a sector, a zero length pads to the next one
+        let len = root[idx] as usize;
+        if len == 0 {
+            idx = (idx/DATA_SIZE + 1)*DATA_SIZE;
+            continue;
+        }
+
+        let record = root.get(idx..idx + len).filter(|record| record.len() > 33).ok_or(ReadError::Record{lba: word(pvd, 156 + 2)})?;
+        let name   = &record[33..(33 + record[32] as usize).min(len)];
+        if record[25] & 0x02 == 0 {
+            let name = String::from_utf8_lossy(name);
+            let name = name.split(';').next().unwrap_or_default().to_owned();
+            files.push(File{name, data: extent(word(record, 2), word(record, 10))?});
+        }
+        idx += len;
+    }
+    Ok(files)
+}
+
+/// Returns: The file `SYSTEM.CNF` of `files` boots or `None` if there is no `BOOT` line
+pub fn boot(files: &[File]) -> Option<&File> {
+    let cnf  = files.iter().find(|file| file.name == SYSTEM_CNF)?;
+    let cnf  = String::from_utf8_lossy(&cnf.data);
+    let line = cnf.lines().find_map(|line| line.split_once('=').filter(|(key, _)| key.trim() == "BOOT"))?.1;
+
+    // `cdrom:\APP.EXE;1` and `cdrom:APP.EXE;1`, only the root directory is searched
+    let path = line.trim().strip_prefix("cdrom:")?;
+    let name = path.rsplit('\\').next()?.split(';').next()?.to_ascii_uppercase();
+    files.iter().find(|file| file.name == name)
+}
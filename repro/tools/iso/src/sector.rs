@@ -0,0 +1,210 @@
+//! Raw Mode 2 Form 1 sectors as the CD-ROM controller of the console reads them
+//!
+//! A raw sector is the sync pattern, the BCD address, the XA subheader twice, 2048 bytes of
+//! user data, the EDC over subheader and data and the P and Q parities of the ECC. The header
+//! is left out of the ECC in Mode 2 so that sectors can be moved without recomputing it.
+
+use std::fmt;
+
+/// The size of a raw sector in the BIN
+pub const RAW_SIZE: usize = 2352;
+/// The user data of a Form 1 sector
+pub const DATA_SIZE: usize = 2048;
+/// The sectors of the lead-in in front of LBA 0, `00:02:00` is the first one of the track
+pub const PREGAP: u32 = 150;
+
+/// The subheader submode of the last sector of a record
+pub const EOR: u8 = 0x01;
+/// The subheader submode of a sector with data
+pub const DATA: u8 = 0x08;
+/// The subheader submode of the last sector of a file
+pub const EOF: u8 = 0x80;
+
+const SYNC: [u8; 12] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const HEADER: usize = 0x0C;
+const SUBHEADER: usize = 0x10;
+const USER: usize = 0x18;
+const EDC: usize = 0x818;
+const ECC_P: usize = 0x81C;
+const ECC_Q: usize = 0x8C8;
+
+/// Why a raw sector can not be read back
+#[derive(Debug, PartialEq)]
+pub enum SectorError {
+    /// The sector does not start with the sync pattern
+    NoSync{lba: u32},
+    /// The header holds another address or is not Mode 2
+    Header{lba: u32, found: [u8; 4]},
+    /// The two copies of the subheader differ
+    Subheader{lba: u32},
+    /// The sector is Form 2 which has no ECC
+    Form2{lba: u32},
+    /// The EDC or ECC do not match the data
+    Checksum{lba: u32},
+}
+
+impl fmt::Display for SectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SectorError::NoSync{lba}        => write!(f, "sector {lba} has no sync pattern"),
+            SectorError::Header{lba, found} => write!(f, "sector {lba} has the header {found:02x?} instead of Mode 2 at {}", Msf::from_lba(*lba)),
+            SectorError::Subheader{lba}     => write!(f, "the two copies of the subheader of sector {lba} differ"),
+            SectorError::Form2{lba}         => write!(f, "sector {lba} is Form 2"),
+            SectorError::Checksum{lba}      => write!(f, "the EDC or ECC of sector {lba} do not match its data"),
+        }
+    }
+}
+
+/// The minute, second and frame of a sector counted from the start of the lead-in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Msf {
+    pub minute: u8,
+    pub second: u8,
+    pub frame:  u8,
+}
+
+impl Msf {
+    /// Returns: The address of sector `lba` of the first track
+    pub fn from_lba(lba: u32) -> Msf {
+        let frames = lba + PREGAP;
+        Msf{minute: (frames/75/60) as u8, second: (frames/75%60) as u8, frame: (frames%75) as u8}
+    }
+
+    /// Returns: The address as it is written into the header, in BCD
+    pub fn to_bcd(self) -> [u8; 3] {
+        [self.minute, self.second, self.frame].map(|value| ((value/10) << 4) | (value%10))
+    }
+}
+
+impl fmt::Display for Msf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.minute, self.second, self.frame)
+    }
+}
+
+/// Encodes the user data of sector `lba`
+///
+/// Arguments:
+/// * `lba`: The logical block address, `16` for the primary volume descriptor
+/// * `submode`: The submode of the subheader, `DATA` with `EOR` and `EOF` as needed
+/// * `data`: The user data, padded with zeros if shorter than `DATA_SIZE`
+///
+/// Returns: The raw sector with its EDC and ECC
+pub fn encode(lba: u32, submode: u8, data: &[u8]) -> [u8; RAW_SIZE] {
+    let mut sector = [0; RAW_SIZE];
+    sector[..SYNC.len()].copy_from_slice(&SYNC);
+
+    let subheader = [0, 0, submode, 0];
+    sector[SUBHEADER..SUBHEADER + 4].copy_from_slice(&subheader);
+    sector[SUBHEADER + 4..USER].copy_from_slice(&subheader);
+
+    let data = &data[..data.len().min(DATA_SIZE)];
+    sector[USER..USER + data.len()].copy_from_slice(data);
+
+    let edc = edc(&sector[SUBHEADER..EDC]);
+    sector[EDC..ECC_P].copy_from_slice(&edc.to_le_bytes());
+
+    // The ECC is computed with the header zeroed and the header is written afterwards
+    ecc(&mut sector);
+    let [minute, second, frame] = Msf::from_lba(lba).to_bcd();
+    sector[HEADER..SUBHEADER].copy_from_slice(&[minute, second, frame, 2]);
+    sector
+}
+
+/// Reads back the user data of sector `lba`
+///
+/// Arguments:
+/// * `lba`: The logical block address the sector is expected at
+/// * `sector`: The raw sector
+///
+/// Returns: The submode and the user data or why the sector is no valid Form 1 sector
+pub fn decode(lba: u32, sector: &[u8; RAW_SIZE]) -> Result<(u8, &[u8]), SectorError> {
+    if sector[..SYNC.len()] != SYNC {
+        return Err(SectorError::NoSync{lba});
+    }
+
+    let [minute, second, frame] = Msf::from_lba(lba).to_bcd();
+    let found: [u8; 4] = sector[HEADER..SUBHEADER].try_into().unwrap();
+    if found != [minute, second, frame, 2] {
+        return Err(SectorError::Header{lba, found});
+    }
+
+    if sector[SUBHEADER..SUBHEADER + 4] != sector[SUBHEADER + 4..USER] {
+        return Err(SectorError::Subheader{lba});
+    }
+    let submode = sector[SUBHEADER + 2];
+    if submode & 0x20 != 0 {
+        return Err(SectorError::Form2{lba});
+    }
+
+    if encode(lba, submode, &sector[USER..EDC])[..] != sector[..] {
+        return Err(SectorError::Checksum{lba});
+    }
+    Ok((submode, &sector[USER..EDC]))
+}
+
+/// Returns: The CRC-32 with the polynomial `0xD8018001` of the CD-ROM EDC over `data`
+fn edc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |mut edc, byte| {
+        edc ^= *byte as u32;
+        for _ in 0..8 {
+            edc = (edc >> 1) ^ if edc & 1 != 0 {0xD801_8001} else {0};
+        }
+        edc
+    })
+}
+
+/// Returns: `value` times `x` in the GF(2^8) of the ECC
+fn times_x(value: u8) -> u8 {
+    (value << 1) ^ if value & 0x80 != 0 {0x1D} else {0}
+}
+
+/// Returns: `value` divided by `x + 1`, the inverse of `value ^ times_x(value)`
+fn div_x1(value: u8) -> u8 {
+    (0..=255).find(|candidate| candidate ^ times_x(*candidate) == value).unwrap()
+}
+
+/// Writes the P and Q parities of the Reed-Solomon product code of `sector`
+fn ecc(sector: &mut [u8; RAW_SIZE]) {
+    // The P vectors run down the 43 columns of 24 bytes, the Q vectors diagonally over the
+    // 26 rows of 43 bytes and the P parity, both split into the even and the odd bytes
+    parity(sector, ECC_P, 86, 24, 2, 86);
+    parity(sector, ECC_Q, 52, 43, 86, 88);
+}
+
+fn parity(sector: &mut [u8; RAW_SIZE], out: usize, majors: usize, minors: usize, major_step: usize, minor_step: usize) {
+    let size = majors*minors;
+    for major in 0..majors {
+        let mut idx = (major >> 1)*major_step + (major & 1);
+        let (mut a, mut b) = (0u8, 0u8);
+        for _ in 0..minors {
+            let byte = sector[HEADER + idx];
+            idx = (idx + minor_step) % size;
+            a = times_x(a ^ byte);
+            b ^= byte;
+        }
+
+        let a = div_x1(times_x(a) ^ b);
+        sector[out + major]          = a;
+        sector[out + major + majors] = a ^ b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sector() {
+        assert_eq!(Msf::from_lba(16).to_bcd(), [0x00, 0x02, 0x16]);
+        assert_eq!(Msf::from_lba(4350).to_string(), "01:00:00");
+
+        let data: Vec<u8> = (0..DATA_SIZE).map(|idx| (idx*7) as u8).collect();
+        let mut sector = encode(20, DATA | EOF | EOR, &data);
+        assert_eq!(decode(20, &sector), Ok((DATA | EOF | EOR, &data[..])));
+        assert_eq!(decode(21, &sector).unwrap_err(), SectorError::Header{lba: 21, found: [0x00, 0x02, 0x20, 0x02]});
+
+        sector[USER + 100] ^= 1;
+        assert_eq!(decode(20, &sector), Err(SectorError::Checksum{lba: 20}));
+    }
+}
@@ -0,0 +1,195 @@
+//! Writes the license of a region into the system area of a disc image
+//!
+//! Licensed discs carry the license text in sector 4 and the data of the logo the BIOS shows
+//! in the sectors after it, all within the 16 sectors in front of the volume descriptor
+//! `iso` leaves empty. The license comes from a data file of the mastering tools or is a
+//! placeholder with only the text. Either way it has to be for the region the EXE the disc
+//! boots is marked for, the marker `psxexe --region` writes into the header.
+
+use std::fmt;
+
+use iso::{SYSTEM_AREA, read::{ReadError, boot, read}, sector::{DATA, DATA_SIZE, RAW_SIZE, encode}};
+use psxexe::{Header, HeaderError, Region};
+
+/// The sectors of the system area
+pub const SECTORS: usize = SYSTEM_AREA as usize;
+/// The sector with the license text
+pub const TEXT_SECTOR: usize = 4;
+
+/// The size of a sector without sync pattern and header, as some license files store them
+const MODE2_SIZE: usize = RAW_SIZE - 16;
+const SYNC: [u8; 12] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+/// Why a disc can not be licensed
+#[derive(Debug, PartialEq)]
+pub enum LicenseError {
+    /// The license file is not 16 sectors of user data, Mode 2 sectors or raw sectors
+    Size{size: usize},
+    /// A raw sector of the license file has no sync pattern
+    Sync{sector: usize},
+    /// The BIN can not be read
+    Read(ReadError),
+    /// The BIN has no `SYSTEM.CNF` booting an EXE of its root directory
+    NoBoot,
+    /// The EXE the disc boots has no header
+    Exe(HeaderError),
+    /// The EXE is marked for another region
+    Marker{marker: String, region: Region},
+    /// The license file is for another region
+    License{found: Region, region: Region},
+}
+
+impl fmt::Display for LicenseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LicenseError::Size{size}             => write!(f, "{size} bytes are no {SECTORS} sectors of {DATA_SIZE}, {MODE2_SIZE} or {RAW_SIZE} bytes"),
+            LicenseError::Sync{sector}           => write!(f, "sector {sector} of the license has no sync pattern"),
+            LicenseError::Read(error)            => write!(f, "{error}"),
+            LicenseError::NoBoot                 => write!(f, "SYSTEM.CNF boots no file of the root directory"),
+            LicenseError::Exe(error)             => write!(f, "the EXE: {error}"),
+            LicenseError::Marker{marker, region} => write!(f, "the EXE is marked \"{marker}\" instead of \"{}\", see psxexe --region {region}", region.marker()),
+            LicenseError::License{found, region} => write!(f, "the license is for {found} instead of {region}"),
+        }
+    }
+}
+
+impl From<ReadError> for LicenseError {
+    fn from(error: ReadError) -> LicenseError {
+        LicenseError::Read(error)
+    }
+}
+
+/// The raw sectors of the system area
+#[derive(Debug, Clone, PartialEq)]
+pub struct License {
+    pub sectors: Vec<[u8; RAW_SIZE]>,
+}
+
+impl License {
+    /// Reads a license file like `LICENSEA.DAT`
+    ///
+    /// Arguments:
+    /// * `file`: 16 sectors of 2048 bytes of user data, of 2336 bytes from the subheader on
+    ///   or of raw 2352 bytes
+    ///
+    /// Returns: The `License` or why `file` is none
+    pub fn parse(file: &[u8]) -> Result<License, LicenseError> {
+        let size = file.len()/SECTORS;
+        if !file.len().is_multiple_of(SECTORS) || ![DATA_SIZE, MODE2_SIZE, RAW_SIZE].contains(&size) {
+            return Err(LicenseError::Size{size: file.len()});
+        }
+
+        let mut sectors = Vec::new();
+        for (lba, chunk) in file.chunks_exact(size).enumerate() {
+            // Mode 2 and raw sectors are copied as they are, Form 2 sectors have no ECC
+            // `encode` could write. The header is outside of the EDC and ECC.
+            let sector = match size {
+                DATA_SIZE  => encode(lba as u32, DATA, chunk),
+                MODE2_SIZE => {
+                    let mut sector = encode(lba as u32, DATA, &[]);
+                    sector[16..].copy_from_slice(chunk);
+                    sector
+                },
+                _          => {
+                    if chunk[..SYNC.len()] != SYNC {
+                        return Err(LicenseError::Sync{sector: lba});
+                    }
+                    let mut sector = encode(lba as u32, DATA, &[]);
+                    sector[16..].copy_from_slice(&chunk[16..]);
+                    sector
+                },
+            };
+            sectors.push(sector);
+        }
+        Ok(License{sectors})
+    }
+
+    /// Returns: A license with only the text for `region` and no logo
+    pub fn placeholder(region: Region) -> License {
+        let text = format!("          Licensed  by          Sony Computer Entertainment {}", match region {
+            Region::NorthAmerica => "America",
+            Region::Europe       => "Europe",
+            Region::Japan        => "Inc.",
+        });
+
+        let sectors = (0..SECTORS).map(|lba| encode(lba as u32, DATA, if lba == TEXT_SECTOR {text.as_bytes()} else {&[]})).collect();
+        License{sectors}
+    }
+
+    /// Returns: The region the license text is for or `None` if there is no text
+    pub fn region(&self) -> Option<Region> {
+        // The text of the North American and European licenses is split by spaces after the
+        // first four letters, like `Amer  ica`
+        let found = |needle: &[u8]| self.sectors.iter().any(|sector| sector.windows(needle.len()).any(|window| window == needle));
+        [(Region::NorthAmerica, b"Entertainment Amer"), (Region::Europe, b"Entertainment Euro"), (Region::Japan, b"Entertainment Inc.")]
+            .into_iter().find(|(_, needle)| found(*needle)).map(|(region, _)| region)
+    }
+}
+
+/// Writes `license` into the system area of `bin`
+///
+/// Arguments:
+/// * `bin`: The raw sectors of a disc image as `iso` masters it
+/// * `license`: The sectors to write
+/// * `region`: The region of the disc
+///
+/// Returns: The name of the EXE the disc boots or why the license does not fit the disc
+pub fn inject(bin: &mut [u8], license: &License, region: Region) -> Result<String, LicenseError> {
+    let files  = read(bin)?;
+    let exe    = boot(&files).ok_or(LicenseError::NoBoot)?;
+    let header = Header::parse(&exe.data).map_err(LicenseError::Exe)?;
+    if header.region() != Some(region) {
+        return Err(LicenseError::Marker{marker: header.marker, region});
+    }
+    if let Some(found) = license.region().filter(|found| *found != region) {
+        return Err(LicenseError::License{found, region});
+    }
+
+    for (lba, sector) in license.sectors.iter().enumerate() {
+        bin[lba*RAW_SIZE..(lba + 1)*RAW_SIZE].copy_from_slice(sector);
+    }
+    Ok(exe.name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use iso::{Config, File, master, sector::decode};
+
+    use super::*;
+
+    fn bin(region: Region) -> Vec<u8> {
+        let header = Header{pc: 0x8001_0000, gp: 0, load_addr: 0x8001_0000, size: 0x800, stack: 0x801F_FF00, stack_offset: 0, marker: region.marker().to_owned()};
+        let mut data = header.to_bytes();
+        data.resize(data.len() + 0x800, 0);
+        master("APP", File{name: "APP.EXE".to_owned(), data}, Vec::new(), &Config::default()).unwrap().to_bin()
+    }
+
+    #[test]
+    fn inject_placeholder() {
+        let mut bin = bin(Region::Europe);
+        assert_eq!(inject(&mut bin, &License::placeholder(Region::Europe), Region::Europe), Ok("APP.EXE".to_owned()));
+
+        let text = decode(TEXT_SECTOR as u32, bin[TEXT_SECTOR*RAW_SIZE..(TEXT_SECTOR + 1)*RAW_SIZE].try_into().unwrap()).unwrap().1;
+        assert!(String::from_utf8_lossy(text).contains("Sony Computer Entertainment Europe"));
+        assert_eq!(read(&bin).unwrap().len(), 2);
+
+        let error = inject(&mut bin, &License::placeholder(Region::Japan), Region::Japan);
+        assert_eq!(error, Err(LicenseError::Marker{marker: Region::Europe.marker().to_owned(), region: Region::Japan}));
+    }
+
+    #[test]
+    fn license_file() {
+        let mut file = vec![0; SECTORS*DATA_SIZE];
+        file[TEXT_SECTOR*DATA_SIZE..][..37].copy_from_slice(b"Sony Computer Entertainment Amer  ica");
+        let license = License::parse(&file).unwrap();
+        assert_eq!(license.region(), Some(Region::NorthAmerica));
+
+        let raw: Vec<u8> = license.sectors.concat();
+        assert_eq!(License::parse(&raw), Ok(license.clone()));
+        assert_eq!(License::parse(&raw.iter().enumerate().filter(|(idx, _)| idx % RAW_SIZE >= 16).map(|(_, byte)| *byte).collect::<Vec<_>>()), Ok(license.clone()));
+        assert_eq!(License::parse(&file[1..]), Err(LicenseError::Size{size: file.len() - 1}));
+
+        let mut bin = bin(Region::Europe);
+        assert_eq!(inject(&mut bin, &license, Region::Europe), Err(LicenseError::License{found: Region::NorthAmerica, region: Region::Europe}));
+    }
+}
@@ -0,0 +1,74 @@
+//! `license <app.bin> --region <NA|EU|JP> [--license <LICENSE.DAT>] [--out <licensed.bin>]`
+//!
+//! Writes the license of `--region` into the first 16 sectors of a BIN mastered by `iso`, in
+//! place unless `--out` names another BIN. The license comes from a license data file of 16
+//! sectors or is a placeholder with only the license text. Fails without writing anything if
+//! the EXE the disc boots or the license file is for another region.
+
+use std::{env, fs, path::PathBuf, process::ExitCode};
+
+use license::{License, inject};
+use psxexe::Region;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [bin_path, options @ ..] = args.as_slice() else {
+        return usage();
+    };
+
+    let mut region       = None;
+    let mut license_path = None;
+    let mut out_path     = PathBuf::from(bin_path);
+    for option in options.chunks(2) {
+        match option {
+            [flag, value] if flag == "--region"  => match value.parse::<Region>() {
+                Ok(value)  => region = Some(value),
+                Err(error) => {
+                    eprintln!("{error}");
+                    return usage();
+                },
+            },
+            [flag, path] if flag == "--license" => license_path = Some(path.clone()),
+            [flag, path] if flag == "--out"     => out_path = PathBuf::from(path),
+            _                                   => return usage(),
+        }
+    }
+    let Some(region) = region else {
+        return usage();
+    };
+
+    let license = match &license_path {
+        Some(path) => match fs::read(path).map(|file| License::parse(&file)) {
+            Ok(Ok(license)) => license,
+            Ok(Err(error))  => return fail(path, error),
+            Err(error)      => return fail(path, error),
+        },
+        None       => License::placeholder(region),
+    };
+
+    let mut bin = match fs::read(bin_path) {
+        Ok(bin)    => bin,
+        Err(error) => return fail(bin_path, error),
+    };
+    let exe = match inject(&mut bin, &license, region) {
+        Ok(exe)    => exe,
+        Err(error) => return fail(bin_path, error),
+    };
+
+    if let Err(error) = fs::write(&out_path, &bin) {
+        return fail(&out_path.display().to_string(), error);
+    }
+    let source = license_path.unwrap_or_else(|| "placeholder".to_owned());
+    eprintln!("{}: licensed for {region} from {source}, boots {exe}", out_path.display());
+    ExitCode::SUCCESS
+}
+
+fn usage() -> ExitCode {
+    eprintln!("Usage: license <app.bin> --region <NA|EU|JP> [--license <LICENSE.DAT>] [--out <licensed.bin>]");
+    ExitCode::from(2)
+}
+
+fn fail(path: &str, error: impl std::fmt::Display) -> ExitCode {
+    eprintln!("{path}: {error}");
+    ExitCode::FAILURE
+}
@@ -0,0 +1,204 @@
+//! Sums up what the sections of a linker map take from the 2 MiB of RAM
+//!
+//! Every input section is put to the crate of its first symbol, so `sdk::…`, `core::…` and
+//! `<app::… as core::…>` land in `sdk`, `core` and `app`. Symbols without a path like `main`
+//! belong to the crate of their object file and sections `ld.lld` made up itself, like `.got`
+//! and merged strings, to `<internal>`. What the linker script adds itself, the header, its
+//! fields and the alignment padding, is `(linker script)`.
+
+use std::collections::BTreeMap;
+
+use disasm::map::{Input, Map};
+
+/// The crate of the header, the fields and the padding written by the linker script
+pub const LINKER_SCRIPT: &str = "(linker script)";
+
+/// An input section with the crate it belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// The demangled symbol or the input section if it has none, like `<internal>:(.got)`
+    pub name:    String,
+    /// The crate
+    pub krate:   String,
+    /// The output section, like `.text`
+    pub section: String,
+    /// The size in bytes
+    pub size:    u32,
+}
+
+/// The RAM of the console as given by `RAM_BASE` and `RAM_SIZE` of `ps-exe.ld`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ram {
+    /// The first address
+    pub base: u32,
+    /// The size in bytes
+    pub size: u32,
+    /// The bytes up to the end of the last section, including the BIOS area in front of the EXE
+    pub used: u32,
+}
+
+/// The sizes of a linker map
+#[derive(Debug, Default)]
+pub struct Usage {
+    /// The loaded output sections with their sizes in the order of the map
+    pub sections: Vec<(String, u32)>,
+    /// The sizes per crate, largest first
+    pub crates:   Vec<(String, u32)>,
+    /// The input sections, largest first
+    pub items:    Vec<Item>,
+    /// The RAM budget, `None` if the map does not come from `ps-exe.ld`
+    pub ram:      Option<Ram>,
+}
+
+impl Usage {
+    /// Sums up the sizes of `map`
+    ///
+    /// Arguments:
+    /// * `map`: The parsed linker map
+    ///
+    /// Returns: The new `Usage`
+    pub fn new(map: &Map) -> Usage {
+        let mut usage  = Usage::default();
+        let mut crates = BTreeMap::new();
+
+        // Sections at 0 like `.comment` are not loaded
+        for section in map.sections.iter().filter(|section| section.address != 0) {
+            let items: Vec<Item> = map.inputs.iter().filter(|input| input.section == section.name && input.name.contains(":(")).map(|input| {
+                Item{name: input.symbol.clone().unwrap_or_else(|| input.name.clone()), krate: krate(input), section: section.name.clone(), size: input.size}
+            }).collect();
+
+            let script = section.size.saturating_sub(items.iter().map(|item| item.size).sum());
+            if script > 0 {
+                *crates.entry(LINKER_SCRIPT.to_owned()).or_default() += script;
+            }
+            for item in &items {
+                *crates.entry(item.krate.clone()).or_default() += item.size;
+            }
+
+            usage.sections.push((section.name.clone(), section.size));
+            usage.items.extend(items);
+        }
+
+        usage.crates = crates.into_iter().collect();
+        usage.crates.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        usage.items.sort_by_key(|item| std::cmp::Reverse(item.size));
+
+        let assignment = |name: &str| map.assignments.iter().find(|(symbol, _)| symbol == name).and_then(|(_, expression)| parse_size(expression));
+        usage.ram = assignment("RAM_BASE").zip(assignment("RAM_SIZE")).map(|(base, size)| {
+            let end = map.sections.iter().filter(|section| section.address >= base).map(|section| section.address + section.size).max().unwrap_or(base);
+            Ram{base, size, used: end - base}
+        });
+        usage
+    }
+}
+
+/// A size that differs between two maps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// The section, crate or item
+    pub name: String,
+    /// The size in the old map, `0` if it is new
+    pub old:  u32,
+    /// The size in the new map, `0` if it is gone
+    pub new:  u32,
+}
+
+impl Change {
+    /// Returns: How many bytes were added
+    pub fn delta(&self) -> i64 {
+        self.new as i64 - self.old as i64
+    }
+}
+
+/// Compares the sizes of two maps by name
+///
+/// Arguments:
+/// * `old`: The names and sizes of the old map, sizes of the same name are added up
+/// * `new`: The names and sizes of the new map
+///
+/// Returns: Every name with a different size, the largest difference first
+pub fn diff(old: impl IntoIterator<Item = (String, u32)>, new: impl IntoIterator<Item = (String, u32)>) -> Vec<Change> {
+    let mut sizes: BTreeMap<String, (u32, u32)> = BTreeMap::new();
+    for (name, size) in old {
+        sizes.entry(name).or_default().0 += size;
+    }
+    for (name, size) in new {
+        sizes.entry(name).or_default().1 += size;
+    }
+
+    let mut changes: Vec<Change> = sizes.into_iter().filter(|(_, (old, new))| old != new).map(|(name, (old, new))| Change{name, old, new}).collect();
+    changes.sort_by_key(|change| std::cmp::Reverse(change.delta().abs()));
+    changes
+}
+
+/// Parses a size of the linker script like `2M`, `64K`, `0x80000000` or `0`
+pub fn parse_size(expression: &str) -> Option<u32> {
+    let expression = expression.trim();
+    let (number, factor) = match expression.as_bytes().last()? {
+        b'K' => (&expression[..expression.len() - 1], 1 << 10),
+        b'M' => (&expression[..expression.len() - 1], 1 << 20),
+        _    => (expression, 1),
+    };
+
+    let value = match number.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None      => number.parse().ok()?,
+    };
+    value.checked_mul(factor)
+}
+
+/// Returns: The crate `input` belongs to
+fn krate(input: &Input) -> String {
+    // The crate is the segment in front of the first `::`, in `<&T as core::fmt::Debug>` too
+    if let Some(path) = input.symbol.as_deref().and_then(|symbol| symbol.find("::").map(|end| &symbol[..end])) {
+        let start = path.rfind(|c: char| !c.is_alphanumeric() && c != '_').map_or(0, |idx| idx + 1);
+        return path[start..].to_owned();
+    }
+
+    let (file, _) = input.name.split_once(":(").unwrap_or((&input.name, ""));
+    if file == "<internal>" {
+        return file.to_owned();
+    }
+
+    // `libsdk-<hash>.rlib(sdk-<hash>.sdk.<hash>-cgu.0.rcgu.o)` or `app-<hash>.app.<hash>-cgu.0.rcgu.o`
+    let file = file.split('(').next().unwrap_or_default();
+    let name = file.rsplit('/').next().unwrap_or_default();
+    let name = name.strip_prefix("lib").filter(|_| file.ends_with(".rlib")).unwrap_or(name);
+    name.split(['-', '.']).next().unwrap_or_default().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "     VMA      LMA     Size Align Out     In      Symbol
+       0        0        0     1 RAM_BASE = 0x80000000
+       0        0        0     1 RAM_SIZE = 2M
+8000f800 8000f800      800     1 .psx_exe_header
+8000f800 8000f800        1     1         BYTE(0x50)
+80010000 80010000       60    16 .text
+80010000 80010000       38     4         /t/deps/app-1a.app.2b-cgu.0.rcgu.o:(.text.main)
+80010000 80010000       38     1                 main
+80010038 80010038       20     4         /t/deps/app-1a.app.2b-cgu.0.rcgu.o:(.text._RNvNtCs1_3sdk10peripheral17update_controller)
+80010038 80010038       20     1                 sdk::peripheral::update_controller
+80010058 80010058        8     4         /t/deps/libcore-3c.rlib(core-3c.core.4d-cgu.0.rcgu.o):(.text._RNvXs_NtCs2_4core3fmtRNtB4_9ArgumentsNtB4_7Display3fmt)
+80010058 80010058        8     1                 <&core::fmt::Arguments as core::fmt::Display>::fmt
+80010060 80010060      7a0    16 .data
+80010060 80010060        8     4         <internal>:(.got)
+80010068 80010068      798     1         . = ALIGN(2048)
+80010800 80010800        0     1 .bss
+";
+
+    #[test]
+    fn usage() {
+        let usage = Usage::new(&Map::parse(MAP));
+
+        assert_eq!(usage.sections, [(".psx_exe_header".to_owned(), 0x800), (".text".to_owned(), 0x60), (".data".to_owned(), 0x7A0), (".bss".to_owned(), 0)]);
+        assert_eq!(usage.crates, [(LINKER_SCRIPT.to_owned(), 0xF98), ("app".to_owned(), 0x38), ("sdk".to_owned(), 0x20), ("<internal>".to_owned(), 8), ("core".to_owned(), 8)]);
+        assert_eq!(usage.items[1], Item{name: "sdk::peripheral::update_controller".to_owned(), krate: "sdk".to_owned(), section: ".text".to_owned(), size: 0x20});
+        assert_eq!(usage.ram, Some(Ram{base: 0x8000_0000, size: 2 << 20, used: 0x1_0800}));
+
+        let changes = diff(usage.sections.clone(), [(".text".to_owned(), 0x70), (".data".to_owned(), 0x7A0)]);
+        assert_eq!(changes.iter().map(|change| (change.name.as_str(), change.delta())).collect::<Vec<_>>(), [(".psx_exe_header", -0x800), (".text", 0x10)]);
+    }
+}
@@ -0,0 +1,106 @@
+//! `mapsize [<app.map | app.elf>] [<other.map | other.elf>] [--top <count>]`
+//!
+//! Prints the sizes of the loaded sections of a linker map or the ELF `psx-ld` keeps,
+//! `target/app.map` if none is given, how much of the RAM of `ps-exe.ld` they use, the sizes per
+//! crate and the `--top` largest input sections. With a second map it prints what changed from the first to the second one
+//! instead, like between the `app.map` of two `variants`.
+
+use std::{env, path::Path, process::ExitCode};
+
+use disasm::map::Map;
+use mapsize::{Change, Usage, diff};
+
+const DEFAULT_MAP: &str = "target/app.map";
+const DEFAULT_TOP: usize = 20;
+
+fn main() -> ExitCode {
+    let mut paths = Vec::new();
+    let mut top   = DEFAULT_TOP;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--top"                   => match args.next().and_then(|count| count.parse().ok()) {
+                Some(count) => top = count,
+                None        => return usage(),
+            },
+            _ if arg.starts_with("--") => return usage(),
+            _                          => paths.push(arg),
+        }
+    }
+
+    let mut maps = Vec::new();
+    for path in paths.iter().map(String::as_str).chain(paths.is_empty().then_some(DEFAULT_MAP)) {
+        match Map::load(Path::new(path)) {
+            Ok(map)    => maps.push((path, Usage::new(&map))),
+            Err(error) => {
+                eprintln!("{path}: {error}");
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+
+    match maps.as_slice() {
+        [(path, usage)]                    => report(path, usage, top),
+        [(old_path, old), (new_path, new)] => compare(old_path, old, new_path, new, top),
+        _                                  => return usage(),
+    }
+    ExitCode::SUCCESS
+}
+
+fn usage() -> ExitCode {
+    eprintln!("Usage: mapsize [<app.map | app.elf>] [<other.map | other.elf>] [--top <count>]");
+    ExitCode::from(2)
+}
+
+/// Prints the sizes of a single map
+fn report(path: &str, usage: &Usage, top: usize) {
+    println!("{path}");
+    for (section, size) in &usage.sections {
+        println!("{size:>9}  {section}");
+    }
+
+    if let Some(ram) = usage.ram {
+        println!("\nRAM: {} of {} bytes used up to the end of the last section ({:.1}%), {} left for the stack and heap",
+            ram.used, ram.size, ram.used as f64*100.0/ram.size as f64, ram.size.saturating_sub(ram.used));
+    }
+
+    println!("\ncrates:");
+    for (krate, size) in &usage.crates {
+        println!("{size:>9}  {krate}");
+    }
+
+    println!("\nlargest {} of {} input sections:", top.min(usage.items.len()), usage.items.len());
+    for item in usage.items.iter().take(top) {
+        println!("{:>9}  {:<8} {:<12} {}", item.size, item.section, item.krate, item.name);
+    }
+}
+
+/// Prints what changed between two maps
+fn compare(old_path: &str, old: &Usage, new_path: &str, new: &Usage, top: usize) {
+    println!("{old_path} -> {new_path}");
+    print_changes("sections", &diff(old.sections.clone(), new.sections.clone()), usize::MAX);
+
+    if let (Some(old), Some(new)) = (old.ram, new.ram) {
+        println!("\nRAM: {} -> {} bytes used ({:+})", old.used, new.used, new.used as i64 - old.used as i64);
+    }
+
+    print_changes("crates", &diff(old.crates.clone(), new.crates.clone()), usize::MAX);
+
+    let items = |usage: &Usage| usage.items.iter().map(|item| (format!("{:<8} {}", item.section, item.name), item.size)).collect::<Vec<_>>();
+    print_changes("input sections", &diff(items(old), items(new)), top);
+}
+
+fn print_changes(title: &str, changes: &[Change], top: usize) {
+    println!("\n{title}:");
+    if changes.is_empty() {
+        println!("  unchanged");
+    }
+
+    for change in changes.iter().take(top) {
+        println!("{:>9} -> {:>9} {:>+8}  {}", change.old, change.new, change.delta(), change.name);
+    }
+    if changes.len() > top {
+        println!("  and {} more", changes.len() - top);
+    }
+}
@@ -0,0 +1,491 @@
+//! Inserts a `nop` in front of every instruction of a PS-X EXE that reads a register in the
+//! delay slot of its load
+//!
+//! A workaround for shipping while the backend schedules such instructions: the hazards are
+//! found like `disasm` does and everything behind an inserted `nop` moves down. Branches and
+//! jumps are re-encoded from their decoded targets, `%hi`/`%lo` pairs and address words are
+//! fixed with the relocations `ld.lld` keeps in the ELF of the same link with `--emit-relocs`.
+//! `.data` and `.bss` move as a whole so they keep their alignment, and the header is updated.
+
+use std::{collections::HashMap, fmt, ops::Range};
+
+use disasm::{decode::decode, disassemble, map::Map};
+use hazard::{Hazard, find_hazards, mips};
+use psxexe::{Exe, HEADER_SIZE, HeaderError, elf::{Elf, R_MIPS_26, R_MIPS_32, R_MIPS_HI16, R_MIPS_LO16, R_MIPS_NONE, SHN_ABS, Section}};
+
+const NOP: u32 = 0;
+/// `ps-exe.ld` pads `.data` to whole sectors for loading from ISO
+const SECTOR: u32 = 2048;
+
+/// Why an EXE could not be patched
+#[derive(Debug, PartialEq)]
+pub enum FixError {
+    /// The EXE could not be read
+    Exe(HeaderError),
+    /// The ELF has no such section
+    Missing{section: &'static str},
+    /// The section of the ELF differs from the EXE, so they are not of the same link
+    Mismatch{section: &'static str},
+    /// The instruction reading the register is in a delay slot itself, a `nop` would take its place
+    DelaySlot{address: u32},
+    /// A relocation of a type that cannot be moved
+    Unsupported{address: u32, kind: u8},
+    /// A `%hi` without `%lo` or the other way around
+    Unpaired{address: u32},
+    /// The moved target does not fit into the instruction anymore
+    OutOfRange{address: u32},
+    /// A relocation against an absolute symbol whose value lies in what moves, it would keep
+    /// pointing to the old address
+    Absolute{address: u32, symbol: String},
+    /// Hazards were found again after patching
+    Remaining{count: usize},
+}
+
+impl fmt::Display for FixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixError::Exe(error)                 => write!(f, "{error}"),
+            FixError::Missing{section}           => write!(f, "the ELF has no {section}"),
+            FixError::Mismatch{section}          => write!(f, "{section} of the ELF differs from the EXE, both have to come from the same build"),
+            FixError::DelaySlot{address}         => write!(f, "{address:08x}: the instruction reading the loaded register is in a delay slot"),
+            FixError::Unsupported{address, kind} => write!(f, "{address:08x}: relocation type {kind} is not supported"),
+            FixError::Unpaired{address}          => write!(f, "{address:08x}: %hi and %lo relocations do not pair up"),
+            FixError::OutOfRange{address}        => write!(f, "{address:08x}: the moved target does not fit into the instruction"),
+            FixError::Absolute{address, symbol}  => write!(f, "{address:08x}: the absolute symbol {symbol} points into a section that moves"),
+            FixError::Remaining{count}           => write!(f, "{count} hazards are left after patching"),
+        }
+    }
+}
+
+/// The part of the text segment an address points into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    /// Code, moves by the `nop`s in front of it
+    Text,
+    /// Moves by `Layout::data_shift`
+    Data,
+    /// Moves by `Layout::bss_shift`
+    Bss,
+    /// Absolute addresses like `STACK_INIT` or I/O ports
+    Fixed,
+}
+
+/// Where everything ends up after inserting the `nop`s
+#[derive(Debug)]
+pub struct Layout {
+    /// `.text` before patching
+    pub text:       Range<u32>,
+    /// `.data` before patching, including the padding
+    pub data:       Range<u32>,
+    /// The sorted addresses of the instructions that get a `nop` in front
+    pub nops:       Vec<u32>,
+    /// How far `.data` moves, the inserted bytes rounded to its alignment
+    pub data_shift: u32,
+    /// How far `.bss` moves, the data shift rounded to whole sectors
+    pub bss_shift:  u32,
+}
+
+impl Layout {
+    /// Creates the layout for inserting `nops`
+    ///
+    /// Arguments:
+    /// * `text`: The `.text` section
+    /// * `data`: The `.data` section
+    /// * `nops`: The addresses of the instructions that get a `nop` in front, in any order
+    ///
+    /// Returns: The new `Layout`
+    pub fn new(text: &Section, data: &Section, mut nops: Vec<u32>) -> Layout {
+        nops.sort_unstable();
+        nops.dedup();
+
+        let data_shift = (nops.len() as u32*4).next_multiple_of(data.align.max(4));
+        Layout{text: text.address..text.end(), data: data.address..data.end(), nops, data_shift, bss_shift: data_shift.next_multiple_of(SECTOR)}
+    }
+
+    /// Returns: Where the instruction or data word at `address` ends up
+    pub fn moved(&self, address: u32) -> u32 {
+        match address < self.text.end {
+            true  => address + 4*self.nops.partition_point(|nop| *nop <= address) as u32,
+            false => address + self.data_shift,
+        }
+    }
+
+    /// Returns: Where a branch to `address` has to continue, on the `nop` in front of it if there is one
+    pub fn target(&self, address: u32) -> u32 {
+        address + 4*self.nops.partition_point(|nop| *nop < address) as u32
+    }
+
+    /// Returns: The new value of an `address` pointing into `region`
+    pub fn relocate(&self, region: Region, address: u32) -> u32 {
+        match region {
+            Region::Text                             => self.target(address),
+            // The end of `.data` is the start of `.bss`
+            Region::Data if address == self.data.end => address + self.bss_shift,
+            Region::Data                             => address + self.data_shift,
+            Region::Bss                              => address + self.bss_shift,
+            Region::Fixed                            => address,
+        }
+    }
+}
+
+/// What was changed at a site
+#[derive(Debug, Clone)]
+pub enum Fixup {
+    /// A `nop` was inserted in front of the instruction reading the register
+    Nop(Hazard),
+    /// The target of a branch
+    Branch{from: u32, to: u32},
+    /// The target of a `j` or `jal`
+    Jump{from: u32, to: u32},
+    /// A `lui` with `%hi` of an address
+    High{from: u32, to: u32},
+    /// An `addiu` or memory access with `%lo` of an address
+    Low{from: u32, to: u32},
+    /// An address word
+    Word{from: u32, to: u32},
+    /// A field of the PS-X EXE header
+    Header{field: &'static str, from: u32, to: u32},
+}
+
+impl fmt::Display for Fixup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fixup::Nop(hazard)             => write!(f, "nop in front of `{}`, reads {} loaded by `{}`", hazard.user, hazard.register, hazard.load),
+            Fixup::Branch{from, to}        => write!(f, "branch to {from:08x} -> {to:08x}"),
+            Fixup::Jump{from, to}          => write!(f, "jump to {from:08x} -> {to:08x}"),
+            Fixup::High{from, to}          => write!(f, "%hi of {from:08x} -> {to:08x}"),
+            Fixup::Low{from, to}           => write!(f, "%lo of {from:08x} -> {to:08x}"),
+            Fixup::Word{from, to}          => write!(f, "word {from:08x} -> {to:08x}"),
+            Fixup::Header{field, from, to} => write!(f, "header {field} {from:08x} -> {to:08x}"),
+        }
+    }
+}
+
+/// A patched site
+#[derive(Debug, Clone)]
+pub struct Patch {
+    /// The address before patching
+    pub address: u32,
+    /// The address after patching, for `Fixup::Nop` the one of the `nop`
+    pub moved:   u32,
+    /// What was changed
+    pub fixup:   Fixup,
+}
+
+/// A patched PS-X EXE
+#[derive(Debug)]
+pub struct Fixed {
+    /// The new content of the file
+    pub file:    Vec<u8>,
+    /// Where everything moved to
+    pub layout:  Layout,
+    /// Every changed site in the order of the addresses
+    pub patches: Vec<Patch>,
+}
+
+/// Inserts a `nop` in front of every instruction reading a register in the delay slot of its load
+///
+/// Arguments:
+/// * `file`: The content of the PS-X EXE
+/// * `elf`: The ELF of the same link, linked with `--emit-relocs`
+/// * `map`: The symbols for naming the functions of the hazards, can be empty
+///
+/// Returns: The patched EXE with every changed site or why it could not be patched
+pub fn fix(file: &[u8], elf: &Elf, map: &Map) -> Result<Fixed, FixError> {
+    let exe = Exe::parse(file).map_err(FixError::Exe)?;
+    let (text_idx, text) = elf.section(".text").ok_or(FixError::Missing{section: ".text"})?;
+    let (data_idx, data) = elf.section(".data").ok_or(FixError::Missing{section: ".data"})?;
+    let bss_idx          = elf.section(".bss").map(|(idx, _)| idx);
+    let bss_end          = elf.section(".bss").map_or(data.end(), |(_, bss)| bss.end());
+
+    let offset = |address: u32| address.wrapping_sub(exe.header.load_addr) as usize;
+    for (name, section) in [(".text", text), (".data", data)] {
+        if exe.text.get(offset(section.address)..offset(section.end())) != Some(section.data) {
+            return Err(FixError::Mismatch{section: name});
+        }
+    }
+    // Anything behind `.data` would be neither moved nor kept in place
+    if offset(data.end()) != exe.text.len() {
+        return Err(FixError::Mismatch{section: ".data"});
+    }
+
+    let listing     = disassemble(&exe, map, text.end());
+    let mut patches = Vec::new();
+    for hazard in find_hazards(&listing) {
+        let address = listing.instructions[hazard.line - 1].address.unwrap_or_default();
+        if hazard.line > 1 && mips::is_branch(&listing.instructions[hazard.line - 2].mnemonic) {
+            return Err(FixError::DelaySlot{address});
+        }
+        patches.push(Patch{address, moved: 0, fixup: Fixup::Nop(hazard)});
+    }
+
+    let layout = Layout::new(text, data, patches.iter().map(|patch| patch.address).collect());
+    for patch in &mut patches {
+        patch.moved = layout.target(patch.address);
+    }
+
+    let read      = |address: u32| u32::from_le_bytes(exe.text[offset(address)..offset(address) + 4].try_into().unwrap());
+    let mut image = exe.text.to_vec();
+    let mut write = |address: u32, word: u32| image[offset(address)..offset(address) + 4].copy_from_slice(&word.to_le_bytes());
+
+    for (address, word) in exe.words().take_while(|(address, _)| *address < text.end()) {
+        let Some(from) = decode(address, word).target else {
+            continue;
+        };
+
+        let moved = layout.moved(address);
+        let to    = layout.target(from);
+        let (patched, fixup) = match word >> 26 {
+            // `j` and `jal` keep the upper bits of the delay slot address
+            2 | 3 => {
+                if (moved + 4) & 0xF000_0000 != to & 0xF000_0000 {
+                    return Err(FixError::OutOfRange{address});
+                }
+                (word & 0xFC00_0000 | (to >> 2) & 0x03FF_FFFF, Fixup::Jump{from, to})
+            },
+            _     => {
+                let offset = i16::try_from(to.wrapping_sub(moved + 4) as i32 >> 2).map_err(|_| FixError::OutOfRange{address})?;
+                (word & 0xFFFF_0000 | offset as u16 as u32, Fixup::Branch{from, to})
+            },
+        };
+
+        if patched != word {
+            write(address, patched);
+            patches.push(Patch{address, moved, fixup});
+        }
+    }
+
+    let section = |symbol: usize| elf.symbols.get(symbol).map(|symbol| symbol.section as usize);
+    let region  = |section: Option<usize>| match section {
+        Some(idx) if idx == text_idx      => Region::Text,
+        Some(idx) if idx == data_idx      => Region::Data,
+        Some(idx) if Some(idx) == bss_idx => Region::Bss,
+        _                                 => Region::Fixed,
+    };
+    // The value of an absolute symbol does not tell where it belongs, only where it points to.
+    // `ps-exe.ld` defines the symbols it hands to the code relative to the sections, one that
+    // is not would silently keep the old address.
+    let resolve = |address: u32, symbol: usize, from: u32| -> Result<u32, FixError> {
+        let to = layout.relocate(region(section(symbol)), from);
+        let Some(absolute) = elf.symbols.get(symbol).filter(|symbol| symbol.section == SHN_ABS) else {
+            return Ok(to);
+        };
+        let points_to = match from {
+            _ if layout.text.contains(&from)                 => Region::Text,
+            _ if (data.address..=data.end()).contains(&from) => Region::Data,
+            _ if (data.end()..=bss_end).contains(&from)      => Region::Bss,
+            _                                                => Region::Fixed,
+        };
+        match layout.relocate(points_to, from) == from {
+            true  => Ok(to),
+            false => Err(FixError::Absolute{address, symbol: absolute.name.clone()}),
+        }
+    };
+
+    // A `%lo` belongs to the `%hi`s of the same symbol before it, several `%lo` can share them
+    let mut pending: HashMap<usize, Vec<u32>> = HashMap::new();
+    let mut last:    HashMap<usize, Vec<u32>> = HashMap::new();
+    let mut lows = Vec::new();
+    for relocation in elf.relocations.iter().filter(|relocation| relocation.section == text_idx || relocation.section == data_idx) {
+        let address = relocation.offset;
+        match relocation.kind {
+            // The jumps were already re-encoded above
+            R_MIPS_NONE | R_MIPS_26 => (),
+            R_MIPS_32               => {
+                let from = read(address);
+                let to   = resolve(address, relocation.symbol, from)?;
+                if from != to {
+                    write(address, to);
+                    patches.push(Patch{address, moved: layout.moved(address), fixup: Fixup::Word{from, to}});
+                }
+            },
+            R_MIPS_HI16             => pending.entry(relocation.symbol).or_default().push(address),
+            R_MIPS_LO16             => {
+                let highs = match pending.remove(&relocation.symbol) {
+                    Some(highs) => {
+                        last.insert(relocation.symbol, highs.clone());
+                        highs
+                    },
+                    None        => last.get(&relocation.symbol).cloned().ok_or(FixError::Unpaired{address})?,
+                };
+                lows.push((highs, address, relocation.symbol));
+            },
+            kind                    => return Err(FixError::Unsupported{address, kind}),
+        }
+    }
+
+    if let Some(address) = pending.values().flatten().min() {
+        return Err(FixError::Unpaired{address: *address});
+    }
+
+    let mut highs: HashMap<u32, (u32, u32)> = HashMap::new();
+    for (high_addresses, address, symbol) in lows {
+        let low  = read(address);
+        let from = (read(high_addresses[0]) << 16).wrapping_add(low as i16 as u32);
+        let to   = resolve(address, symbol, from)?;
+
+        // The first `%lo` decides the new `%hi`, all others have to reach their address from it
+        let first = *highs.entry(high_addresses[0]).or_insert((from, to));
+        for high_address in &high_addresses[1..] {
+            highs.entry(*high_address).or_insert(first);
+        }
+        let high = first.1.wrapping_add(0x8000) & 0xFFFF_0000;
+
+        let offset  = i16::try_from(to.wrapping_sub(high) as i32).map_err(|_| FixError::OutOfRange{address})?;
+        let patched = low & 0xFFFF_0000 | offset as u16 as u32;
+        if patched != low {
+            write(address, patched);
+            patches.push(Patch{address, moved: layout.moved(address), fixup: Fixup::Low{from, to}});
+        }
+    }
+
+    for (address, (from, to)) in highs {
+        let high    = read(address);
+        let patched = high & 0xFFFF_0000 | to.wrapping_add(0x8000) >> 16;
+        if patched != high {
+            write(address, patched);
+            patches.push(Patch{address, moved: layout.moved(address), fixup: Fixup::High{from: from.wrapping_add(0x8000) & 0xFFFF_0000, to: to.wrapping_add(0x8000) & 0xFFFF_0000}});
+        }
+    }
+
+    let mut output = file[..HEADER_SIZE].to_vec();
+    for (address, word) in (layout.text.start..).step_by(4).zip(image[..offset(text.end())].chunks(4)) {
+        if layout.nops.binary_search(&address).is_ok() {
+            output.extend(NOP.to_le_bytes());
+        }
+        output.extend(word);
+    }
+    output.resize(HEADER_SIZE + offset(data.address + layout.data_shift), 0);
+    output.extend(&image[offset(data.address)..]);
+    output.resize(HEADER_SIZE + exe.text.len() + layout.bss_shift as usize, 0);
+
+    let gp     = region(elf.symbol("_gp").map(|gp| gp.section as usize));
+    let fields = [
+        ("pc", 0x10, layout.relocate(if layout.text.contains(&exe.header.pc) {Region::Text} else {Region::Fixed}, exe.header.pc)),
+        ("gp", 0x14, layout.relocate(gp, exe.header.gp)),
+        ("size", 0x1C, (output.len() - HEADER_SIZE) as u32),
+    ];
+    for (field, field_offset, to) in fields {
+        let from = u32::from_le_bytes(output[field_offset..field_offset + 4].try_into().unwrap());
+        if from != to {
+            output[field_offset..field_offset + 4].copy_from_slice(&to.to_le_bytes());
+            let address = exe.header.load_addr.wrapping_sub(HEADER_SIZE as u32) + field_offset as u32;
+            patches.push(Patch{address, moved: address, fixup: Fixup::Header{field, from, to}});
+        }
+    }
+    patches.sort_by_key(|patch| patch.address);
+
+    // The `nop`s must not have moved a hazard anywhere else
+    let patched = Exe::parse(&output).map_err(FixError::Exe)?;
+    let count   = find_hazards(&disassemble(&patched, &Map::default(), layout.moved(text.end() - 4) + 4)).len();
+    if count > 0 {
+        return Err(FixError::Remaining{count});
+    }
+
+    Ok(Fixed{file: output, layout, patches})
+}
+
+#[cfg(test)]
+mod tests {
+    use psxexe::elf::{Relocation, Symbol};
+
+    use super::*;
+
+    const PROGRAM: [u32; 9] = [
+        0x8C82_0000, // lw     $2, 0($4)
+        0x2443_0001, // addiu  $3, $2, 1
+        0x1000_FFFE, // b      80010004
+        0x0000_0000, // nop
+        0x3C05_8001, // lui    $5, %hi(80010020)
+        0x24A5_0020, // addiu  $5, $5, %lo(80010020)
+        0x0C00_4000, // jal    80010000
+        0x0000_0000, // nop
+        0x8001_0014, // .word  80010014
+    ];
+
+    fn word(bytes: &[u8], address: u32) -> u32 {
+        let offset = HEADER_SIZE + (address - 0x8001_0000) as usize;
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Returns: A PS-X EXE of `PROGRAM` with `.data` right behind it
+    fn exe() -> Vec<u8> {
+        let mut file = vec![0; HEADER_SIZE + 0x800];
+        file[..8].copy_from_slice(b"PS-X EXE");
+        file[0x10..0x14].copy_from_slice(&0x8001_0018u32.to_le_bytes());
+        file[0x18..0x1C].copy_from_slice(&0x8001_0000u32.to_le_bytes());
+        file[0x1C..0x20].copy_from_slice(&0x800u32.to_le_bytes());
+        for (idx, word) in PROGRAM.iter().enumerate() {
+            file[HEADER_SIZE + idx*4..HEADER_SIZE + idx*4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        file
+    }
+
+    /// Returns: The ELF `exe` was linked from
+    fn elf(file: &[u8]) -> Elf<'_> {
+        let segment = &file[HEADER_SIZE..];
+        let section = |name: &str, address: u32, size: u32, align: u32, data: &'static [u8]| Section{name: name.to_owned(), kind: 0, flags: 0, address, size, align, data};
+        Elf{
+            entry:       0,
+            sections:    vec![
+                section("", 0, 0, 0, &[]),
+                Section{data: &segment[..0x20], ..section(".text", 0x8001_0000, 0x20, 4, &[])},
+                Section{data: &segment[0x20..], ..section(".data", 0x8001_0020, 0x7E0, 16, &[])},
+                section(".bss", 0x8001_0800, 0, 1, &[]),
+            ],
+            symbols:     vec![
+                Symbol{name: String::new(), value: 0, size: 0, kind: 0, section: 0},
+                Symbol{name: ".text".to_owned(), value: 0x8001_0000, size: 0, kind: 0, section: 1},
+                Symbol{name: ".data".to_owned(), value: 0x8001_0020, size: 0, kind: 0, section: 2},
+            ],
+            relocations: vec![
+                Relocation{section: 1, offset: 0x8001_0010, kind: R_MIPS_HI16, symbol: 2},
+                Relocation{section: 1, offset: 0x8001_0014, kind: R_MIPS_LO16, symbol: 2},
+                Relocation{section: 1, offset: 0x8001_0018, kind: R_MIPS_26, symbol: 1},
+                Relocation{section: 2, offset: 0x8001_0020, kind: R_MIPS_32, symbol: 1},
+            ],
+        }
+    }
+
+    #[test]
+    fn insert_nop() {
+        let file = exe();
+        let elf  = elf(&file);
+
+        let fixed = fix(&file, &elf, &Map::default()).unwrap();
+        let file  = &fixed.file;
+
+        assert_eq!(fixed.layout.nops, [0x8001_0004]);
+        assert_eq!((fixed.layout.data_shift, fixed.layout.bss_shift), (0x10, 0x800));
+        assert_eq!(file.len(), HEADER_SIZE + 0x1000);
+        assert_eq!(Exe::parse(file).unwrap().header.pc, 0x8001_001C);
+        assert_eq!(word(file, 0x8001_0004), NOP);
+        assert_eq!(word(file, 0x8001_0008), 0x2443_0001);
+        // The branch to the hazard continues on the `nop` in front of it
+        assert_eq!(word(file, 0x8001_000C), 0x1000_FFFD);
+        assert_eq!(word(file, 0x8001_0014), 0x3C05_8001);
+        assert_eq!(word(file, 0x8001_0018), 0x24A5_0030);
+        assert_eq!(word(file, 0x8001_001C), 0x0C00_4000);
+        assert_eq!(word(file, 0x8001_0030), 0x8001_0018);
+        assert!(matches!(fixed.patches[0].fixup, Fixup::Header{field: "pc", ..}));
+        assert_eq!(fixed.patches.iter().filter(|patch| matches!(patch.fixup, Fixup::Nop(_))).count(), 1);
+    }
+
+    #[test]
+    fn absolute_symbol_into_text() {
+        let absolute = |file: &[u8], value: u32| {
+            // The address word behind `.text` points into it through a symbol of the linker script
+            let mut elf = elf(file);
+            elf.symbols.push(Symbol{name: "__text_symbol".to_owned(), value, size: 0, kind: 0, section: SHN_ABS});
+            elf.relocations[3].symbol = 3;
+            fix(file, &elf, &Map::default()).map(|fixed| word(&fixed.file, 0x8001_0030))
+        };
+
+        let mut file = exe();
+        assert_eq!(absolute(&file, 0x8001_0014), Err(FixError::Absolute{address: 0x8001_0020, symbol: "__text_symbol".to_owned()}));
+
+        // In front of the `nop` nothing moves, the word can stay
+        file[HEADER_SIZE + 0x20..HEADER_SIZE + 0x24].copy_from_slice(&0x8001_0000u32.to_le_bytes());
+        assert_eq!(absolute(&file, 0x8001_0000), Ok(0x8001_0000));
+    }
+}
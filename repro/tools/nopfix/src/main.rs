@@ -0,0 +1,84 @@
+//! `nopfix <app.exe> [<app.elf>] [--map <app.map>] [--out <fixed.exe>]`
+//!
+//! Inserts a `nop` in front of every instruction of `app.exe` reading a register in the delay
+//! slot of its load and writes the result to `--out`, by default next to `app.exe` as
+//! `app.fixed.exe`. `app.elf` is the ELF of the same link with `--emit-relocs`, by default the
+//! one `psx-ld` linked `app.exe` from. Every patched site is printed, labelled with the
+//! symbols of the ELF unless `--map` names a map or another ELF. The EXE is left alone if
+//! nothing needs a `nop`.
+
+use std::{env, fs, path::{Path, PathBuf}, process::ExitCode};
+
+use disasm::{linked_elf, map::Map, symbolize};
+use nopfix::{Fixup, fix};
+use psxexe::elf::Elf;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (exe_path, elf_path, options) = match args.as_slice() {
+        [exe, elf, options @ ..] if !elf.starts_with("--") => (exe, PathBuf::from(elf), options),
+        [exe, options @ ..]                                 => (exe, linked_elf(Path::new(exe)).unwrap_or_else(|| Path::new(exe).with_extension("elf")), options),
+        []                                                  => return usage(),
+    };
+    let elf_name = elf_path.display().to_string();
+
+    let mut map_path = None;
+    let mut out_path = Path::new(exe_path).with_extension("fixed.exe");
+    for option in options.chunks(2) {
+        match option {
+            [flag, map] if flag == "--map" => map_path = Some(map.clone()),
+            [flag, out] if flag == "--out" => out_path = PathBuf::from(out),
+            _                              => return usage(),
+        }
+    }
+
+    let (exe, elf) = match (fs::read(exe_path), fs::read(&elf_path)) {
+        (Ok(exe), Ok(elf)) => (exe, elf),
+        (Err(error), _)    => return fail(exe_path, error),
+        (_, Err(error))    => return fail(&elf_name, error),
+    };
+    let elf = match Elf::parse(&elf) {
+        Ok(elf)    => elf,
+        Err(error) => return fail(&elf_name, error),
+    };
+    let map = match map_path.as_deref().map(|map| Map::load(Path::new(map))).transpose() {
+        Ok(map)    => map.unwrap_or_else(|| Map::from_elf(&elf)),
+        Err(error) => return fail(map_path.as_deref().unwrap_or_default(), error),
+    };
+
+    let fixed = match fix(&exe, &elf, &map) {
+        Ok(fixed)  => fixed,
+        Err(error) => return fail(exe_path, error),
+    };
+
+    for patch in &fixed.patches {
+        let site = match patch.fixup {
+            Fixup::Nop(_) => symbolize(&map, patch.address),
+            _             => format!("{} -> {:08x}", symbolize(&map, patch.address), patch.moved),
+        };
+        println!("{site}: {}", patch.fixup);
+    }
+
+    let nops = fixed.layout.nops.len();
+    if nops == 0 {
+        eprintln!("{exe_path}: no hazards, nothing to patch");
+        return ExitCode::SUCCESS;
+    }
+
+    if let Err(error) = fs::write(&out_path, &fixed.file) {
+        return fail(&out_path.display().to_string(), error);
+    }
+    eprintln!("{}: {nops} nops inserted, {} sites patched, .data moved by {:#x} and .bss by {:#x}",
+        out_path.display(), fixed.patches.len(), fixed.layout.data_shift, fixed.layout.bss_shift);
+    ExitCode::SUCCESS
+}
+
+fn usage() -> ExitCode {
+    eprintln!("Usage: nopfix <app.exe> [<app.elf>] [--map <app.map>] [--out <fixed.exe>]");
+    ExitCode::from(2)
+}
+
+fn fail(path: &str, error: impl std::fmt::Display) -> ExitCode {
+    eprintln!("{path}: {error}");
+    ExitCode::FAILURE
+}
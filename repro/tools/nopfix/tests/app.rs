@@ -0,0 +1,32 @@
+use std::{fs, path::Path};
+
+use disasm::{linked_elf, map::Map};
+use emu::{Machine, Stop};
+use nopfix::fix;
+use psxexe::{Exe, elf::Elf};
+use sdk::peripheral::serial_bus::{VirtualPort, virtual_port::{VirtualDevice, VirtualDigitalPad, VirtualPad}};
+
+const RELEASE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/mipsel-sony-psx/release");
+
+/// Patches the release build of the app and runs it in `emu` with a digital pad plugged in
+///
+/// Build it first with `cargo +nightly psx_build --release`, the ELF `psx-ld` linked it from has
+/// the relocations.
+#[test]
+#[ignore = "needs `cargo +nightly psx_build --release`"]
+fn patched_app_detects_the_controller() {
+    let file = fs::read(format!("{RELEASE}/app.exe")).expect("no app.exe, build it with `cargo +nightly psx_build --release`");
+    let elf  = fs::read(linked_elf(Path::new(&format!("{RELEASE}/app.exe"))).expect("no ELF linked app.exe")).unwrap();
+    let elf  = Elf::parse(&elf).unwrap();
+
+    let fixed = fix(&file, &elf, &Map::from_elf(&elf)).unwrap();
+    assert!(!fixed.layout.nops.is_empty(), "the build has no hazards to patch");
+    let exe   = Exe::parse(&fixed.file).unwrap();
+
+    let port        = VirtualPort::new(Some(VirtualDevice::Pad(VirtualPad::DigitalPad(VirtualDigitalPad::new()))));
+    let mut machine = Machine::new(&exe, port).unwrap();
+    let stop        = machine.run(10_000_000, |tty| tty.contains('\n')).unwrap();
+
+    assert_eq!(stop, Stop::Condition);
+    assert_eq!(machine.tty, "Good!\n");
+}
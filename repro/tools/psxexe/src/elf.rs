@@ -0,0 +1,243 @@
+//! Reads the sections, symbols and relocations of the ELF `psx-ld` links the PS-X EXE from
+
+use std::fmt;
+
+/// The relocation is ignored
+pub const R_MIPS_NONE: u8 = 0;
+/// A 32 bit word holding an address
+pub const R_MIPS_32: u8 = 2;
+/// The 26 bit target of a `j` or `jal`
+pub const R_MIPS_26: u8 = 4;
+/// The upper half of an address, `lui` with `%hi`
+pub const R_MIPS_HI16: u8 = 5;
+/// The lower half of an address, `addiu` or a memory offset with `%lo`
+pub const R_MIPS_LO16: u8 = 6;
+
+/// A section with content in the file
+pub const SHT_PROGBITS: u32 = 1;
+/// A section without content in the file like `.bss`
+pub const SHT_NOBITS: u32 = 8;
+/// The section takes memory at runtime
+pub const SHF_ALLOC: u32 = 2;
+/// The section contains instructions
+pub const SHF_EXECINSTR: u32 = 4;
+/// The symbol is a variable
+pub const STT_OBJECT: u8 = 1;
+/// The symbol is a function
+pub const STT_FUNC: u8 = 2;
+/// The section index of absolute symbols like `RAM_SIZE`
+pub const SHN_ABS: u16 = 0xFFF1;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_RELA: u32 = 4;
+const SHT_REL: u32 = 9;
+const EM_MIPS: u16 = 8;
+
+/// Why a file is no ELF that can be patched
+#[derive(Debug, PartialEq)]
+pub enum ElfError {
+    /// The file does not start with the magic of a 32 bit little endian ELF
+    NoElf,
+    /// The ELF is not for MIPS
+    NoMips{machine: u16},
+    /// A header or section reaches past the end of the file
+    Truncated{what: &'static str},
+    /// The relocations carry explicit addends, `ld.lld` only writes those for MIPS64
+    Rela{section: String},
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfError::NoElf             => write!(f, "no 32 bit little endian ELF"),
+            ElfError::NoMips{machine}   => write!(f, "machine {machine} is no MIPS"),
+            ElfError::Truncated{what}   => write!(f, "{what} reaches past the end of the file"),
+            ElfError::Rela{section}     => write!(f, "{section} has explicit addends"),
+        }
+    }
+}
+
+/// The fields of a section header that are needed
+struct Header {
+    name:    u32,
+    kind:    u32,
+    flags:   u32,
+    address: u32,
+    offset:  u32,
+    size:    u32,
+    link:    u32,
+    info:    u32,
+    align:   u32,
+}
+
+/// A section of the ELF
+#[derive(Debug, Clone)]
+pub struct Section<'a> {
+    /// The name like `.text`
+    pub name:    String,
+    /// The type like `SHT_NOBITS`
+    pub kind:    u32,
+    /// The flags like `SHF_ALLOC`
+    pub flags:   u32,
+    /// The virtual address, `0` if the section is not loaded
+    pub address: u32,
+    /// The size, also for `.bss`
+    pub size:    u32,
+    /// The alignment of the address
+    pub align:   u32,
+    /// The content, empty for `.bss`
+    pub data:    &'a [u8],
+}
+
+impl Section<'_> {
+    /// Returns: The address right after the section
+    pub fn end(&self) -> u32 {
+        self.address.wrapping_add(self.size)
+    }
+
+    /// Returns: `true` if the content of the section has to be loaded into memory
+    pub fn is_loaded(&self) -> bool {
+        self.flags & SHF_ALLOC != 0 && self.kind != SHT_NOBITS && self.size > 0
+    }
+}
+
+/// A symbol of `.symtab`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// The mangled name
+    pub name:    String,
+    /// The address
+    pub value:   u32,
+    /// The size in bytes, `0` if unknown
+    pub size:    u32,
+    /// The type like `STT_FUNC`
+    pub kind:    u8,
+    /// The index of the section the symbol is defined in, `SHN_ABS` for absolute symbols
+    pub section: u16,
+}
+
+/// A relocation `ld.lld` kept with `--emit-relocs`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    /// The index of the section that is patched
+    pub section: usize,
+    /// The address that is patched
+    pub offset:  u32,
+    /// The type like `R_MIPS_HI16`
+    pub kind:    u8,
+    /// The index of the symbol in `Elf::symbols`
+    pub symbol:  usize,
+}
+
+/// The parts of an ELF needed to move code around after linking
+#[derive(Debug, Default)]
+pub struct Elf<'a> {
+    /// The entry point of the header, `0` if `ld.lld` did not find the `ENTRY` symbol
+    pub entry:       u32,
+    /// All sections in the order of the section headers
+    pub sections:    Vec<Section<'a>>,
+    /// The symbols of `.symtab`
+    pub symbols:     Vec<Symbol>,
+    /// The relocations of all `.rel` sections in their order
+    pub relocations: Vec<Relocation>,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses an executable linked with `--emit-relocs`
+    ///
+    /// Arguments:
+    /// * `file`: The content of the ELF
+    ///
+    /// Returns: The `Elf` or why `file` is none
+    pub fn parse(file: &'a [u8]) -> Result<Elf<'a>, ElfError> {
+        if file.len() < 0x34 || &file[..6] != b"\x7FELF\x01\x01" {
+            return Err(ElfError::NoElf);
+        }
+
+        let machine = half(file, 0x12).ok_or(ElfError::NoElf)?;
+        if machine != EM_MIPS {
+            return Err(ElfError::NoMips{machine});
+        }
+
+        let offset  = word(file, 0x20).ok_or(ElfError::NoElf)? as usize;
+        let count   = half(file, 0x30).ok_or(ElfError::NoElf)? as usize;
+        let strings = half(file, 0x32).ok_or(ElfError::NoElf)? as usize;
+
+        let mut headers = Vec::new();
+        for idx in 0..count {
+            let header = file.get(offset + idx*40..offset + (idx + 1)*40).ok_or(ElfError::Truncated{what: "section header"})?;
+            let field  = |offset: usize| word(header, offset).unwrap();
+            headers.push(Header{
+                name: field(0), kind: field(4), flags: field(8), address: field(12), offset: field(16), size: field(20), link: field(24), info: field(28), align: field(32)
+            });
+        }
+
+        let bytes = |header: &Header| -> Result<&'a [u8], ElfError> {
+            match header.kind {
+                SHT_NOBITS => Ok(&[]),
+                _          => file.get(header.offset as usize..header.offset as usize + header.size as usize).ok_or(ElfError::Truncated{what: "section"}),
+            }
+        };
+        let table = |idx: usize| headers.get(idx).map(bytes).transpose().map(Option::unwrap_or_default);
+        let names = table(strings)?;
+
+        let mut elf = Elf{entry: word(file, 0x18).unwrap(), ..Elf::default()};
+        for header in &headers {
+            elf.sections.push(Section{
+                name: string(names, header.name), kind: header.kind, flags: header.flags, address: header.address, size: header.size, align: header.align, data: bytes(header)?
+            });
+        }
+
+        for header in &headers {
+            match header.kind {
+                SHT_SYMTAB => {
+                    let names = table(header.link as usize)?;
+                    for symbol in bytes(header)?.chunks_exact(16) {
+                        elf.symbols.push(Symbol{
+                            name:    string(names, word(symbol, 0).unwrap()),
+                            value:   word(symbol, 4).unwrap(),
+                            size:    word(symbol, 8).unwrap(),
+                            kind:    symbol[12] & 0x0F,
+                            section: half(symbol, 14).unwrap(),
+                        });
+                    }
+                },
+                SHT_REL    => {
+                    for relocation in bytes(header)?.chunks_exact(8) {
+                        let info = word(relocation, 4).unwrap();
+                        elf.relocations.push(Relocation{section: header.info as usize, offset: word(relocation, 0).unwrap(), kind: info as u8, symbol: (info >> 8) as usize});
+                    }
+                },
+                SHT_RELA   => return Err(ElfError::Rela{section: string(names, header.name)}),
+                _          => (),
+            }
+        }
+        Ok(elf)
+    }
+
+    /// Returns: The index and the section named `name`
+    pub fn section(&self, name: &str) -> Option<(usize, &Section<'a>)> {
+        self.sections.iter().enumerate().find(|(_, section)| section.name == name)
+    }
+
+    /// Returns: The symbol named `name`
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+}
+
+fn half(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().unwrap()))
+}
+
+fn word(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().unwrap()))
+}
+
+/// Returns: The zero terminated string at `offset` of a string table
+fn string(table: &[u8], offset: u32) -> String {
+    let bytes = table.get(offset as usize..).unwrap_or_default();
+    let end   = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
@@ -0,0 +1,507 @@
+//! Writes the PS-X EXE of a linked ELF instead of assembling the header byte by byte in
+//! `ps-exe.ld`
+//!
+//! The fields come from the symbols the linker script defines: the PC is `__startup`, the GP
+//! `_gp` and SP and FP start at `STACK_INIT`. The text segment are the loaded sections from
+//! the lowest address on, padded to whole sectors for loading from ISO. A `.psx_exe_header`
+//! the linker script still assembles is not copied, only its region marker is kept.
+//!
+//! Everything the linker script can not check fails the build: sections overlapping or not
+//! aligned, a load address outside of the RAM the BIOS leaves to the EXE, a PC outside of the
+//! code, `.bss` running into the stack and a region marker not matching the requested region.
+//!
+//! `check` reads the header back from any PS-X EXE and validates it the same way: the load
+//! address in the RAM after the BIOS, a text segment of whole sectors as the `.data` padding of
+//! `ps-exe.ld` promises, the PC in the text segment, the stack in the RAM and a known region.
+//!
+//! `Header::parse` and `Exe::parse` are the readers of the PS-X EXE, and `elf` the reader of the
+//! ELF, for all the tools.
+
+pub mod elf;
+
+use std::{fmt, ops::Range, str::FromStr};
+
+use crate::elf::{Elf, SHF_ALLOC, SHF_EXECINSTR, Section};
+
+/// The size of the header in front of the text segment
+pub const HEADER_SIZE: usize = 0x800;
+/// The EXE has to be a whole number of CD-ROM sectors
+pub const SECTOR: usize = 2048;
+/// The RAM of the console in KSEG0
+pub const RAM: Range<u32> = 0x8000_0000..0x8020_0000;
+/// The BIOS keeps its data in the first 64 KiB of the RAM
+pub const BIOS_END: u32 = 0x8001_0000;
+/// The section the header of `ps-exe.ld` is assembled in
+pub const HEADER_SECTION: &str = ".psx_exe_header";
+
+const MAGIC: &[u8; 8] = b"PS-X EXE";
+/// The offset of the region marker in the header
+const MARKER: usize = 0x4C;
+
+/// The region a disc is licensed for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    /// `NA`, the SCEA discs
+    NorthAmerica,
+    /// `EU`, the SCEE discs
+    Europe,
+    /// `JP`, the SCEI discs
+    Japan,
+}
+
+impl Region {
+    /// Returns: The marker the BIOS of the region expects in the header
+    pub fn marker(self) -> &'static str {
+        match self {
+            Region::NorthAmerica => "Sony Computer Entertainment Inc. for North America area",
+            Region::Europe       => "Sony Computer Entertainment Inc. for Europe area",
+            Region::Japan        => "Sony Computer Entertainment Inc. for Japan area",
+        }
+    }
+
+    /// Returns: The region of `marker` or `None` if it is none of the three
+    pub fn from_marker(marker: &str) -> Option<Region> {
+        [Region::NorthAmerica, Region::Europe, Region::Japan].into_iter().find(|region| region.marker() == marker)
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(region: &str) -> Result<Region, String> {
+        match region.to_ascii_uppercase().as_str() {
+            "NA" => Ok(Region::NorthAmerica),
+            "EU" => Ok(Region::Europe),
+            "JP" => Ok(Region::Japan),
+            _    => Err(format!("unknown region {region}, expected NA, EU or JP")),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Region::NorthAmerica => write!(f, "NA"),
+            Region::Europe       => write!(f, "EU"),
+            Region::Japan        => write!(f, "JP"),
+        }
+    }
+}
+
+/// The fields of a PS-X EXE header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    /// The initial PC
+    pub pc:           u32,
+    /// The initial GP
+    pub gp:           u32,
+    /// The address the text segment is loaded to
+    pub load_addr:    u32,
+    /// The size of the text segment without the header
+    pub size:         u32,
+    /// The initial SP and FP
+    pub stack:        u32,
+    /// Added to `stack` by the BIOS
+    pub stack_offset: u32,
+    /// The ASCII region marker, can be empty
+    pub marker:       String,
+}
+
+impl Header {
+    /// Returns: The header as it is written in front of the text segment
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE];
+        bytes[..MAGIC.len()].copy_from_slice(MAGIC);
+        for (offset, value) in [(0x10, self.pc), (0x14, self.gp), (0x18, self.load_addr), (0x1C, self.size), (0x30, self.stack), (0x34, self.stack_offset)] {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let marker = &self.marker.as_bytes()[..self.marker.len().min(HEADER_SIZE - MARKER - 1)];
+        bytes[MARKER..MARKER + marker.len()].copy_from_slice(marker);
+        bytes
+    }
+
+    /// Parses the header in front of `file`
+    ///
+    /// Arguments:
+    /// * `file`: The content of the PS-X EXE, only the first `HEADER_SIZE` bytes are read
+    ///
+    /// Returns: The `Header` or why `file` has none
+    pub fn parse(file: &[u8]) -> Result<Header, HeaderError> {
+        if file.len() < HEADER_SIZE {
+            return Err(HeaderError::TooSmall{size: file.len()});
+        }
+        if &file[..MAGIC.len()] != MAGIC {
+            return Err(HeaderError::NoMagic);
+        }
+
+        let word = |offset: usize| u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap());
+        Ok(Header{pc: word(0x10), gp: word(0x14), load_addr: word(0x18), size: word(0x1C), stack: word(0x30), stack_offset: word(0x34), marker: marker(&file[..HEADER_SIZE])})
+    }
+
+    /// Returns: The region of the marker or `None` if it is empty or unknown
+    pub fn region(&self) -> Option<Region> {
+        Region::from_marker(&self.marker)
+    }
+
+    /// Returns: The addresses the text segment is loaded to
+    pub fn text(&self) -> Range<u32> {
+        self.load_addr..self.load_addr.saturating_add(self.size)
+    }
+
+    /// Returns: The initial SP and FP, the base plus the offset
+    pub fn sp(&self) -> u32 {
+        self.stack.wrapping_add(self.stack_offset)
+    }
+
+    /// Checks the fields against what the BIOS and loading from ISO need
+    ///
+    /// Arguments:
+    /// * `length`: The size of the whole file including the header
+    ///
+    /// Returns: Everything wrong with the header, empty if nothing is
+    pub fn validate(&self, length: usize) -> Vec<HeaderError> {
+        let mut errors = Vec::new();
+        let text       = self.text();
+
+        if !self.load_addr.is_multiple_of(4) || !(BIOS_END..RAM.end).contains(&self.load_addr) {
+            errors.push(HeaderError::LoadAddress{address: self.load_addr});
+        }
+        else if self.load_addr.checked_add(self.size).is_none_or(|end| end > RAM.end) {
+            errors.push(HeaderError::OutsideRam{end: self.load_addr.wrapping_add(self.size)});
+        }
+
+        // The `.data` padding of `ps-exe.ld` keeps the size a whole number of sectors
+        if !(self.size as usize).is_multiple_of(SECTOR) {
+            errors.push(HeaderError::Size{size: self.size});
+        }
+        if length != HEADER_SIZE + self.size as usize {
+            errors.push(HeaderError::Length{size: self.size, length});
+        }
+
+        if !self.pc.is_multiple_of(4) || !text.contains(&self.pc) {
+            errors.push(HeaderError::Entry{pc: self.pc, text});
+        }
+
+        // The BIOS keeps its own stack if the base is `0`
+        let stack = self.sp();
+        if self.stack != 0 && (!stack.is_multiple_of(4) || !(RAM.start..=RAM.end).contains(&stack)) {
+            errors.push(HeaderError::Stack{stack});
+        }
+
+        if !self.marker.is_empty() && self.region().is_none() {
+            errors.push(HeaderError::Marker{marker: self.marker.clone()});
+        }
+        errors
+    }
+}
+
+/// What is wrong with the header of a PS-X EXE
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderError {
+    /// The file is smaller than the header
+    TooSmall{size: usize},
+    /// The file does not start with `PS-X EXE`
+    NoMagic,
+    /// The text segment reaches past the end of the file
+    Truncated{size: u32, available: usize},
+    /// The text segment does not start word aligned in the RAM after the BIOS
+    LoadAddress{address: u32},
+    /// The text segment does not fit into the RAM
+    OutsideRam{end: u32},
+    /// The size of the text segment is not a whole number of sectors
+    Size{size: u32},
+    /// The file is not the header followed by the text segment
+    Length{size: u32, length: usize},
+    /// The PC is not word aligned in the text segment
+    Entry{pc: u32, text: Range<u32>},
+    /// The initial SP is not word aligned in the RAM
+    Stack{stack: u32},
+    /// The marker is none of the three regions
+    Marker{marker: String},
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::TooSmall{size}              => write!(f, "{size} bytes are smaller than the {HEADER_SIZE} byte header"),
+            HeaderError::NoMagic                     => write!(f, "does not start with \"PS-X EXE\""),
+            HeaderError::Truncated{size, available}  => write!(f, "the text segment is {size} bytes but only {available} bytes follow the header"),
+            HeaderError::LoadAddress{address}        => write!(f, "the load address {address:08x} is not word aligned between {BIOS_END:08x} and {:08x}", RAM.end),
+            HeaderError::OutsideRam{end}             => write!(f, "the text segment ends at {end:08x}, past the end of the RAM at {:08x}", RAM.end),
+            HeaderError::Size{size}                  => write!(f, "the text segment is {size:#x} bytes, not a multiple of {SECTOR}"),
+            HeaderError::Length{size, length}        => write!(f, "the file is {length:#x} bytes instead of the header and {size:#x} bytes of text segment"),
+            HeaderError::Entry{pc, text}             => write!(f, "the PC {pc:08x} is not word aligned in the text segment at {:08x}..{:08x}", text.start, text.end),
+            HeaderError::Stack{stack}                => write!(f, "the initial SP {stack:08x} is not word aligned in the RAM"),
+            HeaderError::Marker{marker}              => write!(f, "the region marker \"{marker}\" is none of NA, EU or JP"),
+        }
+    }
+}
+
+/// A PS-X EXE read from a file, split into header and text segment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exe<'a> {
+    /// The header
+    pub header: Header,
+    /// The text segment, this includes `.data`
+    pub text:   &'a [u8],
+}
+
+impl<'a> Exe<'a> {
+    /// Parses the header of `file` and splits off the text segment it describes
+    ///
+    /// Arguments:
+    /// * `file`: The content of the PS-X EXE
+    ///
+    /// Returns: The `Exe` or why `file` is none
+    pub fn parse(file: &'a [u8]) -> Result<Exe<'a>, HeaderError> {
+        let header = Header::parse(file)?;
+        let text   = file[HEADER_SIZE..].get(..header.size as usize).ok_or(HeaderError::Truncated{size: header.size, available: file.len() - HEADER_SIZE})?;
+
+        Ok(Exe{header, text})
+    }
+
+    /// Returns: The instruction words of the text segment with their addresses
+    pub fn words(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.text.chunks_exact(4).enumerate().map(|(idx, word)| {
+            (self.header.load_addr.wrapping_add(idx as u32*4), u32::from_le_bytes(word.try_into().unwrap()))
+        })
+    }
+}
+
+/// Parses and validates the header of `file`
+///
+/// Arguments:
+/// * `file`: The content of the PS-X EXE
+///
+/// Returns: The `Header` or everything wrong with it
+pub fn check(file: &[u8]) -> Result<Header, Vec<HeaderError>> {
+    let header = Header::parse(file).map_err(|error| vec![error])?;
+    let errors = header.validate(file.len());
+    if errors.is_empty() { Ok(header) } else { Err(errors) }
+}
+
+/// What `build` takes besides the ELF
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// The symbol the PC starts at
+    pub entry:  String,
+    /// The initial SP and FP, `STACK_INIT` of the ELF if `None`
+    pub stack:  Option<u32>,
+    /// The region to write the marker of, the one of the ELF is kept if `None`
+    pub region: Option<Region>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options{entry: "__startup".to_owned(), stack: None, region: None}
+    }
+}
+
+/// Why no EXE can be built from an ELF
+#[derive(Debug, PartialEq)]
+pub enum BuildError {
+    /// The ELF does not define the symbol
+    MissingSymbol{name: String},
+    /// The ELF has no section to load
+    NothingLoaded,
+    /// Two sections share addresses
+    Overlap{first: String, second: String},
+    /// The address of the section is not a multiple of its alignment
+    Misaligned{section: String, address: u32, align: u32},
+    /// The text segment does not start word aligned in the RAM after the BIOS
+    LoadAddress{address: u32},
+    /// The section does not fit into the RAM
+    OutsideRam{section: String, end: u32},
+    /// The PC is not word aligned in a section with code
+    Entry{address: u32},
+    /// The sections reach the initial SP
+    Stack{stack: u32, end: u32},
+    /// The ELF carries the marker of another region
+    Region{marker: String, region: Region},
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingSymbol{name}                 => write!(f, "the ELF does not define {name}"),
+            BuildError::NothingLoaded                       => write!(f, "the ELF has no section to load"),
+            BuildError::Overlap{first, second}              => write!(f, "{first} and {second} overlap"),
+            BuildError::Misaligned{section, address, align} => write!(f, "{section} at {address:08x} is not aligned to {align}"),
+            BuildError::LoadAddress{address}                => write!(f, "the load address {address:08x} is not word aligned between {BIOS_END:08x} and {:08x}", RAM.end),
+            BuildError::OutsideRam{section, end}            => write!(f, "{section} ends at {end:08x}, past the end of the RAM at {:08x}", RAM.end),
+            BuildError::Entry{address}                      => write!(f, "the PC {address:08x} is not word aligned in a section with code"),
+            BuildError::Stack{stack, end}                   => write!(f, "the sections end at {end:08x}, past the initial SP at {stack:08x}"),
+            BuildError::Region{marker, region}              => write!(f, "the ELF is marked \"{marker}\" but {region} was requested"),
+        }
+    }
+}
+
+/// A PS-X EXE split into header and text segment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    /// The header
+    pub header:  Header,
+    /// The loaded sections with the gaps between them, padded to whole sectors
+    pub segment: Vec<u8>,
+}
+
+impl Image {
+    /// Returns: The content of the PS-X EXE
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend(&self.segment);
+        bytes
+    }
+}
+
+/// Builds the PS-X EXE of `elf`
+///
+/// Arguments:
+/// * `elf`: The ELF linked with `ps-exe.ld`
+/// * `options`: The entry, stack and region to use
+///
+/// Returns: The `Image` or what is inconsistent about `elf`
+pub fn build(elf: &Elf, options: &Options) -> Result<Image, BuildError> {
+    let symbol = |name: &str| elf.symbol(name).map(|symbol| symbol.value).ok_or_else(|| BuildError::MissingSymbol{name: name.to_owned()});
+
+    // `.bss` takes RAM as well, so it is part of the overlap checks
+    let mut sections: Vec<&Section> = elf.sections.iter().filter(|section| section.flags & SHF_ALLOC != 0 && section.size > 0 && section.name != HEADER_SECTION).collect();
+    sections.sort_by_key(|section| section.address);
+
+    for section in &sections {
+        if section.address % section.align.max(1) != 0 {
+            return Err(BuildError::Misaligned{section: section.name.clone(), address: section.address, align: section.align});
+        }
+        if section.address < RAM.start || section.end() > RAM.end || section.end() < section.address {
+            return Err(BuildError::OutsideRam{section: section.name.clone(), end: section.end()});
+        }
+    }
+    for pair in sections.windows(2) {
+        if pair[0].end() > pair[1].address {
+            return Err(BuildError::Overlap{first: pair[0].name.clone(), second: pair[1].name.clone()});
+        }
+    }
+
+    let loaded: Vec<&Section> = sections.iter().copied().filter(|section| section.is_loaded()).collect();
+    let (Some(first), Some(last)) = (loaded.first(), loaded.last()) else {
+        return Err(BuildError::NothingLoaded);
+    };
+    let load_addr = first.address;
+    if load_addr % 4 != 0 || load_addr < BIOS_END {
+        return Err(BuildError::LoadAddress{address: load_addr});
+    }
+
+    let pc = symbol(&options.entry)?;
+    if pc % 4 != 0 || !loaded.iter().any(|section| section.flags & SHF_EXECINSTR != 0 && (section.address..section.end()).contains(&pc)) {
+        return Err(BuildError::Entry{address: pc});
+    }
+
+    let stack = options.stack.map_or_else(|| symbol("STACK_INIT"), Ok)?;
+    let end   = sections.iter().map(|section| section.end()).max().unwrap_or(load_addr);
+    if end > stack {
+        return Err(BuildError::Stack{stack, end});
+    }
+
+    let mut segment = vec![0; ((last.end() - load_addr) as usize).next_multiple_of(SECTOR)];
+    for section in &loaded {
+        let offset = (section.address - load_addr) as usize;
+        segment[offset..offset + section.data.len()].copy_from_slice(section.data);
+    }
+
+    let found  = elf.section(HEADER_SECTION).map(|(_, header)| marker(header.data)).unwrap_or_default();
+    let marker = match options.region {
+        Some(region) if !found.is_empty() && found != region.marker() => return Err(BuildError::Region{marker: found, region}),
+        Some(region)                                                  => region.marker().to_owned(),
+        None                                                          => found,
+    };
+
+    let header = Header{pc, gp: elf.symbol("_gp").map_or(0, |gp| gp.value), load_addr, size: segment.len() as u32, stack, stack_offset: 0, marker};
+    Ok(Image{header, segment})
+}
+
+/// Returns: The zero terminated region marker of `header`
+pub fn marker(header: &[u8]) -> String {
+    let bytes = header.get(MARKER..).unwrap_or_default();
+    let end   = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf::{SHT_NOBITS, SHT_PROGBITS, Symbol};
+
+    const CODE: [u8; 8] = [0x08, 0x00, 0xE0, 0x03, 0x00, 0x00, 0x00, 0x00];
+
+    fn section(name: &str, kind: u32, flags: u32, address: u32, size: u32, data: &'static [u8]) -> Section<'static> {
+        Section{name: name.to_owned(), kind, flags, address, size, align: 4, data}
+    }
+
+    fn elf(bss: u32) -> Elf<'static> {
+        Elf{
+            entry:       0,
+            sections:    vec![
+                section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0x8001_0000, 8, &CODE),
+                section(".data", SHT_PROGBITS, SHF_ALLOC, 0x8001_0010, 4, &[1, 2, 3, 4]),
+                section(".bss", SHT_NOBITS, SHF_ALLOC, bss, 0x100, &[]),
+            ],
+            symbols:     vec![
+                Symbol{name: "__startup".to_owned(), value: 0x8001_0004, size: 0, kind: 0, section: 1},
+                Symbol{name: "STACK_INIT".to_owned(), value: 0x801F_FF00, size: 0, kind: 0, section: 0xFFF1},
+            ],
+            relocations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn header() {
+        let image = build(&elf(0x8001_0800), &Options{region: Some(Region::Europe), ..Options::default()}).unwrap();
+        let bytes = image.to_bytes();
+        let exe   = Exe::parse(&bytes).unwrap();
+
+        assert_eq!((exe.header.pc, exe.header.gp, exe.header.load_addr, exe.header.sp()), (0x8001_0004, 0, 0x8001_0000, 0x801F_FF00));
+        assert_eq!(exe.text.len(), SECTOR);
+        assert_eq!(&exe.text[..8], CODE);
+        assert_eq!(&exe.text[0x10..0x14], [1, 2, 3, 4]);
+        assert_eq!(marker(&bytes), "Sony Computer Entertainment Inc. for Europe area");
+    }
+
+    #[test]
+    fn validate() {
+        let bytes  = build(&elf(0x8001_0800), &Options{region: Some(Region::Japan), ..Options::default()}).unwrap().to_bytes();
+        let header = check(&bytes).unwrap();
+        assert_eq!((header.pc, header.load_addr, header.size, header.region()), (0x8001_0004, 0x8001_0000, SECTOR as u32, Some(Region::Japan)));
+
+        let broken = Header{pc: 0x8001_0802, load_addr: 0x8000_F000, size: 0x900, stack: 0x8020_0004, marker: "Licensed by Sony".to_owned(), ..header.clone()};
+        assert_eq!(broken.validate(bytes.len()), [
+            HeaderError::LoadAddress{address: 0x8000_F000},
+            HeaderError::Size{size: 0x900},
+            HeaderError::Length{size: 0x900, length: bytes.len()},
+            HeaderError::Entry{pc: 0x8001_0802, text: 0x8000_F000..0x8000_F900},
+            HeaderError::Stack{stack: 0x8020_0004},
+            HeaderError::Marker{marker: "Licensed by Sony".to_owned()},
+        ]);
+        assert_eq!(check(&bytes[..0x100]), Err(vec![HeaderError::TooSmall{size: 0x100}]));
+        assert_eq!(Header::parse(&[0; HEADER_SIZE]), Err(HeaderError::NoMagic));
+    }
+
+    #[test]
+    fn text_segment() {
+        let mut bytes = build(&elf(0x8001_0800), &Options::default()).unwrap().to_bytes();
+        let exe       = Exe::parse(&bytes).unwrap();
+        assert_eq!(exe.words().take(2).collect::<Vec<_>>(), [(0x8001_0000, 0x03E0_0008), (0x8001_0004, 0)]);
+
+        bytes.truncate(HEADER_SIZE + 8);
+        assert_eq!(Exe::parse(&bytes), Err(HeaderError::Truncated{size: SECTOR as u32, available: 8}));
+    }
+
+    #[test]
+    fn inconsistent() {
+        assert_eq!(build(&elf(0x8001_0010), &Options::default()), Err(BuildError::Overlap{first: ".data".to_owned(), second: ".bss".to_owned()}));
+        assert_eq!(build(&elf(0x801F_FF00), &Options::default()), Err(BuildError::Stack{stack: 0x801F_FF00, end: 0x8020_0000}));
+        assert_eq!(build(&elf(0x8001_0800), &Options{entry: "main".to_owned(), ..Options::default()}), Err(BuildError::MissingSymbol{name: "main".to_owned()}));
+        assert_eq!(build(&elf(0x8001_0800), &Options{stack: Some(0x8001_0400), ..Options::default()}), Err(BuildError::Stack{stack: 0x8001_0400, end: 0x8001_0900}));
+    }
+}
@@ -0,0 +1,111 @@
+//! `psxexe <app.elf> <app.exe> [--region <NA|EU|JP>] [--entry <symbol>] [--stack <address>]`
+//!
+//! Writes the PS-X EXE of an ELF linked with `ps-exe.ld`, like the `deps/app-<hash>.elf` `psx-ld` keeps.
+//! The PC is `__startup` unless `--entry` names another symbol, SP and FP start at `STACK_INIT`
+//! unless `--stack` gives a hex address. Fails without writing anything if the ELF is
+//! inconsistent.
+//!
+//! `psxexe --check <app.exe>...`
+//!
+//! Prints the header fields of every PS-X EXE and everything wrong with them. Fails if any of
+//! them has a problem.
+
+use std::{env, fs, process::ExitCode};
+
+use psxexe::{Header, Options, build, elf::Elf};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let [flag, exe_paths @ ..] = args.as_slice() && flag == "--check" && !exe_paths.is_empty() {
+        return check(exe_paths);
+    }
+
+    let [elf_path, exe_path, flags @ ..] = args.as_slice() else {
+        return usage();
+    };
+
+    let mut options = Options::default();
+    for flag in flags.chunks(2) {
+        match flag {
+            [flag, region] if flag == "--region" => match region.parse() {
+                Ok(region) => options.region = Some(region),
+                Err(error) => {
+                    eprintln!("{error}");
+                    return usage();
+                },
+            },
+            [flag, entry] if flag == "--entry"   => options.entry = entry.clone(),
+            [flag, stack] if flag == "--stack"   => match u32::from_str_radix(stack.trim_start_matches("0x"), 16) {
+                Ok(stack) => options.stack = Some(stack),
+                Err(_)    => return usage(),
+            },
+            _                                    => return usage(),
+        }
+    }
+
+    let file = match fs::read(elf_path) {
+        Ok(file)   => file,
+        Err(error) => return fail(elf_path, error),
+    };
+    let elf = match Elf::parse(&file) {
+        Ok(elf)    => elf,
+        Err(error) => return fail(elf_path, error),
+    };
+    let image = match build(&elf, &options) {
+        Ok(image)  => image,
+        Err(error) => return fail(elf_path, error),
+    };
+
+    if let Err(error) = fs::write(exe_path, image.to_bytes()) {
+        return fail(exe_path, error);
+    }
+
+    print(exe_path, &image.header);
+    ExitCode::SUCCESS
+}
+
+/// Validates the header of every EXE in `exe_paths`
+fn check(exe_paths: &[String]) -> ExitCode {
+    let mut code = ExitCode::SUCCESS;
+    for exe_path in exe_paths {
+        let file = match fs::read(exe_path) {
+            Ok(file)   => file,
+            Err(error) => {
+                code = fail(exe_path, error);
+                continue;
+            },
+        };
+        let header = match Header::parse(&file) {
+            Ok(header) => header,
+            Err(error) => {
+                code = fail(exe_path, error);
+                continue;
+            },
+        };
+
+        print(exe_path, &header);
+        for error in header.validate(file.len()) {
+            code = fail(exe_path, error);
+        }
+    }
+    code
+}
+
+fn print(exe_path: &str, header: &Header) {
+    println!("{exe_path}: pc {:08x}, gp {:08x}, load address {:08x}, size {:#x}, sp {:08x}", header.pc, header.gp, header.load_addr, header.size, header.sp());
+    match header.marker.as_str() {
+        ""     => println!("{exe_path}: no region marker"),
+        marker => println!("{exe_path}: \"{marker}\""),
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("Usage: psxexe <app.elf> <app.exe> [--region <NA|EU|JP>] [--entry <symbol>] [--stack <address>]");
+    eprintln!("       psxexe --check <app.exe>...");
+    ExitCode::from(2)
+}
+
+fn fail(path: &str, error: impl std::fmt::Display) -> ExitCode {
+    eprintln!("{path}: {error}");
+    ExitCode::FAILURE
+}
@@ -0,0 +1,285 @@
+//! Finds the line ranges of a Rust source the reducer tries to remove
+//!
+//! There is no real parser behind this, only the nesting of brackets and a look at how lines
+//! start. Ranges that are no complete item, arm or statement just fail to build and are kept.
+
+/// What a candidate is, in the order the reducer tries them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Functions, types, impls, modules, `use` and the like with their attributes
+    Item,
+    /// Arms of a `match`
+    Arm,
+    /// Variants of an enum, like the ones of `ControllerState` and `ControllerType`
+    Variant,
+    /// Statements and block expressions in functions
+    Statement,
+    /// Runs of comment lines
+    Comment,
+    /// Blank lines next to other blank lines, braces or the end of the file
+    Blank,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 6] = [Kind::Item, Kind::Arm, Kind::Variant, Kind::Statement, Kind::Comment, Kind::Blank];
+}
+
+const ITEMS:      [&str; 12] = ["fn ", "struct ", "enum ", "impl ", "impl<", "mod ", "trait ", "const ", "static ", "use ", "type ", "macro_rules!"];
+const QUALIFIERS: [&str; 8]  = ["pub(crate) ", "pub(super) ", "pub ", "const ", "unsafe ", "async ", "extern \"\" ", "default "];
+const BLOCKS:     [&str; 7]  = ["if ", "while ", "for ", "loop ", "match ", "unsafe{", "unsafe {"];
+
+/// Returns: `line` without comments and with empty string and char literals, so brackets in
+/// them don't count
+pub fn code(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars  = line.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '/' if chars.peek() == Some(&'/') => break,
+            '"' => {
+                while let Some(char) = chars.next() {
+                    match char {
+                        '\\' => {
+                            chars.next();
+                        },
+                        '"'  => break,
+                        _    => {},
+                    }
+                }
+                result.push_str("\"\"");
+            },
+            // A char literal, a lifetime has no closing quote after one char
+            '\'' => {
+                let mut lookahead = chars.clone();
+                let literal = match lookahead.next() {
+                    Some('\\') => lookahead.find(|char| *char == '\'').is_some(),
+                    Some(_)    => lookahead.next() == Some('\''),
+                    None       => false,
+                };
+                if literal {
+                    chars = lookahead;
+                    result.push_str("' '");
+                }
+                else {
+                    result.push('\'');
+                }
+            },
+            _   => result.push(char),
+        }
+    }
+    result
+}
+
+/// Returns: The last line of what starts at `start`, it ends when the brackets opened in it are
+/// closed again or with `terminator` if none were opened
+fn extent(codes: &[String], start: usize, terminator: char) -> Option<usize> {
+    let mut depth  = 0;
+    let mut opened = false;
+
+    for idx in start..codes.len() {
+        for char in codes[idx].chars() {
+            match char {
+                '(' | '[' | '{' => {
+                    depth += 1;
+                    opened = true;
+                },
+                ')' | ']' | '}' => {
+                    depth -= 1;
+                    if depth < 0 {
+                        return None;
+                    }
+                },
+                _               => {},
+            }
+        }
+
+        let line = codes[idx].trim();
+        // The last element of a list doesn't need a terminator
+        let last = next_code(codes, idx + 1).is_some_and(|next| codes[next].trim_start().starts_with(['}', ')', ']']));
+        if depth == 0 && !line.is_empty() && (opened || line.ends_with(terminator) || last) {
+            return Some(idx);
+        }
+    }
+    None
+}
+
+/// Returns: The first line from `start` on with code
+fn next_code(codes: &[String], start: usize) -> Option<usize> {
+    (start..codes.len()).find(|idx| !codes[*idx].trim().is_empty())
+}
+
+/// Returns: The first line of the attributes and doc comments in front of `start`
+fn with_attributes(lines: &[String], start: usize) -> usize {
+    let mut first = start;
+    while first > 0 && matches!(lines[first - 1].trim_start(), line if line.starts_with("#[") || line.starts_with("///")) {
+        first -= 1;
+    }
+    first
+}
+
+fn is_item(code: &str) -> bool {
+    let mut code = code.trim_start();
+    while let Some(rest) = QUALIFIERS.iter().find_map(|qualifier| code.strip_prefix(qualifier)) {
+        code = rest;
+    }
+    // `const fn` and `unsafe fn` lose their qualifier above
+    ITEMS.iter().any(|item| code.starts_with(item)) || code.starts_with("fn ")
+}
+
+fn is_arm(code: &str) -> bool {
+    code.contains("=>") && !code.trim_start().starts_with("macro_rules!")
+}
+
+/// Finds the candidates of `kind` in the source `lines`
+///
+/// Returns: The first and last line of every candidate, sorted
+pub fn find(lines: &[String], kind: Kind) -> Vec<(usize, usize)> {
+    let codes: Vec<String> = lines.iter().map(|line| code(line)).collect();
+    let mut ranges = Vec::new();
+
+    match kind {
+        Kind::Item      => {
+            for start in (0..codes.len()).filter(|idx| is_item(&codes[*idx])) {
+                ranges.extend(extent(&codes, start, ';').map(|end| (with_attributes(lines, start), end)));
+            }
+        },
+        Kind::Arm       => {
+            for start in (0..codes.len()).filter(|idx| is_arm(&codes[*idx]) && !is_item(&codes[*idx])) {
+                ranges.extend(extent(&codes, start, ',').map(|end| (start, end)));
+            }
+        },
+        Kind::Variant   => {
+            for (start, end) in find(lines, Kind::Item).into_iter().filter(|(start, _)| (*start..).find(|idx| !lines[*idx].trim_start().starts_with(['#', '/'])).is_some_and(|idx| codes[idx].contains("enum "))) {
+                let Some(open) = (start..=end).find(|idx| codes[*idx].contains('{')) else {
+                    continue;
+                };
+
+                let mut idx = open + 1;
+                while idx < end {
+                    let line = codes[idx].trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        idx += 1;
+                        continue;
+                    }
+                    let Some(last) = extent(&codes, idx, ',').filter(|last| *last < end) else {
+                        break;
+                    };
+                    ranges.push((with_attributes(lines, idx), last));
+                    idx = last + 1;
+                }
+            }
+        },
+        Kind::Statement => {
+            for start in 0..codes.len() {
+                let line     = codes[start].trim();
+                let previous = (0..start).rev().find(|idx| !codes[*idx].trim().is_empty()).map(|idx| codes[idx].trim_end());
+                let starts   = previous.is_some_and(|previous| previous.ends_with([';', '{', '}']));
+                if !starts || line.is_empty() || line.starts_with(['}', ')', ']', '.', '#']) || line.starts_with("else") || is_item(line) || is_arm(line) {
+                    continue;
+                }
+
+                let Some(mut end) = extent(&codes, start, ';') else {
+                    continue;
+                };
+                // `if` chains with `else` on the next line
+                while BLOCKS.iter().any(|block| line.starts_with(block)) && let Some(next) = next_code(&codes, end + 1) && codes[next].trim_start().starts_with("else") {
+                    let Some(last) = extent(&codes, next, ';') else {
+                        break;
+                    };
+                    end = last;
+                }
+                // Tail expressions can't go and fields or variants are no statements
+                if codes[end].trim_end().ends_with([';', '}']) {
+                    ranges.push((start, end));
+                }
+            }
+        },
+        Kind::Comment   => {
+            let mut idx = 0;
+            while idx < lines.len() {
+                let comment = |idx: usize| lines[idx].trim_start().starts_with("//");
+                if !comment(idx) {
+                    idx += 1;
+                    continue;
+                }
+                let end = (idx..lines.len()).take_while(|idx| comment(*idx)).last().unwrap_or(idx);
+                ranges.push((idx, end));
+                idx = end + 1;
+            }
+        },
+        Kind::Blank     => {
+            let blank = |idx: usize| lines[idx].trim().is_empty();
+            for idx in (0..lines.len()).filter(|idx| blank(*idx)) {
+                let after  = idx == 0 || blank(idx - 1) || lines[idx - 1].trim_end().ends_with('{');
+                let before = idx + 1 == lines.len() || lines[idx + 1].trim_start().starts_with('}');
+                if after || before {
+                    ranges.push((idx, idx));
+                }
+            }
+        },
+    }
+
+    ranges.sort_unstable();
+    ranges.dedup();
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"use crate::printf;
+
+/// The state
+#[derive(Clone, Copy)]
+pub enum State {
+    New,
+    Stable(u8),
+    Config{id: u8}
+}
+
+pub const fn process(state: State) -> u8 {
+    let brace = '{';
+    match state {
+        State::New       => 0,
+        State::Stable(x) => {
+            // The `}` doesn't count
+            printf("}");
+            x
+        },
+        State::Config{id} => id,
+    }
+}
+
+fn check(value: bool) {
+    if value {
+        printf("a");
+
+    }
+
+    else {
+        printf("b");
+    }
+    printf("c");
+}
+"#;
+
+    fn find_in(kind: Kind) -> Vec<(usize, usize)> {
+        let lines: Vec<String> = SOURCE.lines().map(String::from).collect();
+        find(&lines, kind)
+    }
+
+    #[test]
+    fn find_candidates() {
+        assert_eq!(code(r#"    printf("}"); let c = '{'; // {"#), r#"    printf(""); let c = ' '; "#);
+        assert_eq!(code("fn f<'a>(x: &'a u8)"), "fn f<'a>(x: &'a u8)");
+
+        assert_eq!(find_in(Kind::Item), [(0, 0), (2, 8), (10, 21), (23, 33)]);
+        assert_eq!(find_in(Kind::Variant), [(5, 5), (6, 6), (7, 7)]);
+        assert_eq!(find_in(Kind::Arm), [(13, 13), (14, 18), (19, 19)]);
+        assert_eq!(find_in(Kind::Statement), [(11, 11), (12, 20), (16, 16), (24, 31), (25, 25), (30, 30), (32, 32)]);
+        assert_eq!(find_in(Kind::Comment), [(2, 2), (15, 15)]);
+        assert_eq!(find_in(Kind::Blank), [(26, 26)]);
+    }
+}
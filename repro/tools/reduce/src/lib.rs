@@ -0,0 +1,201 @@
+//! Delta debugging on the sources of the workspace
+//!
+//! The `Reducer` removes items, match arms, enum variants, statements and comments in ever
+//! smaller chunks and keeps every removal after which the `Oracle` still sees the bug. What is
+//! left is the minimal source for a bug report.
+
+pub mod candidates;
+
+use std::path::PathBuf;
+
+use crate::candidates::Kind;
+
+/// A source file being reduced
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    /// The path relative to the workspace
+    pub path:  PathBuf,
+    pub lines: Vec<String>,
+}
+
+/// Decides if a reduced workspace still shows the bug
+pub trait Oracle {
+    /// Checks `sources`
+    ///
+    /// Arguments:
+    /// * `sources`: All sources
+    /// * `changed`: The indices of the sources that changed since the last call
+    ///
+    /// Returns: `true` if the bug is still there
+    fn holds(&mut self, sources: &[Source], changed: &[usize]) -> bool;
+}
+
+impl<F: FnMut(&[Source], &[usize]) -> bool> Oracle for F {
+    fn holds(&mut self, sources: &[Source], changed: &[usize]) -> bool {
+        self(sources, changed)
+    }
+}
+
+/// A candidate of a `Kind` in one of the sources
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    source: usize,
+    start:  usize,
+    end:    usize,
+}
+
+pub struct Reducer<O: Oracle> {
+    pub sources: Vec<Source>,
+    /// The number of times the oracle was asked
+    pub runs:    usize,
+    oracle:      O,
+    /// The sources the oracle saw in another state than `sources` during the last run
+    stale:       Vec<usize>,
+    /// Called with a line of progress whenever something was removed
+    log:         Box<dyn FnMut(&str)>,
+}
+
+impl<O: Oracle> Reducer<O> {
+    /// Creates a new `Reducer`
+    ///
+    /// Arguments:
+    /// * `sources`: The sources to reduce
+    /// * `oracle`: Decides if a reduced version still shows the bug
+    /// * `log`: Gets a line of progress whenever something was removed
+    ///
+    /// Returns: The new `Reducer`
+    pub fn new(sources: Vec<Source>, oracle: O, log: impl FnMut(&str) + 'static) -> Reducer<O> {
+        Reducer{sources, runs: 0, oracle, stale: Vec::new(), log: Box::new(log)}
+    }
+
+    /// Returns: The number of lines in all sources
+    pub fn lines(&self) -> usize {
+        self.sources.iter().map(|source| source.lines.len()).sum()
+    }
+
+    /// Removes whatever can go until nothing more can
+    ///
+    /// Returns: `false` if the oracle doesn't hold for the sources as they were given
+    pub fn run(&mut self) -> bool {
+        let all: Vec<usize> = (0..self.sources.len()).collect();
+        self.runs += 1;
+        if !self.oracle.holds(&self.sources, &all) {
+            return false;
+        }
+
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for kind in Kind::ALL {
+                progress |= self.reduce(kind);
+            }
+        }
+        true
+    }
+
+    fn candidates(&self, kind: Kind) -> Vec<Candidate> {
+        self.sources.iter().enumerate().flat_map(|(source, file)| {
+            candidates::find(&file.lines, kind).into_iter().map(move |(start, end)| Candidate{source, start, end})
+        }).collect()
+    }
+
+    /// Tries to remove the candidates of `kind` in chunks, halving the chunks until single ones
+    ///
+    /// Returns: `true` if anything was removed
+    fn reduce(&mut self, kind: Kind) -> bool {
+        let mut removed = false;
+        let mut chunk   = self.candidates(kind).len();
+
+        while chunk > 0 {
+            let mut idx = 0;
+            loop {
+                // Every removal moves the lines, so the candidates are searched again
+                let candidates = self.candidates(kind);
+                if idx >= candidates.len() {
+                    break;
+                }
+
+                let end = (idx + chunk).min(candidates.len());
+                if self.try_remove(&candidates[idx..end]) {
+                    removed = true;
+                    let progress = format!("{kind:?}: removed {} of {} candidates, {} lines left after {} runs", end - idx, candidates.len(), self.lines(), self.runs);
+                    (self.log)(&progress);
+                }
+                else {
+                    idx = end;
+                }
+            }
+            chunk /= 2;
+        }
+        removed
+    }
+
+    /// Removes the lines of `candidates` if the oracle still holds without them
+    fn try_remove(&mut self, candidates: &[Candidate]) -> bool {
+        let mut reduced = self.sources.clone();
+        let mut changed = Vec::new();
+
+        for (source, file) in reduced.iter_mut().enumerate() {
+            let mut keep = vec![true; file.lines.len()];
+            for candidate in candidates.iter().filter(|candidate| candidate.source == source) {
+                keep[candidate.start..=candidate.end].fill(false);
+            }
+            if keep.contains(&false) {
+                let mut keep = keep.into_iter();
+                file.lines.retain(|_| keep.next().unwrap_or(true));
+                changed.push(source);
+            }
+        }
+        if changed.is_empty() {
+            return false;
+        }
+
+        let mut written = changed.clone();
+        written.extend(self.stale.drain(..).filter(|source| !changed.contains(source)));
+        self.runs += 1;
+
+        if self.oracle.holds(&reduced, &written) {
+            self.sources = reduced;
+            true
+        }
+        else {
+            self.stale = changed;
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduce_to_the_bug() {
+        let source = "\
+fn main() {
+    let a = 1;
+    // The bug needs this
+    let bug = 2;
+    if a == 1 {
+        noise();
+    }
+    match a {
+        1 => bug(),
+        _ => {},
+    }
+}
+
+fn noise() {}
+";
+        let sources = vec![Source{path: PathBuf::from("main.rs"), lines: source.lines().map(String::from).collect()}];
+        // The bug shows as long as `bug` is there and the braces match
+        let oracle  = |sources: &[Source], _: &[usize]| {
+            let text = sources[0].lines.join("\n");
+            text.contains("let bug") && text.matches('{').count() == text.matches('}').count()
+        };
+
+        let mut reducer = Reducer::new(sources, oracle, |_| {});
+        assert!(reducer.run());
+        assert_eq!(reducer.sources[0].lines, ["fn main() {", "    let bug = 2;", "}"]);
+    }
+}
@@ -0,0 +1,102 @@
+//! `reduce --oracle <command> [--out <dir>] [--verbose]`
+//!
+//! Copies `app` and `sdk` to `<dir>`, `reduce` in the temporary directory by default, and
+//! reduces their `.rs` files as long as `sh -c <command>` run in `<dir>` exits with `0`. The
+//! oracle finds this workspace in `$WORKSPACE` to use the other tools, for example:
+//!
+//! `reduce --oracle 'cargo +nightly psx_build --release -q && $WORKSPACE/target/release/emu target/mipsel-sony-psx/release/app.exe --expect Bad...'`
+//!
+//! A build failure has to fail the oracle, most removals don't compile.
+
+use std::{env, fs, io, path::{Path, PathBuf}, process::{Command, ExitCode, Stdio}};
+
+use reduce::{Reducer, Source};
+use variants::workspace::{self, WORKSPACE};
+
+/// The directories with the sources to reduce
+const SOURCES: [&str; 2] = ["app/src", "sdk/src"];
+
+struct Options {
+    oracle:  String,
+    out:     PathBuf,
+    verbose: bool,
+}
+
+fn parse_options() -> Option<Options> {
+    let mut args    = env::args().skip(1);
+    let mut options = Options{oracle: String::new(), out: env::temp_dir().join("reduce"), verbose: false};
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--oracle"  => options.oracle = args.next()?,
+            "--out"     => options.out = PathBuf::from(args.next()?),
+            "--verbose" => options.verbose = true,
+            _           => return None,
+        }
+    }
+    (!options.oracle.is_empty()).then_some(options)
+}
+
+fn main() -> ExitCode {
+    let Some(options) = parse_options() else {
+        eprintln!("Usage: reduce --oracle <command> [--out <dir>] [--verbose]");
+        return ExitCode::from(2);
+    };
+
+    let sources = match workspace::copy(&options.out).and_then(|_| read_sources(&options.out)) {
+        Ok(sources) => sources,
+        Err(error)  => {
+            eprintln!("{}: {error}", options.out.display());
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let out    = options.out.clone();
+    let oracle = move |sources: &[Source], changed: &[usize]| {
+        for source in changed.iter().map(|idx| &sources[*idx]) {
+            if let Err(error) = fs::write(out.join(&source.path), source.lines.join("\n") + "\n") {
+                eprintln!("{}: {error}", source.path.display());
+                return false;
+            }
+        }
+
+        let output = match options.verbose {
+            true  => Stdio::inherit,
+            false => Stdio::null,
+        };
+        Command::new("sh").arg("-c").arg(&options.oracle).current_dir(&out).env("WORKSPACE", Path::new(WORKSPACE))
+            .stdout(output()).stderr(output()).status().is_ok_and(|status| status.success())
+    };
+
+    let mut reducer = Reducer::new(sources, oracle, |progress| eprintln!("{progress}"));
+    let lines       = reducer.lines();
+    if !reducer.run() {
+        eprintln!("The oracle fails for the unchanged sources, it has to succeed as long as the bug is there");
+        return ExitCode::FAILURE;
+    }
+
+    eprintln!("{lines} lines reduced to {} after {} runs, the result is in {}", reducer.lines(), reducer.runs, options.out.display());
+    ExitCode::SUCCESS
+}
+
+/// Returns: Every `.rs` file in `SOURCES` below `root`
+fn read_sources(root: &Path) -> io::Result<Vec<Source>> {
+    let mut paths   = Vec::new();
+    let mut pending: Vec<PathBuf> = SOURCES.iter().map(PathBuf::from).collect();
+
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(root.join(&dir))? {
+            let path = dir.join(entry?.file_name());
+            match root.join(&path).is_dir() {
+                true  => pending.push(path),
+                false => paths.extend(path.extension().is_some_and(|extension| extension == "rs").then_some(path)),
+            }
+        }
+    }
+    paths.sort();
+
+    paths.into_iter().map(|path| {
+        let lines = fs::read_to_string(root.join(&path))?.lines().map(String::from).collect();
+        Ok(Source{path, lines})
+    }).collect()
+}
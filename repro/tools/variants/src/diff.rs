@@ -0,0 +1,114 @@
+//! A line diff in the unified format of `diff -u`
+
+/// One line of the diff
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Diffs `old` against `new` through their longest common subsequence
+///
+/// Returns: The lines of both in order, the removed ones of a change before the added ones
+pub fn lines<'a>(old: &'a [String], new: &'a [String]) -> Vec<Line<'a>> {
+    // `common[i][j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true  => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            result.push(Line::Same(&old[i]));
+            (i, j) = (i + 1, j + 1);
+        }
+        else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            result.push(Line::Removed(&old[i]));
+            i += 1;
+        }
+        else {
+            result.push(Line::Added(&new[j]));
+            j += 1;
+        }
+    }
+    result
+}
+
+/// Returns: The number of added and removed lines
+pub fn counts(lines: &[Line]) -> (usize, usize) {
+    lines.iter().fold((0, 0), |(added, removed), line| match line {
+        Line::Same(_)    => (added, removed),
+        Line::Removed(_) => (added, removed + 1),
+        Line::Added(_)   => (added + 1, removed),
+    })
+}
+
+/// Formats `lines` as hunks with `context` unchanged lines around every change
+///
+/// Returns: The hunks without the file header, empty if nothing changed
+pub fn unified(lines: &[Line], context: usize) -> String {
+    // The line numbers in `old` and `new` before every line
+    let positions: Vec<(usize, usize)> = lines.iter().scan((0, 0), |(old, new), line| {
+        let position = (*old, *new);
+        match line {
+            Line::Same(_)    => (*old, *new) = (*old + 1, *new + 1),
+            Line::Removed(_) => *old += 1,
+            Line::Added(_)   => *new += 1,
+        }
+        Some(position)
+    }).collect();
+
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for idx in (0..lines.len()).filter(|idx| !matches!(lines[*idx], Line::Same(_))) {
+        let (start, end) = (idx.saturating_sub(context), (idx + context + 1).min(lines.len()));
+        match hunks.last_mut() {
+            Some(last) if last.1 >= start => last.1 = end,
+            _                             => hunks.push((start, end)),
+        }
+    }
+
+    let mut result = String::new();
+    for (start, end) in hunks {
+        let (old, new)     = positions[start];
+        let (added, removed) = counts(&lines[start..end]);
+        let same           = end - start - added - removed;
+        result += &format!("@@ -{},{} +{},{} @@\n", old + 1, same + removed, new + 1, same + added);
+
+        for line in &lines[start..end] {
+            result += &match line {
+                Line::Same(text)    => format!(" {text}\n"),
+                Line::Removed(text) => format!("-{text}\n"),
+                Line::Added(text)   => format!("+{text}\n"),
+            };
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(text: &str) -> Vec<String> {
+        text.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn unified_hunks() {
+        let old = strings("a b c d e f g h i");
+        let new = strings("a x c d e f g h j");
+        let lines = lines(&old, &new);
+
+        assert_eq!(counts(&lines), (2, 2));
+        assert_eq!(unified(&lines, 1), "@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n@@ -8,2 +8,2 @@\n h\n-i\n+j\n");
+        assert_eq!(unified(&lines, 3), "@@ -1,9 +1,9 @@\n a\n-b\n+x\n c\n d\n e\n f\n g\n h\n-i\n+j\n");
+    }
+}
@@ -0,0 +1,104 @@
+//! Cuts single functions out of the `.s` and `.ll` rustc emits
+
+/// What `find` looks through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// LLVM MIPS assembly, functions end with `.end <symbol>`
+    Assembly,
+    /// LLVM IR, functions are `define`d and end with a lone `}`
+    Ir,
+}
+
+/// Checks if the mangled `symbol` names the function `name`
+///
+/// Both the legacy and the v0 mangling encode identifiers with their length in front, which
+/// keeps `update_controller` from matching `update_controller_on`.
+fn names(symbol: &str, name: &str) -> bool {
+    symbol.contains(&format!("{}{name}", name.len()))
+}
+
+/// Finds the function `name` in `text`
+///
+/// Arguments:
+/// * `text`: The content of a `.s` or `.ll` file
+/// * `format`: What `text` is
+/// * `name`: The unmangled name of the function, like `update_controller`
+///
+/// Returns: The normalized lines of the first matching function or `None` if it was inlined
+/// everywhere
+pub fn find(text: &str, format: Format, name: &str) -> Option<Vec<String>> {
+    let mut lines = text.lines();
+
+    let body: Vec<&str> = match format {
+        Format::Assembly => {
+            let symbol = lines.by_ref().filter_map(|line| line.strip_suffix(':')).find(|symbol| !symbol.starts_with(['\t', ' ', '$', '.']) && names(symbol, name))?;
+            let end    = format!(".end\t{symbol}");
+            lines.take_while(|line| line.trim() != end).collect()
+        },
+        Format::Ir       => {
+            let define = lines.by_ref().find(|line| line.starts_with("define ") && line.split('@').nth(1).is_some_and(|symbol| names(symbol.split('(').next().unwrap_or_default(), name)))?;
+            std::iter::once(define).chain(lines.take_while(|line| *line != "}")).collect()
+        },
+    };
+    Some(body.into_iter().map(normalize).collect())
+}
+
+/// Drops what differs between builds without a change of the code: the crate disambiguators
+/// of v0 symbols, the hashes of legacy symbols and the function numbers in local labels
+pub fn normalize(line: &str) -> String {
+    let line = strip(line, "Cs", |char| char.is_ascii_alphanumeric(), Some('_'));
+    let line = strip(&line, "17h", |char| matches!(char, '0'..='9' | 'a'..='f'), Some('E'));
+    let line = strip(&line, "$BB", |char| char.is_ascii_digit(), Some('_'));
+    strip(&line, "$func_end", |char| char.is_ascii_digit(), None)
+}
+
+/// Removes the run of `allowed` characters after every `prefix` if it is followed by `terminator`
+fn strip(line: &str, prefix: &str, allowed: fn(char) -> bool, terminator: Option<char>) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest   = line;
+
+    while let Some(idx) = rest.find(prefix) {
+        let (before, after) = rest.split_at(idx + prefix.len());
+        result.push_str(before);
+
+        let run  = after.find(|char| !allowed(char)).unwrap_or(after.len());
+        let next = after[run..].chars().next();
+        rest = match run > 0 && (terminator.is_none() || next == terminator) {
+            true  => &after[run..],
+            false => after,
+        };
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_functions() {
+        let assembly = "\
+_RNvNtCsgEGWVYSu1zn_3sdk10peripheral20update_controller_on:
+\tjr\t$ra
+\t.end\t_RNvNtCsgEGWVYSu1zn_3sdk10peripheral20update_controller_on
+_RNvNtCsgEGWVYSu1zn_3sdk10peripheral17update_controller:
+\tj\t$BB1_27
+\t.end\t_RNvNtCsgEGWVYSu1zn_3sdk10peripheral17update_controller
+";
+        assert_eq!(find(assembly, Format::Assembly, "update_controller").unwrap(), ["\tj\t$BB_27"]);
+        assert_eq!(find(assembly, Format::Assembly, "process_existing_controller"), None);
+
+        let ir = "\
+define internal fastcc void @_ZN3sdk10peripheral17update_controller17he5a85756627a568dE() unnamed_addr #4 {
+start:
+  ret void
+}
+";
+        assert_eq!(find(ir, Format::Ir, "update_controller").unwrap(), [
+            "define internal fastcc void @_ZN3sdk10peripheral17update_controller17hE() unnamed_addr #4 {",
+            "start:",
+            "  ret void",
+        ]);
+    }
+}
@@ -0,0 +1,147 @@
+//! Rebuilds the app with the variations of the controller code the bug depends on
+//!
+//! `Bad`, `Good0` and `Good2` were produced by hand by editing `CONTROLLER_SLOT_COUNT`. A
+//! `Variant` patches a copy of `peripheral/mod.rs` instead, so every slot count and both ways
+//! of walking the slots in `process_port` can be regenerated and compared function by function.
+
+pub mod diff;
+pub mod function;
+pub mod workspace;
+
+/// The slot counts every `Walk` is built with
+pub const SLOT_COUNTS: [usize; 4] = [0, 1, 2, 4];
+/// The functions compared between the variants
+pub const FUNCTIONS: [&str; 2] = ["update_controller", "process_existing_controller"];
+
+/// The files `Variant::patch` changes, relative to the workspace
+pub const PATCHED: [&str; 2] = ["sdk/src/peripheral/mod.rs", "sdk/src/peripheral/controller/digital_controller.rs"];
+
+/// How `process_port` walks the slots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Walk {
+    /// `for idx in 0..CONTROLLER_SLOT_COUNT`, which works
+    Index,
+    /// `port_slots.iter_mut()`, which breaks the `is_some` check
+    IterMut,
+}
+
+/// One build of the app, `Bad` is `slots1-iter_mut`, `Good0` and `Good2` are `slots0` and
+/// `slots2-iter_mut`
+///
+/// Without slots `CONTROLLERS_A` is a single `ControllerSlot` instead of an array like in
+/// `Good0`, there is nothing to walk then.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variant {
+    pub slots: usize,
+    pub walk:  Walk,
+}
+
+impl Variant {
+    /// The configuration of the tree as it is checked in
+    pub const BASELINE: Variant = Variant{slots: 1, walk: Walk::IterMut};
+
+    /// Returns: Every slot count with every `Walk`, the single slot only once
+    pub fn all() -> Vec<Variant> {
+        SLOT_COUNTS.iter().flat_map(|&slots| match slots {
+            0 => vec![Variant{slots, walk: Walk::Index}],
+            _ => vec![Variant{slots, walk: Walk::Index}, Variant{slots, walk: Walk::IterMut}],
+        }).collect()
+    }
+
+    /// Returns: The name of the folder the variant is built into, like `slots1-iter_mut`
+    pub fn label(&self) -> String {
+        match (self.slots, self.walk) {
+            (0, _)              => String::from("slots0"),
+            (slots, Walk::Index)   => format!("slots{slots}-index"),
+            (slots, Walk::IterMut) => format!("slots{slots}-iter_mut"),
+        }
+    }
+
+    /// Rewrites one of the `PATCHED` files for the variant
+    ///
+    /// Arguments:
+    /// * `file`: The path of the file in `PATCHED`
+    /// * `source`: The content of the file
+    ///
+    /// Returns: The patched source or `None` if what has to be patched was not found
+    pub fn patch(&self, file: &str, source: &str) -> Option<String> {
+        match file {
+            "sdk/src/peripheral/mod.rs"                           => self.patch_peripheral(source),
+            "sdk/src/peripheral/controller/digital_controller.rs" => match self.slots {
+                0 => source.contains("CONTROLLERS_A[0]").then(|| source.replace("CONTROLLERS_A[0]", "CONTROLLERS_A")),
+                _ => Some(source.to_string()),
+            },
+            _                                                     => None,
+        }
+    }
+
+    /// Rewrites `CONTROLLER_SLOT_COUNT`, `CONTROLLERS_A` and `process_port`
+    fn patch_peripheral(&self, source: &str) -> Option<String> {
+        let lines: Vec<&str> = source.lines().collect();
+        let count  = lines.iter().position(|line| line.starts_with("const CONTROLLER_SLOT_COUNT"))?;
+        let global = lines.iter().position(|line| line.starts_with("static mut CONTROLLERS_A"))?;
+        let start  = lines.iter().position(|line| line.starts_with("fn process_port"))?;
+        let end    = start + lines[start..].iter().position(|line| *line == "}")?;
+
+        let (signature, body): (String, &[&str]) = match (self.slots, self.walk) {
+            (0, _)            => (lines[start].replace("port_slots: &mut [ControllerSlot; CONTROLLER_SLOT_COUNT]", "slot: &mut ControllerSlot"), &[
+                "    process_controller(serial_connection, &mut slot.controller, &slot.configuration);",
+            ]),
+            (_, Walk::Index)   => (lines[start].to_string(), &[
+                "    for idx in 0..CONTROLLER_SLOT_COUNT {",
+                "        let slot = &mut port_slots[idx];",
+                "        process_controller(serial_connection, &mut slot.controller, &slot.configuration);",
+                "    }",
+            ]),
+            (_, Walk::IterMut) => (lines[start].to_string(), &[
+                "    for slot in port_slots.iter_mut() {",
+                "        process_controller(serial_connection, &mut slot.controller, &slot.configuration);",
+                "    }",
+            ]),
+        };
+
+        let slot_count = format!("const CONTROLLER_SLOT_COUNT:usize = {};", self.slots);
+        let single     = "static mut CONTROLLERS_A: ControllerSlot = ControllerSlot::new();";
+        let patched: Vec<&str> = lines.iter().enumerate().filter(|(idx, _)| *idx <= start || *idx >= end).flat_map(|(idx, line)| match idx {
+            _ if idx == count                     => vec![slot_count.as_str()],
+            _ if idx == global && self.slots == 0 => vec![single],
+            _ if idx == start                     => std::iter::once(signature.as_str()).chain(body.iter().copied()).collect(),
+            _                                     => vec![*line],
+        }).collect();
+        Some(patched.join("\n") + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_slot_count_and_walk() {
+        let source  = include_str!("../../../sdk/src/peripheral/mod.rs");
+        let patched = Variant{slots: 4, walk: Walk::Index}.patch(PATCHED[0], source).unwrap();
+
+        assert!(patched.contains("const CONTROLLER_SLOT_COUNT:usize = 4;\n"));
+        assert!(patched.contains("    for idx in 0..CONTROLLER_SLOT_COUNT {\n        let slot = &mut port_slots[idx];"));
+        assert!(!patched.contains("iter_mut()"));
+        assert!(patched.contains("fn process_controller"));
+
+        let baseline = Variant::BASELINE.patch(PATCHED[0], source).unwrap();
+        assert!(baseline.contains("const CONTROLLER_SLOT_COUNT:usize = 1;\n"));
+        assert!(baseline.contains("fn process_port<B: SerialBus>(serial_connection: &mut SerialConnection<B>, port_slots: &mut [ControllerSlot; CONTROLLER_SLOT_COUNT]) {\n    for slot in port_slots.iter_mut() {\n        process_controller(serial_connection, &mut slot.controller, &slot.configuration);"));
+    }
+
+    #[test]
+    fn patch_single_slot() {
+        let single = Variant{slots: 0, walk: Walk::Index};
+        let peripheral = single.patch(PATCHED[0], include_str!("../../../sdk/src/peripheral/mod.rs")).unwrap();
+
+        assert!(peripheral.contains("static mut CONTROLLERS_A: ControllerSlot = ControllerSlot::new();\n"));
+        assert!(peripheral.contains("const CONTROLLER_SLOT_COUNT:usize = 0;\n"));
+        assert!(peripheral.contains("slot: &mut ControllerSlot) {\n    process_controller(serial_connection, &mut slot.controller, &slot.configuration);\n}\n"));
+
+        let digital_controller = single.patch(PATCHED[1], include_str!("../../../sdk/src/peripheral/controller/digital_controller.rs")).unwrap();
+        assert!(digital_controller.contains("unsafe{&raw mut CONTROLLERS_A}"));
+        assert_eq!(single.label(), "slots0");
+    }
+}
@@ -0,0 +1,159 @@
+//! `variants [--out <dir>] [--baseline <label>] [--no-build]`
+//!
+//! Builds the app for every `Variant` with `--emit asm,llvm-ir` into `<dir>/<label>/app.s` and
+//! `app.ll` next to the `app.map` of the link, `target/variants` by default. Every build is a copy of `app` and `sdk` with a
+//! patched `peripheral` module in the temporary directory, the tree itself is left alone. Then `update_controller` and
+//! `process_existing_controller` of every variant are diffed against the `--baseline`,
+//! `slots1-iter_mut` unless given, into `<dir>/diff/<label>/`. `--no-build` only diffs what was
+//! built before.
+
+use std::{env, fs, io, path::{Path, PathBuf}, process::{Command, ExitCode}};
+
+use variants::{FUNCTIONS, PATCHED, Variant, diff, function::{self, Format}, workspace::{self, WORKSPACE}};
+
+const DEFAULT_OUT: &str = "target/variants";
+const TARGET:      &str = "mipsel-sony-psx";
+const FORMATS:     [(Format, &str); 2] = [(Format::Assembly, "s"), (Format::Ir, "ll")];
+
+struct Options {
+    out:      PathBuf,
+    baseline: String,
+    build:    bool,
+}
+
+fn parse_options() -> Option<Options> {
+    let mut args    = env::args().skip(1);
+    let mut options = Options{out: Path::new(WORKSPACE).join(DEFAULT_OUT), baseline: Variant::BASELINE.label(), build: true};
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out"      => options.out = PathBuf::from(args.next()?),
+            "--baseline" => options.baseline = args.next()?,
+            "--no-build" => options.build = false,
+            _            => return None,
+        }
+    }
+    Some(options)
+}
+
+fn main() -> ExitCode {
+    let Some(options) = parse_options() else {
+        eprintln!("Usage: variants [--out <dir>] [--baseline <label>] [--no-build]");
+        return ExitCode::from(2);
+    };
+    let variants = Variant::all();
+    if !variants.iter().any(|variant| variant.label() == options.baseline) {
+        eprintln!("{}: no such variant", options.baseline);
+        return ExitCode::from(2);
+    }
+
+    if options.build {
+        for variant in &variants {
+            eprintln!("Building {}", variant.label());
+            if let Err(error) = build(variant, &options.out) {
+                eprintln!("{}: {error}", variant.label());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    for variant in variants.iter().filter(|variant| variant.label() != options.baseline) {
+        if let Err(error) = compare(&options.baseline, &variant.label(), &options.out) {
+            eprintln!("{}: {error}", variant.label());
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Builds `variant` from a patched copy of the workspace into `<out>/<label>`
+fn build(variant: &Variant, out: &Path) -> io::Result<()> {
+    let dir  = out.join(variant.label());
+    let copy = env::temp_dir().join("variants").join(variant.label());
+    workspace::copy(&copy)?;
+
+    for file in PATCHED {
+        let path    = copy.join(file);
+        let patched = variant.patch(file, &fs::read_to_string(&path)?).ok_or_else(|| io::Error::other(format!("{file} can't be patched")))?;
+        fs::write(&path, patched)?;
+    }
+    fs::create_dir_all(&dir)?;
+
+    // All variants share one target directory to build `core` only once, the old emitted files
+    // have to go to find the new ones
+    let target = out.join("target");
+    let deps   = target.join(TARGET).join("release/deps");
+    for (_, extension) in FORMATS {
+        for file in emitted(&deps, extension)? {
+            fs::remove_file(file)?;
+        }
+    }
+
+    let status = Command::new("cargo").args(["+nightly", "psx_rustc", "-p", "app", "--release", "--", "--emit", "asm,llvm-ir,link"])
+        .current_dir(&copy).env("CARGO_TARGET_DIR", &target).status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("cargo failed with {status}")));
+    }
+
+    for (_, extension) in FORMATS {
+        let file = emitted(&deps, extension)?.pop().ok_or_else(|| io::Error::other(format!("rustc emitted no .{extension}")))?;
+        fs::copy(file, dir.join(format!("app.{extension}")))?;
+    }
+    // `-Map=target/app.map` is relative to the copy the build ran in
+    fs::copy(copy.join("target/app.map"), dir.join("app.map"))?;
+    Ok(())
+}
+
+/// Returns: The `app-<hash>.<extension>` files rustc emitted into `deps`
+fn emitted(deps: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+    if !deps.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(deps)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if name.starts_with("app-") && path.extension().is_some_and(|found| found == extension) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Diffs the `FUNCTIONS` of `label` against `baseline` and prints how much changed
+fn compare(baseline: &str, label: &str, out: &Path) -> io::Result<()> {
+    let dir = out.join("diff").join(label);
+    fs::create_dir_all(&dir)?;
+
+    for name in FUNCTIONS {
+        let mut summary = format!("{label:16} {name:28}");
+
+        for (format, extension) in FORMATS {
+            let file = format!("app.{extension}");
+            let old  = function::find(&fs::read_to_string(out.join(baseline).join(&file))?, format, name);
+            let new  = function::find(&fs::read_to_string(out.join(label).join(&file))?, format, name);
+
+            let (old, new) = match (old, new) {
+                (None, None) => {
+                    summary += &format!(" .{extension} inlined");
+                    continue;
+                },
+                (old, new)   => (old.unwrap_or_default(), new.unwrap_or_default()),
+            };
+            let lines            = diff::lines(&old, &new);
+            let (added, removed) = diff::counts(&lines);
+            summary += &format!(" .{extension} +{added} -{removed}");
+
+            let path = dir.join(format!("{name}.{extension}.diff"));
+            match added + removed {
+                0 => if path.exists() {
+                    fs::remove_file(path)?;
+                },
+                _ => fs::write(path, format!("--- {baseline}/{file}\n+++ {label}/{file}\n{}", diff::unified(&lines, 3)))?,
+            }
+        }
+        println!("{summary}");
+    }
+    Ok(())
+}
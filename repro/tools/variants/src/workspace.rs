@@ -0,0 +1,52 @@
+//! Copies of the workspace with only what a console build needs
+
+use std::{fs, io, path::Path};
+
+/// The workspace the tools are part of
+pub const WORKSPACE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../..");
+/// The part of the workspace a console build needs
+const COPIED:    [&str; 7] = ["app", "sdk", ".cargo", "Cargo.lock", "ps-exe.ld", "psx-ld", "riscv.ld"];
+
+/// Copies `app`, `sdk` and the build configuration of the workspace to `to`
+///
+/// `to` has to be outside of the workspace, cargo would merge the `.cargo/config.toml` of the
+/// workspace into the one of the copy otherwise and run every alias twice.
+///
+/// Arguments:
+/// * `to`: The directory to copy to, replaced if it exists
+pub fn copy(to: &Path) -> io::Result<()> {
+    let workspace = Path::new(WORKSPACE);
+    if to.exists() {
+        fs::remove_dir_all(to)?;
+    }
+    for entry in COPIED {
+        copy_all(&workspace.join(entry), &to.join(entry))?;
+    }
+
+    // The tools are host only and not copied
+    let manifest = fs::read_to_string(workspace.join("Cargo.toml"))?;
+    let manifest: Vec<&str> = manifest.lines().filter(|line| !line.starts_with("default-members")).map(|line| match line.starts_with("members") {
+        true  => r#"members = ["app", "sdk"]"#,
+        false => line,
+    }).collect();
+    fs::write(to.join("Cargo.toml"), manifest.join("\n") + "\n")?;
+
+    // The linker writes the map relative to the workspace
+    fs::create_dir_all(to.join("target"))
+}
+
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_file() {
+        fs::create_dir_all(to.parent().unwrap_or(to))?;
+        return fs::copy(from, to).map(|_| ());
+    }
+
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name() != "target" {
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}